@@ -1,3 +1,3 @@
 pub mod process_control;
 
-pub use process_control::{start_monitor};
\ No newline at end of file
+pub use process_control::{find_banned_windows, find_suspicious_processes, start_monitor};
\ No newline at end of file
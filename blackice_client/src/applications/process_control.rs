@@ -2,7 +2,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::thread;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{channel, Sender};
 use std::collections::HashSet;
 
 use windows::core::BOOL;
@@ -13,6 +13,7 @@ use windows::Win32::UI::WindowsAndMessaging::{
 };
 
 use crate::cloud_reporter::{AppLogs, ViolationType};
+use crate::evidence::ScreenRect;
 
 
 // strict path binding
@@ -91,6 +92,27 @@ pub fn start_monitor(keep_running: Arc<AtomicBool>, tx: Sender<AppLogs>) {
     let _ = tx.send(AppLogs::Info("[application]: MONITOR STOPPED".to_string()));
 }
 
+// one-shot scans for the pre-exam readiness check
+
+// (matched banned word, window title, pid) of every visible banned window
+pub fn find_banned_windows() -> Vec<(&'static str, String, u32)> {
+    let mut found: Vec<(&'static str, String, u32)> = Vec::new();
+    let param = LPARAM(&mut found as *mut Vec<(&'static str, String, u32)> as isize);
+    unsafe {
+        let _ = EnumWindows(Some(collect_window_callback), param);
+    }
+    found
+}
+
+// masquerading / unknown processes, the same messages the monitor reports
+pub fn find_suspicious_processes() -> Vec<AppLogs> {
+    let exact_set: HashSet<&str> = WHITELIST_NAMES.iter().cloned().collect();
+    let (tx, rx) = channel();
+    unsafe { scan_optimized(&tx, &exact_set); }
+    drop(tx);
+    rx.into_iter().collect()
+}
+
 unsafe fn scan_window_titles(tx: &Sender<AppLogs>) {
     let param = LPARAM(tx as *const Sender<AppLogs> as isize);
     EnumWindows(Some(enum_window_callback), param);
@@ -107,13 +129,38 @@ unsafe extern "system" fn enum_window_callback(hwnd: HWND, lparam: LPARAM) -> BO
 
     if len > 0 {
         let title = String::from_utf16_lossy(&buffer[..len as usize]).to_lowercase();
-        for banned_word in BANNED_TITLES {
-            if title.contains(banned_word) {
+        if banned_word(&title).is_some() {
+            let mut pid = 0;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            let tx = &*(lparam.0 as *const Sender<AppLogs>);
+            let msg = format!("[appliaction] [security] BANNED WINDOW: '{}' (PID: {})", title, pid);
+            let _ = match ScreenRect::of_window(hwnd) {
+                Some(rect) => tx.send(AppLogs::WindowViolation(ViolationType::Application, msg, rect)),
+                None => tx.send(AppLogs::Violation(ViolationType::Application, msg)),
+            };
+        }
+    }
+
+    true.into()
+}
+
+// same as above, collects into the Vec behind lparam instead of reporting
+unsafe extern "system" fn collect_window_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() {
+            return true.into();
+        }
+
+        let mut buffer = [0u16; 512];
+        let len = GetWindowTextW(hwnd, &mut buffer);
+
+        if len > 0 {
+            let title = String::from_utf16_lossy(&buffer[..len as usize]).to_lowercase();
+            if let Some(word) = banned_word(&title) {
                 let mut pid = 0;
                 GetWindowThreadProcessId(hwnd, Some(&mut pid));
-                let tx = &*(lparam.0 as *const Sender<AppLogs>);
-                let _ = tx.send(AppLogs::Violation(ViolationType::Application, format!("[appliaction] [security] BANNED WINDOW: '{}' (PID: {})", title, pid)));
-                break; 
+                let found = &mut *(lparam.0 as *mut Vec<(&'static str, String, u32)>);
+                found.push((word, title, pid));
             }
         }
     }
@@ -121,6 +168,10 @@ unsafe extern "system" fn enum_window_callback(hwnd: HWND, lparam: LPARAM) -> BO
     true.into()
 }
 
+fn banned_word(title: &str) -> Option<&'static str> {
+    BANNED_TITLES.iter().copied().find(|word| title.contains(word))
+}
+
 unsafe fn scan_optimized(tx: &Sender<AppLogs>, exact_set: &HashSet<&str>) {
     let snapshot = match CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) {
         Ok(h) => h,
@@ -0,0 +1,47 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CameraError {
+    // no video capture device on the machine
+    NoCamera,
+    // Media Foundation / COM call failed
+    Device { context: &'static str, error: windows::core::Error },
+    // the device stopped delivering frames (unplugged, taken by another app)
+    EndOfStream,
+    // fake frame source files
+    Io(std::io::Error),
+    Decode(String),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::NoCamera => write!(f, "[camera]: No Camera Found"),
+            CameraError::Device { context, error } => write!(f, "[camera]: {} Failed: {}", context, error),
+            CameraError::EndOfStream => write!(f, "[camera]: Camera Stopped Delivering Frames"),
+            CameraError::Io(e) => write!(f, "[camera]: {}", e),
+            CameraError::Decode(msg) => write!(f, "[camera]: Invalid Frame: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CameraError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CameraError::Device { error, .. } => Some(error),
+            CameraError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CameraError {
+    fn from(e: std::io::Error) -> Self {
+        CameraError::Io(e)
+    }
+}
+
+// `.map_err(device("ReadSample"))`
+pub(crate) fn device(context: &'static str) -> impl FnOnce(windows::core::Error) -> CameraError {
+    move |error| CameraError::Device { context, error }
+}
@@ -0,0 +1,208 @@
+use image::RgbaImage;
+use windows::core::PWSTR;
+use windows::Win32::Media::MediaFoundation::*;
+use windows::Win32::System::Com::{CoInitializeEx, CoTaskMemFree, COINIT_MULTITHREADED};
+
+use super::error::{device, CameraError};
+use super::source::{Frame, FrameSource};
+
+// the first video capture device through a Media Foundation source reader, converted to RGB32
+// by the reader. COM objects, so it has to be opened on the thread that reads from it
+
+// the first frames after opening are dark while auto exposure settles
+const WARMUP_FRAMES: usize = 5;
+// ReadSample can return stream ticks without a sample
+const READ_ATTEMPTS: usize = 10;
+
+const VIDEO_STREAM: u32 = MF_SOURCE_READER_FIRST_VIDEO_STREAM.0 as u32;
+
+pub struct MediaFoundationCamera {
+    name: String,
+    source: IMFMediaSource,
+    reader: IMFSourceReader,
+    width: u32,
+    height: u32,
+    // bytes per row, negative for bottom-up images
+    stride: i32,
+    // last, MFShutdown only after the reader and source are released
+    _session: MfSession,
+}
+
+struct MfSession;
+
+impl Drop for MfSession {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = MFShutdown();
+        }
+    }
+}
+
+impl MediaFoundationCamera {
+    pub fn open() -> Result<Self, CameraError> {
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            MFStartup(MF_VERSION, MFSTARTUP_NOSOCKET).map_err(device("MFStartup"))?;
+            Self::open_first_device(MfSession)
+        }
+    }
+
+    unsafe fn open_first_device(session: MfSession) -> Result<Self, CameraError> {
+        unsafe {
+            let mut attributes: Option<IMFAttributes> = None;
+            MFCreateAttributes(&mut attributes, 1).map_err(device("MFCreateAttributes"))?;
+            let attributes = attributes.ok_or(CameraError::NoCamera)?;
+            attributes
+                .SetGUID(&MF_DEVSOURCE_ATTRIBUTE_SOURCE_TYPE, &MF_DEVSOURCE_ATTRIBUTE_SOURCE_TYPE_VIDCAP_GUID)
+                .map_err(device("SetGUID"))?;
+
+            let mut devices: *mut Option<IMFActivate> = std::ptr::null_mut();
+            let mut count = 0u32;
+            MFEnumDeviceSources(&attributes, &mut devices, &mut count).map_err(device("MFEnumDeviceSources"))?;
+            if devices.is_null() {
+                return Err(CameraError::NoCamera);
+            }
+
+            // every activation object is released, the array itself is CoTaskMem
+            let list = std::slice::from_raw_parts_mut(devices, count as usize);
+            let first = list.first().cloned().flatten();
+            for activate in list.iter_mut() {
+                activate.take();
+            }
+            CoTaskMemFree(Some(devices as *const _));
+
+            let activate = first.ok_or(CameraError::NoCamera)?;
+            let name = friendly_name(&activate);
+            let source: IMFMediaSource = activate.ActivateObject().map_err(device("ActivateObject"))?;
+
+            match Self::create_reader(&source) {
+                Ok((reader, width, height, stride)) => {
+                    let mut camera = Self { name, source, reader, width, height, stride, _session: session };
+                    for _ in 0..WARMUP_FRAMES {
+                        camera.read_frame()?;
+                    }
+                    Ok(camera)
+                }
+                Err(e) => {
+                    let _ = source.Shutdown();
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    unsafe fn create_reader(source: &IMFMediaSource) -> Result<(IMFSourceReader, u32, u32, i32), CameraError> {
+        unsafe {
+            // lets the reader convert the camera's YUV / MJPG output to RGB32
+            let mut attributes: Option<IMFAttributes> = None;
+            MFCreateAttributes(&mut attributes, 1).map_err(device("MFCreateAttributes"))?;
+            if let Some(attributes) = &attributes {
+                attributes
+                    .SetUINT32(&MF_SOURCE_READER_ENABLE_VIDEO_PROCESSING, 1)
+                    .map_err(device("SetUINT32"))?;
+            }
+            let reader = MFCreateSourceReaderFromMediaSource(source, attributes.as_ref())
+                .map_err(device("MFCreateSourceReaderFromMediaSource"))?;
+
+            let media_type = MFCreateMediaType().map_err(device("MFCreateMediaType"))?;
+            media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video).map_err(device("SetGUID"))?;
+            media_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_RGB32).map_err(device("SetGUID"))?;
+            reader
+                .SetCurrentMediaType(VIDEO_STREAM, None, &media_type)
+                .map_err(device("SetCurrentMediaType"))?;
+
+            let current = reader.GetCurrentMediaType(VIDEO_STREAM).map_err(device("GetCurrentMediaType"))?;
+            let size = current.GetUINT64(&MF_MT_FRAME_SIZE).map_err(device("MF_MT_FRAME_SIZE"))?;
+            let (width, height) = ((size >> 32) as u32, size as u32);
+            let stride = current
+                .GetUINT32(&MF_MT_DEFAULT_STRIDE)
+                .map(|s| s as i32)
+                .unwrap_or(width as i32 * 4);
+
+            Ok((reader, width, height, stride))
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<RgbaImage, CameraError> {
+        unsafe {
+            for _ in 0..READ_ATTEMPTS {
+                let mut flags = 0u32;
+                let mut sample: Option<IMFSample> = None;
+                self.reader
+                    .ReadSample(VIDEO_STREAM, 0, None, Some(&mut flags), None, Some(&mut sample))
+                    .map_err(device("ReadSample"))?;
+
+                if flags & MF_SOURCE_READERF_ENDOFSTREAM.0 as u32 != 0 {
+                    return Err(CameraError::EndOfStream);
+                }
+                let Some(sample) = sample else { continue };
+
+                let buffer = sample.ConvertToContiguousBuffer().map_err(device("ConvertToContiguousBuffer"))?;
+                let mut data: *mut u8 = std::ptr::null_mut();
+                let mut length = 0u32;
+                buffer.Lock(&mut data, None, Some(&mut length)).map_err(device("Lock"))?;
+                let bytes = std::slice::from_raw_parts(data, length as usize);
+                let image = self.to_rgba(bytes);
+                let _ = buffer.Unlock();
+                return image;
+            }
+            Err(CameraError::EndOfStream)
+        }
+    }
+
+    // BGRX rows (top-down or bottom-up) to RGBA
+    fn to_rgba(&self, bytes: &[u8]) -> Result<RgbaImage, CameraError> {
+        let row = self.width as usize * 4;
+        let pitch = self.stride.unsigned_abs() as usize;
+        if self.height == 0 || pitch < row || bytes.len() < pitch * (self.height as usize - 1) + row {
+            return Err(CameraError::Decode(format!("{} byte frame for {}x{}", bytes.len(), self.width, self.height)));
+        }
+
+        let mut pixels = Vec::with_capacity(row * self.height as usize);
+        for y in 0..self.height as usize {
+            let line = if self.stride < 0 { self.height as usize - 1 - y } else { y };
+            for px in bytes[line * pitch..line * pitch + row].chunks_exact(4) {
+                pixels.extend_from_slice(&[px[2], px[1], px[0], 255]);
+            }
+        }
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| CameraError::Decode("frame size mismatch".into()))
+    }
+}
+
+impl FrameSource for MediaFoundationCamera {
+    fn name(&self) -> String {
+        format!("{} ({}x{})", self.name, self.width, self.height)
+    }
+
+    fn next_frame(&mut self) -> Result<Frame, CameraError> {
+        let image = self.read_frame()?;
+        Ok(Frame { image, captured_at: chrono::Utc::now().timestamp() })
+    }
+}
+
+impl Drop for MediaFoundationCamera {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.source.Shutdown();
+        }
+    }
+}
+
+// Helpers functions
+
+unsafe fn friendly_name(activate: &IMFActivate) -> String {
+    unsafe {
+        let mut name = PWSTR::null();
+        let mut length = 0u32;
+        if activate
+            .GetAllocatedString(&MF_DEVSOURCE_ATTRIBUTE_FRIENDLY_NAME, &mut name, &mut length)
+            .is_err()
+        {
+            return "camera".to_string();
+        }
+        let text = name.to_string().unwrap_or_else(|_| "camera".to_string());
+        CoTaskMemFree(Some(name.0 as *const _));
+        text
+    }
+}
@@ -0,0 +1,11 @@
+mod error;
+mod media_foundation;
+mod monitor;
+mod presence;
+mod source;
+
+pub use error::CameraError;
+pub use media_foundation::*;
+pub use monitor::*;
+pub use presence::*;
+pub use source::*;
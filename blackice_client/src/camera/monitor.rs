@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::error::CameraError;
+use super::presence::PresenceDetector;
+use super::source::FrameSource;
+use crate::cloud_reporter::{AppLogs, ViolationType};
+use crate::evidence::{encode_png, EvidenceUpload};
+
+// periodic webcam snapshots during the exam, checked for a dark / covered / frozen camera.
+// frames with a problem are always uploaded, others only every `upload_every` snapshots
+
+// longest side of an uploaded frame
+const MAX_SIDE: u32 = 640;
+// upper bound for the sleep so the stop signal is noticed
+const STOP_CHECK: Duration = Duration::from_millis(500);
+// failed snapshots in a row before the camera counts as gone
+const FAILURES_BEFORE_VIOLATION: u32 = 3;
+
+// set per session by the proctor (the `webcam` part of the schedule)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraPolicy {
+    pub interval_secs: u64,
+    #[serde(default = "default_upload_every")]
+    pub upload_every: u64,
+}
+
+fn default_upload_every() -> u64 {
+    10
+}
+
+// who the uploaded frames belong to
+pub struct UploadTarget {
+    pub student_id: String,
+    pub session_id: String,
+    pub uploads: Sender<EvidenceUpload>,
+}
+
+// opens the source on the new thread (camera COM objects stay on the thread that made them)
+pub fn spawn_camera_monitor<F>(
+    open: F,
+    policy: CameraPolicy,
+    target: Option<UploadTarget>,
+    keep_running: Arc<AtomicBool>,
+    tx: Sender<AppLogs>,
+) -> JoinHandle<()>
+where
+    F: FnOnce() -> Result<Box<dyn FrameSource>, CameraError> + Send + 'static,
+{
+    thread::spawn(move || match open() {
+        Ok(source) => run_camera_monitor(source, policy, target, keep_running, tx),
+        Err(e) => {
+            let _ = tx.send(AppLogs::Violation(
+                ViolationType::Environment,
+                format!("[camera] CAMERA UNAVAILABLE: {}", e),
+            ));
+        }
+    })
+}
+
+pub fn run_camera_monitor(
+    mut source: Box<dyn FrameSource>,
+    policy: CameraPolicy,
+    target: Option<UploadTarget>,
+    keep_running: Arc<AtomicBool>,
+    tx: Sender<AppLogs>,
+) {
+    let _ = tx.send(AppLogs::Info(format!(
+        "[camera]: Snapshots every {}s from {}",
+        policy.interval_secs,
+        source.name()
+    )));
+
+    let interval = Duration::from_secs(policy.interval_secs.max(1));
+    let mut detector = PresenceDetector::new();
+    let mut snapshots: u64 = 0;
+    let mut failures = 0;
+
+    while keep_running.load(Ordering::Relaxed) {
+        let started = Instant::now();
+
+        match source.next_frame() {
+            Ok(frame) => {
+                failures = 0;
+                let check = detector.check(&frame.image, frame.captured_at);
+                if !check.is_ok() {
+                    let _ = tx.send(AppLogs::Violation(
+                        ViolationType::Environment,
+                        format!("[camera] {}", check.summary()),
+                    ));
+                }
+
+                let due = snapshots.is_multiple_of(policy.upload_every.max(1));
+                if let Some(target) = &target
+                    && (due || !check.is_ok())
+                {
+                    match encode_png(&frame.image, MAX_SIDE) {
+                        Ok((png, width, height)) => {
+                            let _ = target.uploads.send(EvidenceUpload {
+                                kind: "webcam",
+                                content_type: "image/png",
+                                student_id: target.student_id.clone(),
+                                session_id: target.session_id.clone(),
+                                violation_id: None,
+                                metadata: json!({
+                                    "source": source.name(),
+                                    "snapshot": snapshots,
+                                    "check": check,
+                                    "width": width,
+                                    "height": height,
+                                }),
+                                data: png,
+                            });
+                        }
+                        Err(e) => {
+                            let _ = tx.send(AppLogs::Info(e.to_string()));
+                        }
+                    }
+                }
+
+                snapshots += 1;
+                let _ = tx.send(AppLogs::Camera(check));
+            }
+            Err(e) => {
+                failures += 1;
+                if failures == FAILURES_BEFORE_VIOLATION {
+                    let _ = tx.send(AppLogs::Violation(
+                        ViolationType::Environment,
+                        format!("[camera] CAMERA UNAVAILABLE: {}", e),
+                    ));
+                } else {
+                    let _ = tx.send(AppLogs::Info(e.to_string()));
+                }
+            }
+        }
+
+        while keep_running.load(Ordering::Relaxed) && started.elapsed() < interval {
+            thread::sleep(STOP_CHECK.min(interval.saturating_sub(started.elapsed())));
+        }
+    }
+
+    let _ = tx.send(AppLogs::Info("[camera]: Snapshots stopped".to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{FileFrameSource, FrameProblem};
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn fixture_frames_run_through_the_monitor() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/camera");
+        let source = FileFrameSource::new(&fixtures).unwrap();
+        let (tx, rx) = channel();
+        let (uploads, uploaded) = channel();
+        let target = UploadTarget { student_id: "st-1".to_string(), session_id: "s-1".to_string(), uploads };
+        let policy = CameraPolicy { interval_secs: 1, upload_every: 10 };
+        let keep_running = Arc::new(AtomicBool::new(true));
+        let running = keep_running.clone();
+        let monitor = thread::spawn(move || run_camera_monitor(Box::new(source), policy, Some(target), running, tx));
+
+        // 01_scene, then 02_covered
+        let mut checks = Vec::new();
+        let mut violations = Vec::new();
+        while checks.len() < 2 {
+            match rx.recv_timeout(Duration::from_secs(10)).expect("no snapshot from the monitor") {
+                AppLogs::Camera(check) => checks.push(check),
+                AppLogs::Violation(ViolationType::Environment, msg) => violations.push(msg),
+                _ => {}
+            }
+        }
+        keep_running.store(false, Ordering::Relaxed);
+        monitor.join().unwrap();
+
+        assert!(checks[0].is_ok());
+        assert_eq!(checks[1].problems, vec![FrameProblem::Covered]);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("CAMERA COVERED"), "{}", violations[0]);
+
+        // the first snapshot is due, the covered one goes up because of its problem
+        let uploads: Vec<EvidenceUpload> = uploaded.try_iter().collect();
+        assert_eq!(uploads.len(), 2);
+        for (snapshot, upload) in uploads.iter().enumerate() {
+            assert_eq!((upload.kind, upload.content_type), ("webcam", "image/png"));
+            assert_eq!((upload.student_id.as_str(), upload.session_id.as_str()), ("st-1", "s-1"));
+            assert_eq!(upload.metadata["snapshot"], snapshot);
+            assert_eq!((upload.metadata["width"].as_u64(), upload.metadata["height"].as_u64()), (Some(128), Some(96)));
+            let png = image::load_from_memory(&upload.data).unwrap();
+            assert_eq!((png.width(), png.height()), (128, 96));
+        }
+        assert_eq!(uploads[1].metadata["check"]["problems"][0], "Covered");
+    }
+}
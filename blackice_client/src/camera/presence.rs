@@ -0,0 +1,144 @@
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use serde::Serialize;
+
+// cheap "is anybody there" checks on a small grayscale thumbnail, no face detection.
+// a lens covered by tape or a finger is uniform, a dark room is dark but still has edges,
+// a virtual camera playing a photo never changes
+
+const THUMB_WIDTH: u32 = 64;
+const THUMB_HEIGHT: u32 = 48;
+// 0-255 luma
+const DARK_BRIGHTNESS: f32 = 35.0;
+const COVERED_CONTRAST: f32 = 10.0;
+// mean absolute change to the previous snapshot, sensor noise alone stays above it
+const STATIC_CHANGE: f32 = 0.5;
+// unchanged snapshots in a row before the feed counts as frozen
+const STATIC_FRAMES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FrameProblem {
+    Dark,
+    Covered,
+    Static,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameCheck {
+    pub at: i64,
+    pub brightness: f32,
+    // standard deviation of the luma
+    pub contrast: f32,
+    // None for the first frame
+    pub change: Option<f32>,
+    pub problems: Vec<FrameProblem>,
+}
+
+impl FrameCheck {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        let problems: Vec<&str> = self
+            .problems
+            .iter()
+            .map(|p| match p {
+                FrameProblem::Dark => "CAMERA DARK",
+                FrameProblem::Covered => "CAMERA COVERED",
+                FrameProblem::Static => "CAMERA FEED FROZEN",
+            })
+            .collect();
+
+        if problems.is_empty() {
+            format!("camera ok (brightness {:.0}, contrast {:.0})", self.brightness, self.contrast)
+        } else {
+            format!("{} (brightness {:.0}, contrast {:.0})", problems.join(", "), self.brightness, self.contrast)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PresenceDetector {
+    previous: Option<Vec<f32>>,
+    unchanged: u32,
+}
+
+impl PresenceDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, frame: &RgbaImage, at: i64) -> FrameCheck {
+        let thumb = imageops::resize(frame, THUMB_WIDTH, THUMB_HEIGHT, FilterType::Triangle);
+        let luma: Vec<f32> = thumb
+            .pixels()
+            .map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32)
+            .collect();
+
+        let count = luma.len() as f32;
+        let brightness = luma.iter().sum::<f32>() / count;
+        let contrast = (luma.iter().map(|l| (l - brightness).powi(2)).sum::<f32>() / count).sqrt();
+        let change = self
+            .previous
+            .as_ref()
+            .map(|prev| prev.iter().zip(&luma).map(|(a, b)| (a - b).abs()).sum::<f32>() / count);
+
+        self.unchanged = match change {
+            Some(c) if c < STATIC_CHANGE => self.unchanged + 1,
+            _ => 0,
+        };
+        self.previous = Some(luma);
+
+        let mut problems = Vec::new();
+        if contrast < COVERED_CONTRAST {
+            problems.push(FrameProblem::Covered);
+        } else if brightness < DARK_BRIGHTNESS {
+            problems.push(FrameProblem::Dark);
+        }
+        // a covered lens doesn't change either, that is already reported
+        if self.unchanged >= STATIC_FRAMES && problems.is_empty() {
+            problems.push(FrameProblem::Static);
+        }
+
+        FrameCheck { at, brightness, contrast, change, problems }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{FileFrameSource, FrameSource};
+    use std::path::{Path, PathBuf};
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/camera").join(name)
+    }
+
+    #[test]
+    fn fixture_frames_are_classified() {
+        let mut source = FileFrameSource::new(&fixture("")).unwrap();
+        let mut detector = PresenceDetector::new();
+        let mut next = || detector.check(&source.next_frame().unwrap().image, 0);
+
+        let scene = next();
+        assert!(scene.is_ok(), "{}", scene.summary());
+        assert_eq!(scene.change, None);
+        assert_eq!(next().problems, vec![FrameProblem::Covered]);
+        assert_eq!(next().problems, vec![FrameProblem::Dark]);
+        let moved = next();
+        assert!(moved.is_ok(), "{}", moved.summary());
+        assert!(moved.change.unwrap() > STATIC_CHANGE);
+    }
+
+    #[test]
+    fn repeated_frame_is_frozen() {
+        let mut source = FileFrameSource::new(&fixture("01_scene.png")).unwrap();
+        let mut detector = PresenceDetector::new();
+        let checks: Vec<FrameCheck> =
+            (0..=STATIC_FRAMES).map(|_| detector.check(&source.next_frame().unwrap().image, 0)).collect();
+
+        assert!(checks[..STATIC_FRAMES as usize].iter().all(|c| c.is_ok()));
+        assert_eq!(checks[STATIC_FRAMES as usize].problems, vec![FrameProblem::Static]);
+    }
+}
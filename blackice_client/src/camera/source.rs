@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::RgbaImage;
+
+use super::error::CameraError;
+
+// where snapshots come from, the real camera or PNG files on machines without one
+
+pub struct Frame {
+    pub image: RgbaImage,
+    pub captured_at: i64,
+}
+
+pub trait FrameSource {
+    // shown in logs and stored with uploaded frames
+    fn name(&self) -> String;
+    fn next_frame(&mut self) -> Result<Frame, CameraError>;
+}
+
+// PNG files of a directory (or a single file), in name order, from the start again at the end
+pub struct FileFrameSource {
+    files: Vec<PathBuf>,
+    next: usize,
+}
+
+impl FileFrameSource {
+    pub fn new(path: &Path) -> Result<Self, CameraError> {
+        let files = if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        if files.is_empty() {
+            return Err(CameraError::Decode(format!("no PNG frames in {}", path.display())));
+        }
+        Ok(Self { files, next: 0 })
+    }
+}
+
+impl FrameSource for FileFrameSource {
+    fn name(&self) -> String {
+        format!("files ({} frames)", self.files.len())
+    }
+
+    fn next_frame(&mut self) -> Result<Frame, CameraError> {
+        let path = &self.files[self.next];
+        self.next = (self.next + 1) % self.files.len();
+
+        let image = image::open(path)
+            .map_err(|e| CameraError::Decode(format!("{}: {}", path.display(), e)))?
+            .to_rgba8();
+        Ok(Frame { image, captured_at: chrono::Utc::now().timestamp() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/camera")
+    }
+
+    #[test]
+    fn directory_frames_come_in_name_order_and_wrap() {
+        let mut source = FileFrameSource::new(&fixtures()).unwrap();
+        // README.txt is not a frame
+        assert_eq!(source.name(), "files (4 frames)");
+
+        let first = source.next_frame().unwrap().image;
+        assert_eq!(first.dimensions(), (128, 96));
+        // 01_scene starts with a dark square, 02_covered is flat gray
+        assert_eq!(first.get_pixel(0, 0).0, [60, 60, 60, 255]);
+        assert_eq!(source.next_frame().unwrap().image.get_pixel(0, 0).0, [128, 128, 128, 255]);
+        source.next_frame().unwrap();
+        source.next_frame().unwrap();
+        assert_eq!(source.next_frame().unwrap().image, first);
+    }
+
+    #[test]
+    fn single_file_repeats() {
+        let mut source = FileFrameSource::new(&fixtures().join("02_covered.png")).unwrap();
+        assert_eq!(source.name(), "files (1 frames)");
+        assert_eq!(source.next_frame().unwrap().image, source.next_frame().unwrap().image);
+    }
+
+    #[test]
+    fn directory_without_frames_is_refused() {
+        let empty = std::env::temp_dir().join(format!("blackice-no-frames-{}", std::process::id()));
+        fs::create_dir_all(&empty).unwrap();
+        let result = FileFrameSource::new(&empty);
+        fs::remove_dir_all(&empty).unwrap();
+        assert!(matches!(result, Err(CameraError::Decode(_))));
+    }
+
+    #[test]
+    fn unreadable_frame_is_a_decode_error() {
+        let mut source = FileFrameSource::new(&fixtures().join("README.txt")).unwrap();
+        assert!(matches!(source.next_frame(), Err(CameraError::Decode(_))));
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+use crate::camera::{self, CameraPolicy, FileFrameSource, FrameSource, MediaFoundationCamera};
+use crate::cloud_reporter::{AppLogs, Authorize, SERVER_URL};
+use crate::evidence::{self, EvidenceExport, EvidenceKind};
+use crate::network::{self, journal, LockdownPhase};
+use crate::{applications, environment};
+
+// headless mode, the same library calls the GUI makes. every line on stdout is one JSON
+// object: events while a command runs ({"event": ...}) and a final {"command": ..., "ok": ...}.
+// long running commands stop on "stop" / EOF on stdin, or after --seconds
+
+pub const USAGE: &str = "\
+usage: blackice_proctor [command]
+
+  (none)                       start the GUI
+  check                        run the pre-exam readiness checks, exit 1 if any fails
+  lock [--verify] [--reapply] [--seconds N]
+                               lock the network and hold it until stopped, then unlock
+  unlock                       restore the network locked by a `lock` process (or the GUI)
+  monitor [--seconds N]        run the environment / process monitor, print violations
+  recover                      restore the network left locked by a killed session
+  camera [--frames <dir>] [--interval N] [--seconds N]
+                               take webcam snapshots and print the presence checks,
+                               --frames reads PNG files instead of the camera
+  export-evidence <file> [--server]
+                               decrypt and verify a session evidence log, --server also
+                               checks which reported entries the server received
+  help                         show this text
+
+long running commands stop on a `stop` line or EOF on stdin";
+
+// commands that never change the network, a panic in them must not reset a lockdown
+// owned by another process
+pub const READ_ONLY_COMMANDS: &[&str] = &["check", "monitor", "camera", "export-evidence", "help", "--help", "-h"];
+
+const POLL: Duration = Duration::from_millis(500);
+
+// None when args don't name a command (GUI), otherwise the exit code
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let options = &args[2..];
+
+    let code = match command.as_str() {
+        "check" => check(),
+        "lock" => lock(options),
+        "unlock" => unlock(),
+        "monitor" => monitor(options),
+        "recover" => recover(),
+        "camera" => camera(options),
+        "export-evidence" => export_evidence(options),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        other => {
+            eprintln!("unknown command '{}'\n\n{}", other, USAGE);
+            2
+        }
+    };
+    Some(code)
+}
+
+
+// Commands
+
+fn check() -> i32 {
+    let report = environment::run_readiness_checks();
+    let passed = report.passed();
+    emit(json!({ "command": "check", "ok": passed, "summary": report.summary(), "report": report }));
+    if passed { 0 } else { 1 }
+}
+
+fn lock(options: &[String]) -> i32 {
+    let verify = options.iter().any(|o| o == "--verify");
+    let reapply = options.iter().any(|o| o == "--reapply");
+    let seconds = match seconds_option(options) {
+        Ok(s) => s,
+        Err(e) => return usage_error("lock", e),
+    };
+
+    if let Some(stale) = network::stale_lockdown() {
+        emit(json!({
+            "command": "lock",
+            "ok": false,
+            "error": format!("network already {:?} by pid {}, run `unlock` or `recover` first", stale.phase, stale.pid),
+        }));
+        return 1;
+    }
+
+    let (tx, rx) = channel::<AppLogs>();
+    let guard = match network::run_lockdown(&tx) {
+        Ok(guard) => guard,
+        Err(failure) => {
+            drain(&rx);
+            emit(json!({ "command": "lock", "ok": false, "error": failure.summary(), "failure": failure }));
+            return 1;
+        }
+    };
+    journal::record(LockdownPhase::Locked);
+    drain(&rx);
+    emit(json!({ "event": "locked" }));
+
+    // same background work the GUI starts after LockSuccess
+    let dns_signal = Arc::new(AtomicBool::new(true));
+    let tamper_signal = Arc::new(AtomicBool::new(true));
+    let guard = Arc::new(Mutex::new(Some(guard)));
+    spawn_network_threads(&tx, &dns_signal, &tamper_signal, &guard, reapply);
+
+    if verify {
+        let upstreams = network::upstream_servers();
+        let report = network::verify_lockdown(&upstreams);
+        emit(json!({ "event": "verification", "passed": report.passed, "report": report }));
+    }
+
+    let stop = stop_signal();
+    let deadline = seconds.map(|s| Instant::now() + Duration::from_secs(s));
+    let mut unlocked_elsewhere = false;
+    while !stop.load(Ordering::Relaxed) && deadline.is_none_or(|d| Instant::now() < d) {
+        forward(&rx, POLL);
+
+        // `unlock` from another process writes the journal first, it does the reset then
+        if let Some(entry) = journal::read()
+            && entry.pid != std::process::id()
+        {
+            unlocked_elsewhere = true;
+            break;
+        }
+    }
+
+    // removing our own rules is not tampering
+    tamper_signal.store(false, Ordering::Relaxed);
+    dns_signal.store(false, Ordering::Relaxed);
+    let mut guard = guard.lock().unwrap_or_else(|p| p.into_inner());
+
+    if unlocked_elsewhere {
+        *guard = None;
+        drain(&rx);
+        emit(json!({ "command": "lock", "ok": true, "unlocked_by": "unlock command" }));
+        return 0;
+    }
+
+    journal::record(LockdownPhase::Unlocking);
+    let result = network::reset_firewall();
+    *guard = None;
+    drain(&rx);
+
+    match result {
+        Ok(msg) => {
+            journal::record(LockdownPhase::Idle);
+            emit(json!({ "command": "lock", "ok": true, "unlocked": msg }));
+            0
+        }
+        Err(e) => {
+            emit(json!({ "command": "lock", "ok": false, "error": format!("unlock failed: {}", e) }));
+            1
+        }
+    }
+}
+
+fn unlock() -> i32 {
+    let Some(entry) = journal::read().filter(|e| e.phase != LockdownPhase::Idle) else {
+        emit(json!({ "command": "unlock", "ok": true, "unlocked": "nothing locked" }));
+        return 0;
+    };
+
+    // the owning `lock` process sees this and exits without touching the firewall
+    journal::record(LockdownPhase::Unlocking);
+    // give it a poll interval to stop its tamper watchdog
+    thread::sleep(POLL * 2);
+
+    match network::reset_firewall() {
+        Ok(msg) => {
+            journal::record(LockdownPhase::Idle);
+            emit(json!({ "command": "unlock", "ok": true, "unlocked": msg, "previous": entry }));
+            0
+        }
+        Err(e) => {
+            emit(json!({ "command": "unlock", "ok": false, "error": e.to_string(), "previous": entry }));
+            1
+        }
+    }
+}
+
+fn monitor(options: &[String]) -> i32 {
+    let seconds = match seconds_option(options) {
+        Ok(s) => s,
+        Err(e) => return usage_error("monitor", e),
+    };
+
+    let (tx, rx) = channel::<AppLogs>();
+    let signal = Arc::new(AtomicBool::new(true));
+    let mut violations = 0usize;
+
+    if let Some(violation) = environment::scan_environment() {
+        violations += 1;
+        emit(json!({ "event": "violation", "category": "Environment", "message": violation }));
+    }
+
+    let monitor_signal = signal.clone();
+    let monitor = thread::spawn(move || applications::start_monitor(monitor_signal, tx));
+
+    let stop = stop_signal();
+    let deadline = seconds.map(|s| Instant::now() + Duration::from_secs(s));
+    while !stop.load(Ordering::Relaxed) && deadline.is_none_or(|d| Instant::now() < d) {
+        violations += forward(&rx, POLL);
+    }
+
+    signal.store(false, Ordering::Relaxed);
+    let _ = monitor.join();
+    violations += forward(&rx, Duration::ZERO);
+
+    emit(json!({ "command": "monitor", "ok": true, "violations": violations }));
+    0
+}
+
+fn camera(options: &[String]) -> i32 {
+    let seconds = match seconds_option(options) {
+        Ok(s) => s,
+        Err(e) => return usage_error("camera", e),
+    };
+    let interval = match options.iter().position(|o| o == "--interval") {
+        None => 5,
+        Some(i) => match options.get(i + 1).and_then(|s| s.parse::<u64>().ok()).filter(|s| *s > 0) {
+            Some(interval) => interval,
+            None => return usage_error("camera", "--interval needs a number of seconds".into()),
+        },
+    };
+    let frames = match options.iter().position(|o| o == "--frames") {
+        None => None,
+        Some(i) => match options.get(i + 1) {
+            Some(path) => Some(path.clone()),
+            None => return usage_error("camera", "--frames needs a directory or PNG file".into()),
+        },
+    };
+
+    let (tx, rx) = channel::<AppLogs>();
+    let signal = Arc::new(AtomicBool::new(true));
+    let policy = CameraPolicy { interval_secs: interval, upload_every: 1 };
+    let open = move || -> Result<Box<dyn FrameSource>, camera::CameraError> {
+        match frames {
+            Some(path) => Ok(Box::new(FileFrameSource::new(Path::new(&path))?)),
+            None => Ok(Box::new(MediaFoundationCamera::open()?)),
+        }
+    };
+    // no upload target, the frames stay local
+    let monitor = camera::spawn_camera_monitor(open, policy, None, signal.clone(), tx);
+
+    let stop = stop_signal();
+    let deadline = seconds.map(|s| Instant::now() + Duration::from_secs(s));
+    let mut violations = 0usize;
+    while !stop.load(Ordering::Relaxed) && deadline.is_none_or(|d| Instant::now() < d) && !monitor.is_finished() {
+        violations += forward(&rx, POLL);
+    }
+
+    signal.store(false, Ordering::Relaxed);
+    let _ = monitor.join();
+    violations += forward(&rx, Duration::ZERO);
+
+    emit(json!({ "command": "camera", "ok": violations == 0, "violations": violations }));
+    if violations == 0 { 0 } else { 1 }
+}
+
+fn recover() -> i32 {
+    let stale = network::stale_lockdown();
+    match network::recover_network() {
+        Ok(msg) => {
+            emit(json!({ "command": "recover", "ok": true, "recovered": msg, "stale": stale }));
+            0
+        }
+        Err(e) => {
+            emit(json!({ "command": "recover", "ok": false, "error": e.to_string(), "stale": stale }));
+            1
+        }
+    }
+}
+
+fn export_evidence(options: &[String]) -> i32 {
+    let Some(file) = options.first().filter(|f| !f.starts_with("--")) else {
+        return usage_error("export-evidence", "missing evidence log file".into());
+    };
+
+    let export = match evidence::export(Path::new(file)) {
+        Ok(export) => export,
+        Err(e) => {
+            emit(json!({ "command": "export-evidence", "ok": false, "error": e.to_string() }));
+            return 1;
+        }
+    };
+
+    let server = if options.iter().any(|o| o == "--server") {
+        Some(verify_with_server(&export))
+    } else {
+        None
+    };
+    let server_ok = server
+        .as_ref()
+        .is_none_or(|results| results.iter().all(|r| r.get("consistent") == Some(&Value::Bool(true))));
+
+    let ok = export.chain_valid && server_ok;
+    emit(json!({ "command": "export-evidence", "ok": ok, "export": export, "server": server }));
+    if ok { 0 } else { 1 }
+}
+
+// asks the server which of the reported entries it has, per student / session in the log
+fn verify_with_server(export: &EvidenceExport) -> Vec<Value> {
+    let mut sessions: BTreeMap<(String, String), Vec<Value>> = BTreeMap::new();
+    for e in export.entries.iter().filter(|e| e.entry.kind == EvidenceKind::Report) {
+        let key = (
+            e.entry.student_id.clone().unwrap_or_default(),
+            e.entry.session_id.clone().unwrap_or_default(),
+        );
+        sessions.entry(key).or_default().push(json!({ "seq": e.seq, "hash": e.hash }));
+    }
+
+    let client = reqwest::blocking::Client::new();
+    sessions
+        .into_iter()
+        .map(|((student_id, session_id), entries)| {
+            let url = format!("{}/api/sessions/{}/verify_log", SERVER_URL, session_id);
+            let body = json!({ "student_id": student_id, "entries": entries });
+            let result = client
+                .post(&url)
+                .authorize()
+                .json(&body)
+                .timeout(Duration::from_secs(30))
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.json::<Value>());
+
+            match result {
+                Ok(mut v) => {
+                    v["student_id"] = json!(student_id);
+                    v["session_id"] = json!(session_id);
+                    v
+                }
+                Err(e) => json!({
+                    "student_id": student_id,
+                    "session_id": session_id,
+                    "consistent": false,
+                    "error": e.to_string(),
+                }),
+            }
+        })
+        .collect()
+}
+
+
+// Helpers functions
+
+fn spawn_network_threads(
+    tx: &Sender<AppLogs>,
+    dns_signal: &Arc<AtomicBool>,
+    tamper_signal: &Arc<AtomicBool>,
+    guard: &Arc<Mutex<Option<network::WfpGuard>>>,
+    reapply: bool,
+) {
+    let upstreams = network::upstream_servers();
+
+    let (signal, tx_proxy, servers) = (dns_signal.clone(), tx.clone(), upstreams.clone());
+    thread::spawn(move || network::run_dns_proxy(signal, tx_proxy, servers));
+
+    let (signal, tx_ttl) = (dns_signal.clone(), tx.clone());
+    thread::spawn(move || network::run_ttl_refresher(signal, tx_ttl, upstreams));
+
+    let (signal, tx_tamper, guard) = (tamper_signal.clone(), tx.clone(), guard.clone());
+    let reapply = Arc::new(AtomicBool::new(reapply));
+    thread::spawn(move || network::run_tamper_watchdog(signal, tx_tamper, guard, reapply));
+}
+
+// set once stdin says "stop" or closes
+fn stop_signal() -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(l) if l.trim() != "stop" => continue,
+                _ => break,
+            }
+        }
+        flag.store(true, Ordering::Relaxed);
+    });
+    stop
+}
+
+// prints everything that arrives within `wait`, returns how many violations were among it
+fn forward(rx: &Receiver<AppLogs>, wait: Duration) -> usize {
+    let first = rx.recv_timeout(wait).ok();
+    let mut violations = 0;
+    for msg in first.into_iter().chain(rx.try_iter()) {
+        if matches!(msg, AppLogs::Violation(..) | AppLogs::WindowViolation(..)) {
+            violations += 1;
+        }
+        if let Some(event) = event_json(&msg) {
+            emit(event);
+        }
+    }
+    violations
+}
+
+fn drain(rx: &Receiver<AppLogs>) {
+    forward(rx, Duration::ZERO);
+}
+
+fn event_json(msg: &AppLogs) -> Option<Value> {
+    Some(match msg {
+        AppLogs::Info(text) => json!({ "event": "info", "message": text }),
+        AppLogs::Error(text) => json!({ "event": "error", "message": text }),
+        AppLogs::Violation(v_type, text) => {
+            json!({ "event": "violation", "category": format!("{:?}", v_type), "message": text })
+        }
+        AppLogs::WindowViolation(v_type, text, window) => json!({
+            "event": "violation",
+            "category": format!("{:?}", v_type),
+            "message": text,
+            "window": window,
+        }),
+        AppLogs::Verification(report) => json!({ "event": "verification", "passed": report.passed, "report": report }),
+        AppLogs::Readiness(report) => json!({ "event": "readiness", "passed": report.passed(), "report": report }),
+        AppLogs::Camera(check) => json!({ "event": "camera", "ok": check.is_ok(), "check": check }),
+        // GUI only
+        _ => return None,
+    })
+}
+
+fn seconds_option(options: &[String]) -> Result<Option<u64>, String> {
+    match options.iter().position(|o| o == "--seconds") {
+        None => Ok(None),
+        Some(i) => options
+            .get(i + 1)
+            .and_then(|s| s.parse().ok())
+            .map(Some)
+            .ok_or_else(|| "--seconds needs a number".to_string()),
+    }
+}
+
+fn usage_error(command: &str, error: String) -> i32 {
+    emit(json!({ "command": command, "ok": false, "error": error }));
+    2
+}
+
+fn emit(value: Value) {
+    println!("{}", value);
+}
@@ -0,0 +1,39 @@
+use std::sync::{Mutex, OnceLock};
+use reqwest::blocking::RequestBuilder;
+
+// the server only takes requests with a bearer token. a lab machine gets the token of a
+// student-client account through BLACKICE_TOKEN, the CLI commands use the proctor's own
+// token the same way
+
+static TOKEN: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+
+fn slot() -> &'static Mutex<Option<String>> {
+    TOKEN.get_or_init(|| {
+        let token = std::env::var("BLACKICE_TOKEN").ok().map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+        Mutex::new(token)
+    })
+}
+
+pub fn server_token() -> Option<String> {
+    slot().lock().ok().and_then(|t| t.clone())
+}
+
+pub fn set_server_token(token: Option<String>) {
+    if let Ok(mut slot) = slot().lock() {
+        *slot = token;
+    }
+}
+
+// adds the token to a request to the server, requests go out without one if there is none
+pub trait Authorize {
+    fn authorize(self) -> Self;
+}
+
+impl Authorize for RequestBuilder {
+    fn authorize(self) -> Self {
+        match server_token() {
+            Some(token) => self.bearer_auth(token),
+            None => self,
+        }
+    }
+}
@@ -1,17 +1,46 @@
-use crate::network::WfpGuard;
+use crate::camera::FrameCheck;
+use crate::environment::ReadinessReport;
+use crate::evidence::ScreenRect;
+use crate::exam::{ExamEvent, ExamSchedule, JoinedSession};
+use crate::network::{LockdownFailure, VerificationReport, WfpGuard};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ViolationType {
     Application,
     Network,
     Environment,
+    // the lockdown itself was modified (rules deleted, defaults flipped, WFP filters removed)
+    Tamper,
     Other,
 }
 
+impl ViolationType {
+    pub const ALL: [ViolationType; 5] = [
+        ViolationType::Application,
+        ViolationType::Network,
+        ViolationType::Environment,
+        ViolationType::Tamper,
+        ViolationType::Other,
+    ];
+}
+
 pub enum AppLogs {
     Info(String),
     Error(String),
     Violation(ViolationType, String),
+    // a violation about a visible window, with where it is on screen
+    WindowViolation(ViolationType, String, ScreenRect),
     LockSuccess(WfpGuard),
+    // lockdown failed and was rolled back (fully or not, see LockdownFailure::is_clean)
+    LockFailed(LockdownFailure),
     UnlockSuccess,
+    Verification(VerificationReport),
+    Readiness(ReadinessReport),
+    Schedule(ExamSchedule),
+    // result of the latest webcam snapshot
+    Camera(FrameCheck),
+    // answer to a join code sign-in
+    Joined(Result<JoinedSession, String>),
+    // events for the exam state machine from background threads (pre-check results ...)
+    Exam(ExamEvent),
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 pub mod reporter;
 pub mod logger;
+pub mod auth;
 
 pub use reporter::*;
-pub use logger::*;
\ No newline at end of file
+pub use logger::*;
+pub use auth::*;
\ No newline at end of file
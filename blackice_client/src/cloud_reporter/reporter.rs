@@ -1,9 +1,17 @@
 use std::sync::mpsc::{channel, Receiver, Sender, RecvTimeoutError};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use serde::Serialize;
 use reqwest::blocking::Client; // Using blocking client
 
+use super::auth::{server_token, Authorize};
+use crate::evidence::{EvidenceEntry, EvidenceKind, EvidenceLog};
+
+pub const SERVER_URL: &str = "http://localhost:3000";
+// entries kept while there is no token yet, the oldest are dropped past this
+const MAX_PENDING: usize = 5000;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct LogEntry {
     pub student_id: String,
@@ -11,6 +19,14 @@ pub struct LogEntry {
     pub level: String,
     pub message: String,
     pub timestamp: i64,
+    // position in the local evidence log, lets an exported log be matched against the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence_seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence_hash: Option<String>,
+    // set on violations, uploaded evidence (screenshots) refers to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violation_id: Option<String>,
 }
 
 pub struct ReporterActor {
@@ -19,10 +35,11 @@ pub struct ReporterActor {
     client: Client,
     api_url: String,
     flush_interval: Duration,
+    evidence: Option<Arc<EvidenceLog>>,
 }
 
 impl ReporterActor {
-    pub fn spawn(api_url: String) -> Sender<LogEntry> {
+    pub fn spawn(api_url: String, evidence: Option<Arc<EvidenceLog>>) -> Sender<LogEntry> {
         let (tx, rx) = channel();
 
         thread::spawn(move || {
@@ -32,6 +49,7 @@ impl ReporterActor {
                 client: Client::new(),
                 api_url,
                 flush_interval: Duration::from_secs(10), // flush every 10s
+                evidence,
             };
             actor.run();
         });
@@ -70,6 +88,11 @@ impl ReporterActor {
                     if !self.buffer.is_empty() {
                         self.flush();
                     }
+                    // still no token, they can't be sent anymore
+                    if !self.buffer.is_empty() {
+                        let pending = std::mem::take(&mut self.buffer);
+                        self.record_delivery(&pending, Err("no server token".to_string()));
+                    }
                     break;
                 }
             }
@@ -79,17 +102,51 @@ impl ReporterActor {
     fn flush(&mut self) {
         if self.buffer.is_empty() { return; }
 
+        // the server answers 401 without a token, entries from before the join (like the unclean
+        // exit report) wait until there is one
+        if server_token().is_none() {
+            if self.buffer.len() > MAX_PENDING {
+                let excess = self.buffer.len() - MAX_PENDING;
+                let dropped: Vec<LogEntry> = self.buffer.drain(..excess).collect();
+                self.record_delivery(&dropped, Err("no server token".to_string()));
+            }
+            return;
+        }
+
         println!("[Reporter] Flushing {} logs...", self.buffer.len());
 
         let batch = std::mem::take(&mut self.buffer); 
         
-        match self.client.post(&self.api_url).json(&batch).send() {
+        let result = match self.client.post(&self.api_url).authorize().json(&batch).send() {
             Ok(resp) => {
                 if !resp.status().is_success() {
                     eprintln!("[Reporter] Server rejected logs: {}", resp.status());
+                    Err(format!("Server rejected logs: {}", resp.status()))
+                } else {
+                    Ok(())
                 }
             },
-            Err(e) => eprintln!("[Reporter] Network error: {}", e),
+            Err(e) => {
+                eprintln!("[Reporter] Network error: {}", e);
+                Err(format!("Network error: {}", e))
+            }
+        };
+
+        self.record_delivery(&batch, result);
+    }
+
+    // the evidence log shows which entries the server never got
+    fn record_delivery(&self, batch: &[LogEntry], result: Result<(), String>) {
+        let Some(evidence) = &self.evidence else { return };
+
+        let seqs: Vec<String> = batch.iter().filter_map(|l| l.evidence_seq).map(|s| s.to_string()).collect();
+        let (level, message) = match result {
+            Ok(()) => ("DELIVERED", format!("{} entries delivered (seq {})", batch.len(), seqs.join(","))),
+            Err(e) => ("DELIVERY_FAILED", format!("{} entries lost (seq {}): {}", batch.len(), seqs.join(","), e)),
+        };
+
+        if let Err(e) = evidence.append(&EvidenceEntry::new(EvidenceKind::Delivery, level, &message)) {
+            eprintln!("[Reporter] {}", e);
         }
     }
 }
\ No newline at end of file
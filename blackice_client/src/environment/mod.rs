@@ -1,5 +1,7 @@
 pub mod bypass;
+pub mod readiness;
 pub mod vpn;
 
 pub use bypass::*;
+pub use readiness::*;
 pub use vpn::*;
\ No newline at end of file
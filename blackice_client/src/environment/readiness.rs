@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashSet};
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+use super::bypass::SecurityCheck;
+use crate::applications;
+use crate::cloud_reporter::AppLogs;
+use crate::network::{self, probe, whitelist};
+
+// pre-flight checklist, every environment / network / process check once before the exam.
+// a single Fail keeps the student out, warnings are only shown. the report is uploaded as the
+// session's baseline so later violations can be compared against what was there at the start
+
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckCategory {
+    Environment,
+    Network,
+    Processes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessItem {
+    pub category: CheckCategory,
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    // what the student has to do about it, None on Pass
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub checked_at: i64,
+    pub items: Vec<ReadinessItem>,
+}
+
+impl ReadinessReport {
+    pub fn new(items: Vec<ReadinessItem>) -> Self {
+        Self { checked_at: chrono::Utc::now().timestamp(), items }
+    }
+
+    // warnings don't block entry
+    pub fn passed(&self) -> bool {
+        self.count(CheckStatus::Fail) == 0
+    }
+
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.items.iter().filter(|i| i.status == status).count()
+    }
+
+    pub fn summary(&self) -> String {
+        let counts = format!(
+            "{} Passed, {} Warnings, {} Failed",
+            self.count(CheckStatus::Pass),
+            self.count(CheckStatus::Warn),
+            self.count(CheckStatus::Fail)
+        );
+        if self.passed() {
+            return format!("[readiness]: {}", counts);
+        }
+
+        let failed: Vec<&str> = self.items.iter()
+            .filter(|i| i.status == CheckStatus::Fail)
+            .map(|i| i.name.as_str())
+            .collect();
+        format!("[readiness]: {} ({})", counts, failed.join(", "))
+    }
+}
+
+// blocking, takes a few seconds (process scan, reachability probe)
+pub fn run_readiness_checks() -> ReadinessReport {
+    let mut items = Vec::new();
+    environment_checks(&mut items);
+    network_checks(&mut items);
+    process_checks(&mut items);
+    ReadinessReport::new(items)
+}
+
+
+// Checks
+
+fn environment_checks(items: &mut Vec<ReadinessItem>) {
+    use CheckCategory::Environment;
+    let checks = SecurityCheck::check();
+
+    items.push(match checks.vm_vendor {
+        None => pass(Environment, "Virtual Machine", "Running on physical hardware"),
+        // same exception as scan_environment
+        Some(vendor) if cfg!(debug_assertions) => item(
+            Environment, "Virtual Machine", CheckStatus::Warn,
+            format!("{} detected, ignored in debug builds", vendor), None,
+        ),
+        Some(vendor) => fail(
+            Environment, "Virtual Machine", format!("{} detected", vendor),
+            "Run the exam on the host machine, not inside a virtual machine",
+        ),
+    });
+
+    items.push(if checks.is_remote {
+        fail(
+            Environment, "Remote Desktop", "Running inside a Remote Desktop session",
+            "Sit at the exam machine and start BlackICE locally",
+        )
+    } else {
+        pass(Environment, "Remote Desktop", "Local session")
+    });
+
+    items.push(if checks.monitor_count > 1 {
+        fail(
+            Environment, "Monitors", format!("{} monitors connected", checks.monitor_count),
+            "Disconnect the second monitor (or switch Windows to 'PC screen only')",
+        )
+    } else {
+        pass(Environment, "Monitors", "Single monitor")
+    });
+
+    items.push(match checks.vpn_detected {
+        None => pass(Environment, "VPN / Proxy", "No VPN adapter active"),
+        Some(vpn) => fail(Environment, "VPN / Proxy", vpn, "Disconnect the VPN and disable its network adapter"),
+    });
+}
+
+fn network_checks(items: &mut Vec<ReadinessItem>) {
+    use CheckCategory::Network;
+
+    // the lockdown (WFP, firewall, adapter DNS) needs an elevated token
+    items.push(if is_elevated() {
+        pass(Network, "Administrator Rights", "Running elevated")
+    } else {
+        fail(
+            Network, "Administrator Rights", "Not running as Administrator, the lockdown would fail",
+            "Close BlackICE, right-click it and choose 'Run as administrator'",
+        )
+    });
+
+    items.push(match network::stale_lockdown() {
+        None => pass(Network, "Previous Lockdown", "No leftover lockdown"),
+        Some(entry) => fail(
+            Network, "Previous Lockdown", format!("Earlier session ended unclean ({:?})", entry.phase),
+            "Click 'Recover Network' first",
+        ),
+    });
+
+    // a whitelisted site has to work before the lockdown, or the exam can't be taken at all
+    let host = whitelist::exact_hosts().next().unwrap_or_default();
+    let addr = (host, whitelist::WHITELIST_PORT).to_socket_addrs().ok().and_then(|mut a| a.next());
+    items.push(match addr {
+        None => fail(
+            Network, "Internet Connection", format!("Could not resolve {}", host),
+            "Check your internet connection",
+        ),
+        Some(addr) => match probe::connect(addr, REACHABILITY_TIMEOUT) {
+            outcome if outcome.reached() => pass(Network, "Internet Connection", format!("{} reachable", host)),
+            outcome => fail(
+                Network, "Internet Connection", format!("{} not reachable ({:?})", host, outcome),
+                "Check your internet connection and any other firewall software",
+            ),
+        },
+    });
+}
+
+fn process_checks(items: &mut Vec<ReadinessItem>) {
+    use CheckCategory::Processes;
+
+    // one item per banned application, not per window
+    let mut banned: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (word, title, pid) in applications::find_banned_windows() {
+        banned.entry(word).or_default().push(format!("'{}' (PID: {})", title, pid));
+    }
+    if banned.is_empty() {
+        items.push(pass(Processes, "Banned Applications", "None open"));
+    }
+    for (word, windows) in banned {
+        items.push(fail(
+            Processes, "Banned Applications", windows.join(", "),
+            format!("Close {}", title_case(word)),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for log in applications::find_suspicious_processes() {
+        let AppLogs::Violation(_, msg) = log else { continue };
+        if !seen.insert(msg.clone()) {
+            continue;
+        }
+
+        // drop the "[application] [security]" prefix for the checklist
+        let detail = msg.rsplit("] ").next().unwrap_or(&msg).to_string();
+        items.push(if msg.contains("MASQUERADE") {
+            fail(Processes, "Renamed Binary", detail, "Close it, it is not the application its name claims")
+        } else {
+            item(
+                Processes, "Unknown Application", CheckStatus::Warn, detail,
+                Some("Close it unless you need it during the exam".into()),
+            )
+        });
+    }
+    if seen.is_empty() {
+        items.push(pass(Processes, "Unknown Applications", "None running"));
+    }
+}
+
+
+// Helpers functions
+
+fn item(
+    category: CheckCategory,
+    name: &str,
+    status: CheckStatus,
+    detail: impl Into<String>,
+    hint: Option<String>,
+) -> ReadinessItem {
+    ReadinessItem { category, name: name.to_string(), status, detail: detail.into(), hint }
+}
+
+fn pass(category: CheckCategory, name: &str, detail: impl Into<String>) -> ReadinessItem {
+    item(category, name, CheckStatus::Pass, detail, None)
+}
+
+fn fail(category: CheckCategory, name: &str, detail: impl Into<String>, hint: impl Into<String>) -> ReadinessItem {
+    item(category, name, CheckStatus::Fail, detail, Some(hint.into()))
+}
+
+// "stack overflow" -> "Stack Overflow"
+fn title_case(s: &str) -> String {
+    s.split(' ')
+        .map(|w| {
+            let mut c = w.chars();
+            match c.next() {
+                Some(first) => first.to_uppercase().chain(c).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut len = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        )
+        .is_ok();
+        let _ = CloseHandle(token);
+
+        ok && elevation.TokenIsElevated != 0
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::imageops::{self, FilterType as ResizeFilter};
+use image::{ExtendedColorType, ImageEncoder, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits, ReleaseDC,
+    SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, HBITMAP, HDC, HGDIOBJ, SRCCOPY,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetSystemMetrics, GetWindowRect, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+};
+
+use super::error::EvidenceError;
+use super::upload::EvidenceUpload;
+use crate::cloud_reporter::{AppLogs, ViolationType};
+
+// screenshots taken when a violation fires. the source is a trait so the upload pipeline
+// can be driven by synthetic images instead of the real desktop
+
+// violation kinds that get a screenshot (network / tamper violations show nothing on screen)
+pub const CAPTURE_KINDS: &[ViolationType] = &[ViolationType::Application, ViolationType::Environment];
+// the monitors re-report a violation every scan, one screenshot per violation and minute
+pub const CAPTURE_COOLDOWN_SECS: i64 = 60;
+
+// longest side of the uploaded image, a multi monitor 4k desktop is ~10 MB as PNG otherwise
+const MAX_SIDE: u32 = 1920;
+
+// virtual desktop coordinates, negative left of / above the primary monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl ScreenRect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    // bounds of a top level window, None if it is already gone
+    pub fn of_window(hwnd: HWND) -> Option<ScreenRect> {
+        let mut rect = Default::default();
+        unsafe { GetWindowRect(hwnd, &mut rect).ok()? };
+        Some(ScreenRect { left: rect.left, top: rect.top, right: rect.right, bottom: rect.bottom })
+    }
+}
+
+pub struct Screenshot {
+    pub image: RgbaImage,
+    // where the image sits on the virtual desktop, to place a window rect on it
+    pub bounds: ScreenRect,
+}
+
+pub trait CaptureSource: Send {
+    fn capture(&mut self) -> Result<Screenshot, EvidenceError>;
+}
+
+pub struct CaptureRequest {
+    pub violation_id: String,
+    pub student_id: String,
+    pub session_id: String,
+    pub kind: ViolationType,
+    pub message: String,
+    // the offending window, when the violation is about one
+    pub window: Option<ScreenRect>,
+    pub at: i64,
+}
+
+// decides which violations get a screenshot, keyed by the violation signature
+#[derive(Default)]
+pub struct CapturePolicy {
+    last_capture: HashMap<(ViolationType, String), i64>,
+}
+
+impl CapturePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // true (and remembered) if this violation should be captured now
+    pub fn should_capture(&mut self, kind: ViolationType, signature: &str, now: i64) -> bool {
+        if !CAPTURE_KINDS.contains(&kind) {
+            return false;
+        }
+        let last = self.last_capture.entry((kind, signature.to_string())).or_insert(i64::MIN);
+        if now.saturating_sub(*last) < CAPTURE_COOLDOWN_SECS {
+            return false;
+        }
+        *last = now;
+        true
+    }
+}
+
+// takes the screenshots off the UI thread, at once: the screen may look different by the
+// time the upload goes out
+pub fn spawn_capture(
+    mut source: Box<dyn CaptureSource>,
+    uploads: Sender<EvidenceUpload>,
+    app_tx: Sender<AppLogs>,
+) -> Sender<CaptureRequest> {
+    let (tx, rx) = channel::<CaptureRequest>();
+
+    thread::spawn(move || {
+        for request in rx {
+            let upload = source.capture().and_then(|shot| {
+                let (png, width, height) = encode_png(&shot.image, MAX_SIDE)?;
+                Ok(EvidenceUpload {
+                    kind: "screenshot",
+                    content_type: "image/png",
+                    student_id: request.student_id,
+                    session_id: request.session_id,
+                    violation_id: Some(request.violation_id),
+                    metadata: json!({
+                        "violation_kind": format!("{:?}", request.kind),
+                        "message": request.message,
+                        "violation_at": request.at,
+                        "captured_at": chrono::Utc::now().timestamp(),
+                        "desktop": shot.bounds,
+                        "window": request.window,
+                        "width": width,
+                        "height": height,
+                    }),
+                    data: png,
+                })
+            });
+
+            match upload {
+                Ok(upload) => {
+                    if uploads.send(upload).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = app_tx.send(AppLogs::Info(e.to_string()));
+                }
+            }
+        }
+    });
+
+    tx
+}
+
+// the whole virtual desktop (every monitor) through GDI
+pub struct ScreenCapture;
+
+impl CaptureSource for ScreenCapture {
+    fn capture(&mut self) -> Result<Screenshot, EvidenceError> {
+        unsafe { capture_desktop() }
+    }
+}
+
+// PNG without alpha, scaled down to max_side. returns the bytes and the final size
+pub fn encode_png(image: &RgbaImage, max_side: u32) -> Result<(Vec<u8>, u32, u32), EvidenceError> {
+    let (width, height) = image.dimensions();
+    let scale = (max_side as f64 / width.max(height) as f64).min(1.0);
+
+    let rgb = if scale < 1.0 {
+        let w = ((width as f64 * scale) as u32).max(1);
+        let h = ((height as f64 * scale) as u32).max(1);
+        image::DynamicImage::ImageRgba8(imageops::resize(image, w, h, ResizeFilter::Triangle)).to_rgb8()
+    } else {
+        image::DynamicImage::ImageRgba8(image.clone()).to_rgb8()
+    };
+
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Best, FilterType::Adaptive)
+        .write_image(rgb.as_raw(), rgb.width(), rgb.height(), ExtendedColorType::Rgb8)
+        .map_err(|e| EvidenceError::Capture(format!("PNG encoding failed: {}", e)))?;
+
+    Ok((png, rgb.width(), rgb.height()))
+}
+
+// Helpers functions
+
+// GDI handles of one capture, released in reverse order
+struct DesktopDc {
+    screen: HDC,
+    memory: HDC,
+    bitmap: HBITMAP,
+    previous: HGDIOBJ,
+}
+
+impl Drop for DesktopDc {
+    fn drop(&mut self) {
+        unsafe {
+            SelectObject(self.memory, self.previous);
+            let _ = DeleteObject(self.bitmap.into());
+            let _ = DeleteDC(self.memory);
+            ReleaseDC(None, self.screen);
+        }
+    }
+}
+
+unsafe fn capture_desktop() -> Result<Screenshot, EvidenceError> {
+    unsafe {
+        let bounds = ScreenRect {
+            left: GetSystemMetrics(SM_XVIRTUALSCREEN),
+            top: GetSystemMetrics(SM_YVIRTUALSCREEN),
+            right: GetSystemMetrics(SM_XVIRTUALSCREEN) + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            bottom: GetSystemMetrics(SM_YVIRTUALSCREEN) + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        };
+        let (width, height) = (bounds.width(), bounds.height());
+        if width <= 0 || height <= 0 {
+            return Err(EvidenceError::Capture("no desktop to capture".into()));
+        }
+
+        let screen = GetDC(None);
+        if screen.is_invalid() {
+            return Err(EvidenceError::Capture("GetDC failed".into()));
+        }
+        let memory = CreateCompatibleDC(Some(screen));
+        let bitmap = CreateCompatibleBitmap(screen, width, height);
+        let previous = SelectObject(memory, bitmap.into());
+        let dc = DesktopDc { screen, memory, bitmap, previous };
+
+        // CAPTUREBLT includes layered windows (most overlays are)
+        BitBlt(dc.memory, 0, 0, width, height, Some(dc.screen), bounds.left, bounds.top, SRCCOPY | CAPTUREBLT)
+            .map_err(|e| EvidenceError::Capture(format!("BitBlt failed: {}", e)))?;
+
+        // negative height => top-down rows, 32 bit => BGRA
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        let lines = GetDIBits(
+            dc.memory,
+            dc.bitmap,
+            0,
+            height as u32,
+            Some(pixels.as_mut_ptr() as *mut _),
+            &mut info,
+            DIB_RGB_COLORS,
+        );
+        if lines != height {
+            return Err(EvidenceError::Capture(format!("GetDIBits copied {} of {} lines", lines, height)));
+        }
+
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+            px[3] = 255;
+        }
+
+        let image = RgbaImage::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| EvidenceError::Capture("bitmap size mismatch".into()))?;
+        Ok(Screenshot { image, bounds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::time::Duration;
+
+    // a two monitor desktop read from tests/fixtures, red on the left monitor, blue on the right
+    struct FixtureDesktop;
+
+    impl CaptureSource for FixtureDesktop {
+        fn capture(&mut self) -> Result<Screenshot, EvidenceError> {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/screenshot/desktop.png");
+            let image = image::open(path).map_err(|e| EvidenceError::Capture(e.to_string()))?.to_rgba8();
+            Ok(Screenshot { image, bounds: ScreenRect { left: -1920, top: 0, right: 1920, bottom: 400 } })
+        }
+    }
+
+    struct BrokenDesktop;
+
+    impl CaptureSource for BrokenDesktop {
+        fn capture(&mut self) -> Result<Screenshot, EvidenceError> {
+            Err(EvidenceError::Capture("BitBlt failed".into()))
+        }
+    }
+
+    fn request(window: Option<ScreenRect>) -> CaptureRequest {
+        CaptureRequest {
+            violation_id: "v-1".to_string(),
+            student_id: "st-1".to_string(),
+            session_id: "s-1".to_string(),
+            kind: ViolationType::Application,
+            message: "Blocked app: discord.exe".to_string(),
+            window,
+            at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn fixture_screenshot_is_scaled_and_uploaded() {
+        let (uploads, uploaded) = channel();
+        let (app_tx, _app_rx) = channel();
+        let capture = spawn_capture(Box::new(FixtureDesktop), uploads, app_tx);
+        let window = ScreenRect { left: -800, top: 100, right: -200, bottom: 300 };
+        capture.send(request(Some(window))).unwrap();
+
+        let upload = uploaded.recv_timeout(Duration::from_secs(10)).expect("no screenshot upload");
+        assert_eq!((upload.kind, upload.content_type), ("screenshot", "image/png"));
+        assert_eq!(upload.violation_id.as_deref(), Some("v-1"));
+        assert_eq!((upload.student_id.as_str(), upload.session_id.as_str()), ("st-1", "s-1"));
+        assert_eq!(upload.metadata["violation_kind"], "Application");
+        assert_eq!(upload.metadata["violation_at"], 1_700_000_000);
+        assert_eq!(upload.metadata["desktop"]["left"], -1920);
+        assert_eq!(upload.metadata["window"]["right"], -200);
+
+        // 3840x400 down to MAX_SIDE on the long side
+        assert_eq!((upload.metadata["width"].as_u64(), upload.metadata["height"].as_u64()), (Some(1920), Some(200)));
+        let png = image::load_from_memory(&upload.data).unwrap().to_rgb8();
+        assert_eq!(png.dimensions(), (1920, 200));
+        assert_eq!(png.get_pixel(10, 100).0, [255, 0, 0]);
+        assert_eq!(png.get_pixel(1910, 100).0, [0, 0, 255]);
+    }
+
+    #[test]
+    fn failed_capture_is_reported_not_uploaded() {
+        let (uploads, uploaded) = channel();
+        let (app_tx, app_rx) = channel();
+        let capture = spawn_capture(Box::new(BrokenDesktop), uploads, app_tx);
+        capture.send(request(None)).unwrap();
+
+        match app_rx.recv_timeout(Duration::from_secs(10)).expect("no report of the failure") {
+            AppLogs::Info(msg) => assert!(msg.contains("BitBlt failed"), "{}", msg),
+            _ => panic!("expected an info log"),
+        }
+        drop(capture);
+        assert!(uploaded.recv_timeout(Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn small_images_keep_their_size() {
+        let image = RgbaImage::from_pixel(64, 32, image::Rgba([1, 2, 3, 255]));
+        let (png, width, height) = encode_png(&image, MAX_SIDE).unwrap();
+        assert_eq!((width, height), (64, 32));
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgb8().get_pixel(0, 0).0, [1, 2, 3]);
+    }
+
+    #[test]
+    fn policy_captures_once_per_cooldown() {
+        let mut policy = CapturePolicy::new();
+        assert!(!policy.should_capture(ViolationType::Network, "dns", 0));
+        assert!(policy.should_capture(ViolationType::Application, "discord.exe", 0));
+        assert!(!policy.should_capture(ViolationType::Application, "discord.exe", CAPTURE_COOLDOWN_SECS - 1));
+        assert!(policy.should_capture(ViolationType::Application, "obs.exe", 1));
+        assert!(policy.should_capture(ViolationType::Application, "discord.exe", CAPTURE_COOLDOWN_SECS));
+    }
+}
@@ -0,0 +1,206 @@
+use windows::Win32::Foundation::{HLOCAL, LocalFree, NTSTATUS};
+use windows::Win32::Security::Cryptography::{
+    BCryptDecrypt, BCryptDestroyKey, BCryptEncrypt, BCryptGenRandom, BCryptGenerateSymmetricKey, BCryptHash,
+    CryptProtectData, CryptUnprotectData, BCRYPT_AES_GCM_ALG_HANDLE, BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO,
+    BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO_VERSION, BCRYPT_FLAGS, BCRYPT_KEY_HANDLE, BCRYPT_SHA256_ALG_HANDLE,
+    BCRYPT_USE_SYSTEM_PREFERRED_RNG, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+};
+
+use super::error::EvidenceError;
+
+// CNG (SHA-256, AES-256-GCM, system RNG) and DPAPI, nothing beyond what Windows ships
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, EvidenceError> {
+    let mut buf = vec![0u8; len];
+    let status = unsafe { BCryptGenRandom(None, &mut buf, BCRYPT_USE_SYSTEM_PREFERRED_RNG) };
+    check(status, "BCryptGenRandom")?;
+    Ok(buf)
+}
+
+pub fn sha256(parts: &[&[u8]]) -> Result<[u8; 32], EvidenceError> {
+    let input = parts.concat();
+    let mut out = [0u8; 32];
+    let status = unsafe { BCryptHash(BCRYPT_SHA256_ALG_HANDLE, None, &input, &mut out) };
+    check(status, "BCryptHash")?;
+    Ok(out)
+}
+
+// returns ciphertext || tag
+pub fn encrypt(key: &[u8], nonce: &[u8], aad: &[u8], plain: &[u8]) -> Result<Vec<u8>, EvidenceError> {
+    let handle = Key::new(key)?;
+    let mut nonce = nonce.to_vec();
+    let mut aad = aad.to_vec();
+    let mut tag = [0u8; TAG_LEN];
+    let info = cipher_info(&mut nonce, &mut aad, &mut tag);
+
+    let mut out = vec![0u8; plain.len()];
+    let mut written = 0u32;
+    let status = unsafe {
+        BCryptEncrypt(
+            handle.0,
+            Some(plain),
+            Some(&info as *const _ as *const core::ffi::c_void),
+            None,
+            Some(&mut out),
+            &mut written,
+            BCRYPT_FLAGS(0),
+        )
+    };
+    check(status, "BCryptEncrypt")?;
+
+    out.truncate(written as usize);
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+// fails on a wrong key or any modified byte (ciphertext, tag, nonce or aad)
+pub fn decrypt(key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, EvidenceError> {
+    if sealed.len() < TAG_LEN {
+        return Err(EvidenceError::Format("ciphertext shorter than its tag".into()));
+    }
+    let (cipher, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+
+    let handle = Key::new(key)?;
+    let mut nonce = nonce.to_vec();
+    let mut aad = aad.to_vec();
+    let mut tag = tag.to_vec();
+    let info = cipher_info(&mut nonce, &mut aad, &mut tag);
+
+    let mut out = vec![0u8; cipher.len()];
+    let mut written = 0u32;
+    let status = unsafe {
+        BCryptDecrypt(
+            handle.0,
+            Some(cipher),
+            Some(&info as *const _ as *const core::ffi::c_void),
+            None,
+            Some(&mut out),
+            &mut written,
+            BCRYPT_FLAGS(0),
+        )
+    };
+    check(status, "BCryptDecrypt")?;
+
+    out.truncate(written as usize);
+    Ok(out)
+}
+
+// machine scope: any administrator on this machine can export the log, the student's copy
+// of the file alone is useless elsewhere
+pub fn protect(data: &[u8]) -> Result<Vec<u8>, EvidenceError> {
+    dpapi(data, true)
+}
+
+pub fn unprotect(data: &[u8]) -> Result<Vec<u8>, EvidenceError> {
+    dpapi(data, false)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// works on the bytes, the string comes from a file anyone could have edited
+pub fn from_hex(s: &str) -> Result<Vec<u8>, EvidenceError> {
+    if !s.len().is_multiple_of(2) {
+        return Err(EvidenceError::Format("odd length hex".into()));
+    }
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .ok_or_else(|| EvidenceError::Format(format!("bad hex: byte 0x{:02x}", c)))
+    };
+    s.as_bytes().chunks_exact(2).map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8)).collect()
+}
+
+
+// Helpers functions
+
+// symmetric key handle, destroyed on drop
+struct Key(BCRYPT_KEY_HANDLE);
+
+impl Key {
+    fn new(secret: &[u8]) -> Result<Self, EvidenceError> {
+        let mut handle = BCRYPT_KEY_HANDLE::default();
+        let status = unsafe { BCryptGenerateSymmetricKey(BCRYPT_AES_GCM_ALG_HANDLE, &mut handle, None, secret, 0) };
+        check(status, "BCryptGenerateSymmetricKey")?;
+        Ok(Key(handle))
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = BCryptDestroyKey(self.0);
+        }
+    }
+}
+
+// the buffers have to outlive the returned struct, it only points into them
+fn cipher_info(nonce: &mut [u8], aad: &mut [u8], tag: &mut [u8]) -> BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO {
+    BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO {
+        cbSize: std::mem::size_of::<BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO>() as u32,
+        dwInfoVersion: BCRYPT_AUTHENTICATED_CIPHER_MODE_INFO_VERSION,
+        pbNonce: nonce.as_mut_ptr(),
+        cbNonce: nonce.len() as u32,
+        pbAuthData: aad.as_mut_ptr(),
+        cbAuthData: aad.len() as u32,
+        pbTag: tag.as_mut_ptr(),
+        cbTag: tag.len() as u32,
+        ..Default::default()
+    }
+}
+
+fn dpapi(data: &[u8], protect: bool) -> Result<Vec<u8>, EvidenceError> {
+    let input = CRYPT_INTEGER_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    let flags = CRYPTPROTECT_LOCAL_MACHINE | CRYPTPROTECT_UI_FORBIDDEN;
+
+    unsafe {
+        let result = if protect {
+            CryptProtectData(&input, windows::core::w!("BlackICE evidence key"), None, None, None, flags, &mut output)
+        } else {
+            CryptUnprotectData(&input, None, None, None, None, flags, &mut output)
+        };
+        result.map_err(|e| EvidenceError::Crypto { context: "DPAPI", code: e.code().0 })?;
+
+        let bytes = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+        let _ = LocalFree(Some(HLOCAL(output.pbData as *mut core::ffi::c_void)));
+        Ok(bytes)
+    }
+}
+
+fn check(status: NTSTATUS, context: &'static str) -> Result<(), EvidenceError> {
+    if status.is_ok() {
+        Ok(())
+    } else {
+        Err(EvidenceError::Crypto { context, code: status.0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trip() {
+        let bytes = [0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(to_hex(&bytes), "000fa5ff");
+        assert_eq!(from_hex("000fa5ff").unwrap(), bytes);
+        assert_eq!(from_hex("000FA5FF").unwrap(), bytes);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn bad_hex_is_an_error() {
+        assert!(matches!(from_hex("abc"), Err(EvidenceError::Format(_))));
+        assert!(matches!(from_hex("zz"), Err(EvidenceError::Format(_))));
+        assert!(matches!(from_hex("+1"), Err(EvidenceError::Format(_))));
+        // even length in bytes, a char boundary in the middle of a pair
+        assert!(matches!(from_hex("0é0"), Err(EvidenceError::Format(_))));
+        assert!(matches!(from_hex("éé"), Err(EvidenceError::Format(_))));
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum EvidenceError {
+    // CNG / DPAPI call failed, NTSTATUS or HRESULT
+    Crypto { context: &'static str, code: i32 },
+    Io(std::io::Error),
+    // a log file that doesn't parse
+    Format(String),
+    // screenshot could not be taken or encoded
+    Capture(String),
+    // the server didn't take an evidence upload
+    Upload(String),
+}
+
+impl fmt::Display for EvidenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvidenceError::Crypto { context, code } => write!(f, "[evidence]: {} Failed: 0x{:08X}", context, code),
+            EvidenceError::Io(e) => write!(f, "[evidence]: {}", e),
+            EvidenceError::Format(msg) => write!(f, "[evidence]: Invalid Log: {}", msg),
+            EvidenceError::Capture(msg) => write!(f, "[evidence]: Capture Failed: {}", msg),
+            EvidenceError::Upload(msg) => write!(f, "[evidence]: Upload Failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EvidenceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvidenceError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EvidenceError {
+    fn from(e: std::io::Error) -> Self {
+        EvidenceError::Io(e)
+    }
+}
@@ -0,0 +1,10 @@
+mod capture;
+mod crypto;
+mod error;
+mod session_log;
+mod upload;
+
+pub use capture::*;
+pub use error::EvidenceError;
+pub use session_log::*;
+pub use upload::*;
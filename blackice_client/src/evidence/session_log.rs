@@ -0,0 +1,358 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::crypto::{self, from_hex, to_hex, KEY_LEN, NONCE_LEN};
+use super::error::EvidenceError;
+use crate::network::snapshot;
+
+// append-only session log, one encrypted record per line. every record carries the hash of
+// the one before it, so a removed, reordered or edited line breaks the chain. the chain is
+// over the ciphertext, it can be checked without the key
+
+const EVIDENCE_DIR: &str = "evidence";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvidenceKind {
+    // a line of the GUI event log
+    Log,
+    // an entry handed to the reporter for the server
+    Report,
+    // what the reporter could (not) deliver
+    Delivery,
+    // a screenshot taken for a violation, with the hash of the uploaded file
+    Capture,
+}
+
+// the plaintext of a record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceEntry {
+    pub at: i64,
+    pub kind: EvidenceKind,
+    pub level: String,
+    pub message: String,
+    // set on Report entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+// where an entry ended up in the chain, sent along with the report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceRef {
+    pub seq: u64,
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    created_at: i64,
+    pid: u32,
+    // DPAPI protected AES-256 key
+    key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    seq: u64,
+    prev: String,
+    nonce: String,
+    data: String,
+    hash: String,
+}
+
+struct Writer {
+    file: File,
+    key: Vec<u8>,
+    seq: u64,
+    prev: [u8; 32],
+}
+
+pub struct EvidenceLog {
+    path: PathBuf,
+    writer: Mutex<Writer>,
+}
+
+impl EvidenceLog {
+    // a new log for this run under %ProgramData%\BlackICE\evidence
+    pub fn create() -> Result<Self, EvidenceError> {
+        Self::create_in(&snapshot::state_dir().join(EVIDENCE_DIR))
+    }
+
+    pub fn create_in(dir: &Path) -> Result<Self, EvidenceError> {
+        fs::create_dir_all(dir)?;
+
+        let created_at = chrono::Utc::now().timestamp();
+        let path = dir.join(format!("session_{}_{}.log", created_at, std::process::id()));
+
+        let key = crypto::random_bytes(KEY_LEN)?;
+        let header = Header {
+            version: FORMAT_VERSION,
+            created_at,
+            pid: std::process::id(),
+            key: to_hex(&crypto::protect(&key)?),
+        };
+        let header_line = serde_json::to_string(&header).map_err(|e| EvidenceError::Format(e.to_string()))?;
+
+        let mut file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+        writeln!(file, "{}", header_line)?;
+
+        // the header (and with it the key) is the first link of the chain
+        let prev = crypto::sha256(&[header_line.as_bytes()])?;
+        Ok(Self { path, writer: Mutex::new(Writer { file, key, seq: 0, prev }) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &EvidenceEntry) -> Result<EvidenceRef, EvidenceError> {
+        let plain = serde_json::to_vec(entry).map_err(|e| EvidenceError::Format(e.to_string()))?;
+        let mut w = self.writer.lock().unwrap_or_else(|p| p.into_inner());
+
+        let seq = w.seq + 1;
+        let nonce = crypto::random_bytes(NONCE_LEN)?;
+        // the previous hash as AAD, a record only decrypts at its own position
+        let data = crypto::encrypt(&w.key, &nonce, &w.prev, &plain)?;
+        let hash = record_hash(&w.prev, seq, &nonce, &data)?;
+
+        let record = Record { seq, prev: to_hex(&w.prev), nonce: to_hex(&nonce), data: to_hex(&data), hash: to_hex(&hash) };
+        let line = serde_json::to_string(&record).map_err(|e| EvidenceError::Format(e.to_string()))?;
+        writeln!(w.file, "{}", line)?;
+
+        w.seq = seq;
+        w.prev = hash;
+        Ok(EvidenceRef { seq, hash: record.hash })
+    }
+
+    pub fn log(&self, level: &str, message: &str) -> Result<EvidenceRef, EvidenceError> {
+        self.append(&EvidenceEntry::new(EvidenceKind::Log, level, message))
+    }
+}
+
+impl EvidenceEntry {
+    pub fn new(kind: EvidenceKind, level: &str, message: &str) -> Self {
+        Self {
+            at: chrono::Utc::now().timestamp(),
+            kind,
+            level: level.to_string(),
+            message: message.to_string(),
+            student_id: None,
+            session_id: None,
+        }
+    }
+}
+
+
+// Export
+
+#[derive(Debug, Serialize)]
+pub struct ExportedEntry {
+    pub seq: u64,
+    pub hash: String,
+    pub entry: EvidenceEntry,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvidenceExport {
+    pub path: String,
+    pub created_at: i64,
+    pub pid: u32,
+    // true if every record links to the one before it and decrypts
+    pub chain_valid: bool,
+    pub problems: Vec<String>,
+    pub entries: Vec<ExportedEntry>,
+}
+
+// decrypts and verifies a log, needs to run on the machine that wrote it (DPAPI)
+pub fn export(path: &Path) -> Result<EvidenceExport, EvidenceError> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let header_line = lines.next().ok_or_else(|| EvidenceError::Format("empty file".into()))??;
+    let header: Header = serde_json::from_str(&header_line).map_err(|e| EvidenceError::Format(e.to_string()))?;
+    if header.version != FORMAT_VERSION {
+        return Err(EvidenceError::Format(format!("unsupported version {}", header.version)));
+    }
+    let key = crypto::unprotect(&from_hex(&header.key)?)?;
+
+    let mut prev = crypto::sha256(&[header_line.as_bytes()])?;
+    let mut expected_seq = 1;
+    let mut problems = Vec::new();
+    let mut entries = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        // a crash mid-write can leave a torn last line, anything else is tampering
+        let record: Record = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(e) => {
+                problems.push(format!("line {}: unreadable record: {}", i + 2, e));
+                continue;
+            }
+        };
+
+        if record.seq != expected_seq {
+            problems.push(format!("seq {}: expected seq {}, records missing or reordered", record.seq, expected_seq));
+        }
+        expected_seq = record.seq + 1;
+        let [record_prev, nonce, data] = match decode_record(&record) {
+            Ok(fields) => fields,
+            // the next record won't link either, the chain is broken here
+            Err(e) => {
+                problems.push(format!("seq {}: {}", record.seq, e));
+                continue;
+            }
+        };
+        if record_prev != prev {
+            problems.push(format!("seq {}: does not link to the previous record", record.seq));
+        }
+
+        let hash = record_hash(&record_prev, record.seq, &nonce, &data)?;
+        if to_hex(&hash) != record.hash {
+            problems.push(format!("seq {}: hash mismatch, record modified", record.seq));
+        }
+
+        match crypto::decrypt(&key, &nonce, &record_prev, &data)
+            .and_then(|plain| serde_json::from_slice::<EvidenceEntry>(&plain).map_err(|e| EvidenceError::Format(e.to_string())))
+        {
+            Ok(entry) => entries.push(ExportedEntry { seq: record.seq, hash: record.hash.clone(), entry }),
+            Err(e) => problems.push(format!("seq {}: {}", record.seq, e)),
+        }
+
+        prev = hash;
+    }
+
+    Ok(EvidenceExport {
+        path: path.display().to_string(),
+        created_at: header.created_at,
+        pid: header.pid,
+        chain_valid: problems.is_empty(),
+        problems,
+        entries,
+    })
+}
+
+fn record_hash(prev: &[u8], seq: u64, nonce: &[u8], data: &[u8]) -> Result<[u8; 32], EvidenceError> {
+    crypto::sha256(&[prev, &seq.to_le_bytes(), nonce, data])
+}
+
+// prev, nonce and data of a record
+fn decode_record(record: &Record) -> Result<[Vec<u8>; 3], EvidenceError> {
+    Ok([from_hex(&record.prev)?, from_hex(&record.nonce)?, from_hex(&record.data)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a log with three entries in its own directory, removed on drop
+    struct TestLog {
+        dir: PathBuf,
+        path: PathBuf,
+        refs: Vec<EvidenceRef>,
+    }
+
+    impl TestLog {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("blackice-evidence-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let log = EvidenceLog::create_in(&dir).unwrap();
+            let refs = ["first", "second", "third"].iter().map(|m| log.log("INFO", m).unwrap()).collect();
+            Self { path: log.path().to_path_buf(), dir, refs }
+        }
+
+        fn lines(&self) -> Vec<String> {
+            fs::read_to_string(&self.path).unwrap().lines().map(str::to_string).collect()
+        }
+
+        fn rewrite(&self, lines: &[String]) {
+            fs::write(&self.path, lines.join("\n") + "\n").unwrap();
+        }
+    }
+
+    impl Drop for TestLog {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn has_problem(export: &EvidenceExport, needle: &str) -> bool {
+        export.problems.iter().any(|p| p.contains(needle))
+    }
+
+    #[test]
+    fn chain_round_trip() {
+        let log = TestLog::new("round-trip");
+        let export = export(&log.path).unwrap();
+
+        assert!(export.chain_valid, "{:?}", export.problems);
+        let messages: Vec<&str> = export.entries.iter().map(|e| e.entry.message.as_str()).collect();
+        assert_eq!(messages, ["first", "second", "third"]);
+        for (exported, appended) in export.entries.iter().zip(&log.refs) {
+            assert_eq!((exported.seq, &exported.hash), (appended.seq, &appended.hash));
+        }
+    }
+
+    #[test]
+    fn edited_line_is_reported() {
+        let log = TestLog::new("edited");
+        let mut lines = log.lines();
+        let mut record: Record = serde_json::from_str(&lines[2]).unwrap();
+        let flipped = if record.data.starts_with('0') { "1" } else { "0" };
+        record.data.replace_range(0..1, flipped);
+        lines[2] = serde_json::to_string(&record).unwrap();
+        log.rewrite(&lines);
+
+        let export = export(&log.path).unwrap();
+        assert!(!export.chain_valid);
+        assert!(has_problem(&export, "seq 2: hash mismatch"), "{:?}", export.problems);
+        assert_eq!(export.entries.len(), 2);
+    }
+
+    #[test]
+    fn removed_line_is_reported() {
+        let log = TestLog::new("removed");
+        let mut lines = log.lines();
+        lines.remove(2);
+        log.rewrite(&lines);
+
+        let export = export(&log.path).unwrap();
+        assert!(!export.chain_valid);
+        assert!(has_problem(&export, "seq 3: expected seq 2"), "{:?}", export.problems);
+        assert!(has_problem(&export, "seq 3: does not link"), "{:?}", export.problems);
+    }
+
+    #[test]
+    fn reordered_lines_are_reported() {
+        let log = TestLog::new("reordered");
+        let mut lines = log.lines();
+        lines.swap(2, 3);
+        log.rewrite(&lines);
+
+        let export = export(&log.path).unwrap();
+        assert!(!export.chain_valid);
+        assert!(has_problem(&export, "seq 3: expected seq 2"), "{:?}", export.problems);
+        assert!(has_problem(&export, "seq 2: expected seq 4"), "{:?}", export.problems);
+    }
+
+    #[test]
+    fn garbage_hex_is_a_problem_not_an_error() {
+        let log = TestLog::new("garbage");
+        let mut lines = log.lines();
+        let mut record: Record = serde_json::from_str(&lines[1]).unwrap();
+        record.nonce = "é".repeat(record.nonce.len() / 2);
+        lines[1] = serde_json::to_string(&record).unwrap();
+        log.rewrite(&lines);
+
+        // the rest of the log is still exported
+        let export = export(&log.path).unwrap();
+        let first = &export.problems[0];
+        assert!(first.starts_with("seq 1: ") && first.contains("bad hex"), "{:?}", export.problems);
+        assert!(has_problem(&export, "seq 2: does not link"), "{:?}", export.problems);
+        assert_eq!(export.entries.len(), 2);
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::crypto::{random_bytes, sha256, to_hex};
+use super::error::EvidenceError;
+use super::session_log::{EvidenceEntry, EvidenceKind, EvidenceLog};
+use crate::cloud_reporter::{AppLogs, Authorize};
+
+// binary evidence (screenshots, webcam frames) goes to the server through /api/evidence in
+// chunks. uploads that fail are kept and retried, a bounded number of them
+
+const PENDING_LIMIT: usize = 20;
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// ids for violations, sent with the report and the evidence so the server can link them
+pub fn new_violation_id() -> String {
+    match random_bytes(12) {
+        Ok(bytes) => to_hex(&bytes),
+        Err(_) => format!("{:x}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()),
+    }
+}
+
+// one blob for the server, size and hash are filled in by the uploader
+pub struct EvidenceUpload {
+    // one of the server's evidence kinds ("screenshot", "webcam" ...)
+    pub kind: &'static str,
+    pub content_type: &'static str,
+    pub student_id: String,
+    pub session_id: String,
+    pub violation_id: Option<String>,
+    pub metadata: Value,
+    pub data: Vec<u8>,
+}
+
+impl EvidenceUpload {
+    fn label(&self) -> String {
+        match &self.violation_id {
+            Some(id) => format!("{} for violation {}", self.kind, id),
+            None => self.kind.to_string(),
+        }
+    }
+}
+
+// an upload with its announcement, waiting for the server
+struct PendingUpload {
+    label: String,
+    announce: Value,
+    data: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct UploadStarted {
+    upload_id: String,
+    chunk_size: usize,
+}
+
+#[derive(Deserialize)]
+struct UploadCompleted {
+    evidence_id: String,
+}
+
+pub struct EvidenceUploader {
+    rx: Receiver<EvidenceUpload>,
+    client: Client,
+    server_url: String,
+    evidence: Option<Arc<EvidenceLog>>,
+    app_tx: Sender<AppLogs>,
+    pending: VecDeque<PendingUpload>,
+}
+
+impl EvidenceUploader {
+    pub fn spawn(server_url: String, evidence: Option<Arc<EvidenceLog>>, app_tx: Sender<AppLogs>) -> Sender<EvidenceUpload> {
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            let mut uploader = Self {
+                rx,
+                client: Client::new(),
+                server_url,
+                evidence,
+                app_tx,
+                pending: VecDeque::new(),
+            };
+            uploader.run();
+        });
+
+        tx
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.rx.recv_timeout(RETRY_INTERVAL) {
+                Ok(upload) => match self.prepare(upload) {
+                    Ok(pending) => {
+                        self.pending.push_back(pending);
+                        self.flush();
+                    }
+                    Err(e) => {
+                        let _ = self.app_tx.send(AppLogs::Info(e.to_string()));
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush();
+                    break;
+                }
+            }
+        }
+    }
+
+    // hashes the blob and keeps the hash in the evidence log before anything is sent
+    fn prepare(&self, upload: EvidenceUpload) -> Result<PendingUpload, EvidenceError> {
+        let hash = to_hex(&sha256(&[&upload.data])?);
+        let label = upload.label();
+
+        if let Some(evidence) = &self.evidence {
+            let mut entry = EvidenceEntry::new(EvidenceKind::Capture, &upload.kind.to_uppercase(), &format!(
+                "{}: {} bytes, sha256 {}", label, upload.data.len(), hash
+            ));
+            entry.student_id = Some(upload.student_id.clone());
+            entry.session_id = Some(upload.session_id.clone());
+            evidence.append(&entry)?;
+        }
+
+        let announce = json!({
+            "session_id": upload.session_id,
+            "student_id": upload.student_id,
+            "violation_id": upload.violation_id,
+            "kind": upload.kind,
+            "content_type": upload.content_type,
+            "size": upload.data.len(),
+            "sha256": hash,
+            "metadata": upload.metadata,
+        });
+
+        Ok(PendingUpload { label, announce, data: upload.data })
+    }
+
+    // oldest first, stops at the first failure (the server is most likely down)
+    fn flush(&mut self) {
+        while let Some(upload) = self.pending.pop_front() {
+            match self.upload(&upload) {
+                Ok(evidence_id) => {
+                    self.record(&upload, "EVIDENCE_UPLOADED", &format!("evidence {}", evidence_id));
+                    let _ = self.app_tx.send(AppLogs::Info(format!("[evidence]: Uploaded {}", upload.label)));
+                }
+                Err(e) => {
+                    self.record(&upload, "EVIDENCE_UPLOAD_FAILED", &e.to_string());
+                    self.pending.push_front(upload);
+                    break;
+                }
+            }
+        }
+
+        while self.pending.len() > PENDING_LIMIT {
+            if let Some(dropped) = self.pending.pop_front() {
+                self.record(&dropped, "EVIDENCE_DROPPED", "upload queue full");
+            }
+        }
+    }
+
+    // start, chunks, complete
+    fn upload(&self, upload: &PendingUpload) -> Result<String, EvidenceError> {
+        let started: UploadStarted = self
+            .client
+            .post(format!("{}/api/evidence", self.server_url))
+            .authorize()
+            .json(&upload.announce)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.json())
+            .map_err(|e| EvidenceError::Upload(e.to_string()))?;
+
+        for (index, chunk) in upload.data.chunks(started.chunk_size.max(1)).enumerate() {
+            self.client
+                .put(format!("{}/api/evidence/uploads/{}/{}", self.server_url, started.upload_id, index))
+                .authorize()
+                .body(chunk.to_vec())
+                .timeout(REQUEST_TIMEOUT)
+                .send()
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| EvidenceError::Upload(e.to_string()))?;
+        }
+
+        let completed: UploadCompleted = self
+            .client
+            .post(format!("{}/api/evidence/uploads/{}/complete", self.server_url, started.upload_id))
+            .authorize()
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.json())
+            .map_err(|e| EvidenceError::Upload(e.to_string()))?;
+
+        Ok(completed.evidence_id)
+    }
+
+    fn record(&self, upload: &PendingUpload, level: &str, detail: &str) {
+        let Some(evidence) = &self.evidence else { return };
+        let message = format!("{}: {}", upload.label, detail);
+        if let Err(e) = evidence.append(&EvidenceEntry::new(EvidenceKind::Delivery, level, &message)) {
+            eprintln!("{}", e);
+        }
+    }
+}
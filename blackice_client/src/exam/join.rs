@@ -0,0 +1,41 @@
+use std::time::Duration;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::cloud_reporter::SERVER_URL;
+
+// students sign in with the join code from their exam roster. the server answers with the
+// student and session ids and a token that only works for that student in that session
+
+// what the server hands out for a join code
+#[derive(Debug, Clone, Deserialize)]
+pub struct JoinedSession {
+    pub token: String,
+    pub student_id: String,
+    pub session_id: String,
+    pub name: String,
+    pub expires_at: i64,
+}
+
+pub fn redeem_join_code(code: &str) -> Result<JoinedSession, String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err("[exam] [join]: Enter the join code from your exam slip".to_string());
+    }
+
+    let resp = Client::new()
+        .post(format!("{}/api/join", SERVER_URL))
+        .json(&json!({ "join_code": code }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .map_err(|e| format!("[exam] [join]: Server unreachable: {}", e))?;
+
+    match resp.status() {
+        StatusCode::UNAUTHORIZED => Err("[exam] [join]: Unknown join code".to_string()),
+        StatusCode::FORBIDDEN => Err("[exam] [join]: This exam session is over".to_string()),
+        status if !status.is_success() => Err(format!("[exam] [join]: Server returned {}", status)),
+        _ => resp.json().map_err(|e| format!("[exam] [join]: Invalid answer: {}", e)),
+    }
+}
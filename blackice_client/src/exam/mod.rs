@@ -0,0 +1,9 @@
+pub mod join;
+pub mod schedule;
+pub mod state_machine;
+pub mod submit;
+
+pub use join::*;
+pub use schedule::*;
+pub use state_machine::*;
+pub use submit::*;
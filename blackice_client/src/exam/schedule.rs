@@ -0,0 +1,235 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+
+use crate::camera::CameraPolicy;
+use crate::cloud_reporter::{AppLogs, Authorize, SERVER_URL};
+
+// exam window from the server session. the client locks when it opens and releases when it
+// closes on its own, polling the server picks up per-student extensions from the proctor
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+// upper bound for the sleep so the stop signal is noticed
+const STOP_CHECK: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExamSchedule {
+    pub session_id: String,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub join_grace_secs: i64,
+    pub submit_grace_secs: i64,
+    pub warn_before_end_secs: i64,
+    // granted to this student by the proctor
+    pub extra_secs: i64,
+    pub server_time: i64,
+    // server_time - local time when fetched, filled in by fetch_schedule
+    #[serde(default)]
+    pub clock_offset: i64,
+    // webcam snapshots while the exam runs, none if the session doesn't use the camera
+    #[serde(default)]
+    pub webcam: Option<CameraPolicy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulePhase {
+    Upcoming { starts_in: i64 },
+    Open { remaining: i64 },
+    // inside the warning window before the end
+    Ending { remaining: i64 },
+    // time is up, the submission still has until the grace period ends
+    Grace { remaining: i64 },
+    Over,
+}
+
+impl ExamSchedule {
+    pub fn end(&self) -> i64 {
+        self.ends_at + self.extra_secs
+    }
+
+    // local clock corrected to server time
+    pub fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.clock_offset
+    }
+
+    pub fn phase(&self, now: i64) -> SchedulePhase {
+        let end = self.end();
+        if now < self.starts_at {
+            SchedulePhase::Upcoming { starts_in: self.starts_at - now }
+        } else if now < end - self.warn_before_end_secs {
+            SchedulePhase::Open { remaining: end - now }
+        } else if now < end {
+            SchedulePhase::Ending { remaining: end - now }
+        } else if now < end + self.submit_grace_secs {
+            SchedulePhase::Grace { remaining: end + self.submit_grace_secs - now }
+        } else {
+            SchedulePhase::Over
+        }
+    }
+
+    // late students can still enter until the join grace runs out
+    pub fn can_join(&self, now: i64) -> bool {
+        now >= self.starts_at && now < (self.starts_at + self.join_grace_secs).min(self.end())
+    }
+}
+
+// "1:02:05" / "12:40"
+pub fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{:02}:{:02}", m, s)
+    }
+}
+
+// Ok(None) when the session has no schedule, the exam is started manually then
+pub fn fetch_schedule(client: &Client, session_id: &str, student_id: &str) -> Result<Option<ExamSchedule>, String> {
+    let url = format!("{}/api/sessions/{}/schedule", SERVER_URL, session_id);
+    let resp = client
+        .get(&url)
+        .authorize()
+        .query(&[("student_id", student_id)])
+        .timeout(Duration::from_secs(10))
+        .send()
+        .map_err(|e| format!("[exam] [schedule]: Server unreachable: {}", e))?;
+
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(format!("[exam] [schedule]: Server returned {}", resp.status()));
+    }
+
+    let mut schedule: ExamSchedule = resp
+        .json()
+        .map_err(|e| format!("[exam] [schedule]: Invalid schedule: {}", e))?;
+    schedule.clock_offset = schedule.server_time - chrono::Utc::now().timestamp();
+    Ok(Some(schedule))
+}
+
+// blocks until keep_running is cleared. every poll also tells the server the student is online.
+// the last known schedule stays in effect while the server is unreachable
+pub fn run_schedule_poller(keep_running: Arc<AtomicBool>, tx: Sender<AppLogs>, session_id: String, student_id: String) {
+    let client = Client::new();
+    let mut failing = false;
+    let mut next_poll = Instant::now();
+
+    while keep_running.load(Ordering::Relaxed) {
+        if Instant::now() < next_poll {
+            thread::sleep(STOP_CHECK);
+            continue;
+        }
+        next_poll = Instant::now() + POLL_INTERVAL;
+
+        match fetch_schedule(&client, &session_id, &student_id) {
+            Ok(schedule) => {
+                failing = false;
+                if let Some(schedule) = schedule {
+                    let _ = tx.send(AppLogs::Schedule(schedule));
+                }
+            }
+            // reported once per outage. not an Error, that would re-enable the buttons mid-lockdown
+            Err(e) if !failing => {
+                failing = true;
+                let _ = tx.send(AppLogs::Info(e));
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SchedulePhase::*;
+
+    // 1000..4600 plus a 600s extension, so it ends at 5200
+    fn schedule(extra_secs: i64) -> ExamSchedule {
+        ExamSchedule {
+            session_id: "s-1".to_string(),
+            starts_at: 1000,
+            ends_at: 4600,
+            join_grace_secs: 900,
+            submit_grace_secs: 120,
+            warn_before_end_secs: 300,
+            extra_secs,
+            server_time: 1000,
+            clock_offset: 0,
+            webcam: None,
+        }
+    }
+
+    #[test]
+    fn phase_boundaries() {
+        let extended = schedule(600);
+        let cases = [
+            (0, Upcoming { starts_in: 1000 }),
+            (999, Upcoming { starts_in: 1 }),
+            (1000, Open { remaining: 4200 }),
+            (4600, Open { remaining: 600 }),
+            (4899, Open { remaining: 301 }),
+            (4900, Ending { remaining: 300 }),
+            (5199, Ending { remaining: 1 }),
+            (5200, Grace { remaining: 120 }),
+            (5319, Grace { remaining: 1 }),
+            (5320, Over),
+            (99_999, Over),
+        ];
+        for (now, phase) in cases {
+            assert_eq!(extended.phase(now), phase, "at {}", now);
+        }
+
+        let plain = schedule(0);
+        let cases = [
+            (4299, Open { remaining: 301 }),
+            (4300, Ending { remaining: 300 }),
+            (4600, Grace { remaining: 120 }),
+            (4720, Over),
+        ];
+        for (now, phase) in cases {
+            assert_eq!(plain.phase(now), phase, "at {}", now);
+        }
+    }
+
+    #[test]
+    fn no_warning_or_grace() {
+        let mut s = schedule(0);
+        s.warn_before_end_secs = 0;
+        s.submit_grace_secs = 0;
+        assert_eq!(s.phase(4599), Open { remaining: 1 });
+        assert_eq!(s.phase(4600), Over);
+    }
+
+    #[test]
+    fn join_window() {
+        let s = schedule(0);
+        let cases = [(999, false), (1000, true), (1899, true), (1900, false), (4600, false)];
+        for (now, can_join) in cases {
+            assert_eq!(s.can_join(now), can_join, "at {}", now);
+        }
+
+        // the join grace never outlasts the exam, an extension moves that end
+        let mut s = schedule(0);
+        s.join_grace_secs = 10_000;
+        assert!(s.can_join(4599));
+        assert!(!s.can_join(4600));
+        s.extra_secs = 600;
+        assert!(s.can_join(5199));
+        assert!(!s.can_join(5200));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(-5), "00:00");
+        assert_eq!(format_duration(59), "00:59");
+        assert_eq!(format_duration(12 * 60 + 40), "12:40");
+        assert_eq!(format_duration(3600 + 2 * 60 + 5), "1:02:05");
+    }
+}
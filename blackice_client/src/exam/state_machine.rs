@@ -0,0 +1,310 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// the exam as one explicit state machine. the GUI only feeds it events and carries out the
+// actions it returns, so lockdown and monitoring can't be switched independently anymore.
+// no egui / windows types in here
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExamState {
+    SignedOut,
+    // environment / network / process checks before the exam
+    PreCheck,
+    Ready,
+    // network locked and monitoring running, the exam itself
+    Locked,
+    Submitting,
+    // exam over, network restored
+    Released,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExamEvent {
+    SignIn { student_id: String, session_id: String },
+    PreCheckPassed,
+    PreCheckFailed(String),
+    RetryPreCheck,
+    Start,
+    // the lockdown transaction rolled back, back to Ready
+    LockdownFailed(String),
+    Submit,
+    Submitted,
+    SubmitFailed(String),
+    // the scheduled end (or the submission grace after it) passed
+    TimeUp,
+    SignOut,
+}
+
+// side effects the caller has to carry out, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExamAction {
+    RunPreCheck,
+    StartLockdown,
+    StartMonitoring,
+    StopMonitoring,
+    ReleaseLockdown,
+    SubmitExam,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: ExamState,
+    pub to: ExamState,
+    pub event: String,
+    pub at: i64,
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} -> {:?} ({})", self.from, self.to, self.event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalTransition {
+    pub state: ExamState,
+    pub event: String,
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[exam]: '{}' is not allowed while {:?}", self.event, self.state)
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
+pub struct ExamMachine {
+    state: ExamState,
+    student_id: Option<String>,
+    session_id: Option<String>,
+    last_error: Option<String>,
+}
+
+impl Default for ExamMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExamMachine {
+    pub fn new() -> Self {
+        Self { state: ExamState::SignedOut, student_id: None, session_id: None, last_error: None }
+    }
+
+    pub fn state(&self) -> ExamState {
+        self.state
+    }
+
+    pub fn student_id(&self) -> Option<&str> {
+        self.student_id.as_deref()
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    // why the last pre-check / lockdown / submission failed, cleared by the next transition
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    // applies an event, returns the transition and what has to happen for it.
+    // the state is left untouched when the event isn't allowed right now
+    pub fn handle(&mut self, event: ExamEvent) -> Result<(Transition, Vec<ExamAction>), IllegalTransition> {
+        use ExamAction::*;
+        use ExamState::*;
+
+        let (to, actions, error) = match (self.state, &event) {
+            (SignedOut, ExamEvent::SignIn { student_id, session_id }) => {
+                if student_id.trim().is_empty() || session_id.trim().is_empty() {
+                    return Err(self.illegal(&event));
+                }
+                self.student_id = Some(student_id.trim().to_string());
+                self.session_id = Some(session_id.trim().to_string());
+                (PreCheck, vec![RunPreCheck], None)
+            }
+
+            (PreCheck, ExamEvent::PreCheckPassed) => (Ready, vec![], None),
+            // stays in PreCheck until the student fixed it and retries
+            (PreCheck, ExamEvent::PreCheckFailed(reason)) => (PreCheck, vec![], Some(reason.clone())),
+            (PreCheck, ExamEvent::RetryPreCheck) => (PreCheck, vec![RunPreCheck], None),
+
+            // lockdown and monitoring only ever start together
+            (Ready, ExamEvent::Start) => (Locked, vec![StartLockdown, StartMonitoring], None),
+            (Locked, ExamEvent::LockdownFailed(reason)) => (Ready, vec![StopMonitoring], Some(reason.clone())),
+
+            // the network stays locked until the submission went through
+            (Locked, ExamEvent::Submit) => (Submitting, vec![SubmitExam], None),
+            (Submitting, ExamEvent::SubmitFailed(reason)) => (Locked, vec![], Some(reason.clone())),
+            (Submitting, ExamEvent::Submitted) => (Released, vec![StopMonitoring, ReleaseLockdown], None),
+
+            // the end submits on the student's behalf, once the grace is over the network
+            // is released whether the submission went through or not
+            (Locked, ExamEvent::TimeUp) => (Submitting, vec![SubmitExam], None),
+            (Submitting, ExamEvent::TimeUp) => (Released, vec![StopMonitoring, ReleaseLockdown], None),
+
+            (PreCheck | Ready | Released, ExamEvent::SignOut) => {
+                self.student_id = None;
+                self.session_id = None;
+                (SignedOut, vec![], None)
+            }
+
+            _ => return Err(self.illegal(&event)),
+        };
+
+        let transition = Transition {
+            from: self.state,
+            to,
+            event: event_name(&event).to_string(),
+            at: chrono::Utc::now().timestamp(),
+        };
+        self.state = to;
+        self.last_error = error;
+
+        Ok((transition, actions))
+    }
+
+    fn illegal(&self, event: &ExamEvent) -> IllegalTransition {
+        IllegalTransition { state: self.state, event: event_name(event).to_string() }
+    }
+}
+
+fn event_name(event: &ExamEvent) -> &'static str {
+    match event {
+        ExamEvent::SignIn { .. } => "SignIn",
+        ExamEvent::PreCheckPassed => "PreCheckPassed",
+        ExamEvent::PreCheckFailed(_) => "PreCheckFailed",
+        ExamEvent::RetryPreCheck => "RetryPreCheck",
+        ExamEvent::Start => "Start",
+        ExamEvent::LockdownFailed(_) => "LockdownFailed",
+        ExamEvent::Submit => "Submit",
+        ExamEvent::Submitted => "Submitted",
+        ExamEvent::SubmitFailed(_) => "SubmitFailed",
+        ExamEvent::TimeUp => "TimeUp",
+        ExamEvent::SignOut => "SignOut",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ExamAction::*;
+    use ExamState::*;
+
+    fn sign_in() -> ExamEvent {
+        ExamEvent::SignIn { student_id: " st-1 ".to_string(), session_id: "s-1".to_string() }
+    }
+
+    // a machine in the given state, reached through legal events only
+    fn machine_in(state: ExamState) -> ExamMachine {
+        let path: &[ExamEvent] = match state {
+            SignedOut => &[],
+            PreCheck => &[sign_in()][..],
+            Ready => &[sign_in(), ExamEvent::PreCheckPassed][..],
+            Locked => &[sign_in(), ExamEvent::PreCheckPassed, ExamEvent::Start][..],
+            Submitting => &[sign_in(), ExamEvent::PreCheckPassed, ExamEvent::Start, ExamEvent::Submit][..],
+            Released => {
+                &[sign_in(), ExamEvent::PreCheckPassed, ExamEvent::Start, ExamEvent::Submit, ExamEvent::Submitted][..]
+            }
+        };
+        let mut machine = ExamMachine::new();
+        for event in path {
+            machine.handle(event.clone()).unwrap();
+        }
+        assert_eq!(machine.state(), state);
+        machine
+    }
+
+    #[test]
+    fn legal_transitions() {
+        let failed = || "no".to_string();
+        let table: Vec<(ExamState, ExamEvent, ExamState, Vec<ExamAction>)> = vec![
+            (SignedOut, sign_in(), PreCheck, vec![RunPreCheck]),
+            (PreCheck, ExamEvent::PreCheckPassed, Ready, vec![]),
+            (PreCheck, ExamEvent::PreCheckFailed(failed()), PreCheck, vec![]),
+            (PreCheck, ExamEvent::RetryPreCheck, PreCheck, vec![RunPreCheck]),
+            (PreCheck, ExamEvent::SignOut, SignedOut, vec![]),
+            (Ready, ExamEvent::Start, Locked, vec![StartLockdown, StartMonitoring]),
+            (Ready, ExamEvent::SignOut, SignedOut, vec![]),
+            (Locked, ExamEvent::LockdownFailed(failed()), Ready, vec![StopMonitoring]),
+            (Locked, ExamEvent::Submit, Submitting, vec![SubmitExam]),
+            (Locked, ExamEvent::TimeUp, Submitting, vec![SubmitExam]),
+            (Submitting, ExamEvent::SubmitFailed(failed()), Locked, vec![]),
+            (Submitting, ExamEvent::Submitted, Released, vec![StopMonitoring, ReleaseLockdown]),
+            (Submitting, ExamEvent::TimeUp, Released, vec![StopMonitoring, ReleaseLockdown]),
+            (Released, ExamEvent::SignOut, SignedOut, vec![]),
+        ];
+
+        for (from, event, to, actions) in table {
+            let mut machine = machine_in(from);
+            let (transition, got) = machine.handle(event.clone()).unwrap();
+            assert_eq!((transition.from, transition.to), (from, to), "{:?} in {:?}", event, from);
+            assert_eq!(transition.event, event_name(&event));
+            assert_eq!(got, actions, "{:?} in {:?}", event, from);
+            assert_eq!(machine.state(), to);
+        }
+    }
+
+    #[test]
+    fn illegal_events_leave_the_state_alone() {
+        let table: Vec<(ExamState, ExamEvent)> = vec![
+            (SignedOut, ExamEvent::Start),
+            (SignedOut, ExamEvent::SignOut),
+            (PreCheck, ExamEvent::Start),
+            (PreCheck, sign_in()),
+            (Ready, ExamEvent::Submit),
+            (Ready, ExamEvent::TimeUp),
+            // the network can't be released by signing out mid-exam
+            (Locked, ExamEvent::SignOut),
+            (Locked, ExamEvent::Start),
+            (Locked, ExamEvent::Submitted),
+            (Submitting, ExamEvent::Submit),
+            (Submitting, ExamEvent::SignOut),
+            (Released, ExamEvent::Start),
+            (Released, ExamEvent::TimeUp),
+            (Released, ExamEvent::Submitted),
+        ];
+
+        for (state, event) in table {
+            let mut machine = machine_in(state);
+            let err = machine.handle(event.clone()).unwrap_err();
+            assert_eq!(err, IllegalTransition { state, event: event_name(&event).to_string() });
+            assert_eq!(machine.state(), state, "{:?} moved {:?}", event, state);
+        }
+    }
+
+    #[test]
+    fn sign_in_needs_both_ids() {
+        let mut machine = ExamMachine::new();
+        let blank = ExamEvent::SignIn { student_id: "  ".to_string(), session_id: "s-1".to_string() };
+        assert!(machine.handle(blank).is_err());
+        assert_eq!(machine.state(), SignedOut);
+
+        machine.handle(sign_in()).unwrap();
+        assert_eq!(machine.student_id(), Some("st-1"));
+        assert_eq!(machine.session_id(), Some("s-1"));
+    }
+
+    #[test]
+    fn sign_out_forgets_the_ids() {
+        let mut machine = machine_in(Released);
+        machine.handle(ExamEvent::SignOut).unwrap();
+        assert_eq!(machine.student_id(), None);
+        assert_eq!(machine.session_id(), None);
+    }
+
+    #[test]
+    fn failure_reason_is_kept_until_the_next_transition() {
+        let mut machine = machine_in(Submitting);
+        machine.handle(ExamEvent::SubmitFailed("server down".to_string())).unwrap();
+        assert_eq!(machine.last_error(), Some("server down"));
+
+        // an illegal event doesn't clear it
+        assert!(machine.handle(ExamEvent::Start).is_err());
+        assert_eq!(machine.last_error(), Some("server down"));
+
+        machine.handle(ExamEvent::Submit).unwrap();
+        assert_eq!(machine.last_error(), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use eframe::egui;
+
+use crate::evidence::EvidenceLog;
+
+// the raw event log. bounded, a long exam with a noisy monitor would otherwise grow it forever
+
+const MAX_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+    Violation,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [LogLevel::Info, LogLevel::Warning, LogLevel::Error, LogLevel::Violation];
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            LogLevel::Info => egui::Color32::GRAY,
+            LogLevel::Warning => egui::Color32::YELLOW,
+            LogLevel::Error => egui::Color32::RED,
+            LogLevel::Violation => egui::Color32::LIGHT_RED,
+        }
+    }
+}
+
+pub struct LogLine {
+    pub at: i64,
+    pub level: LogLevel,
+    pub text: String,
+}
+
+pub struct LogView {
+    lines: VecDeque<LogLine>,
+    // lines pushed out by the bound, shown so nobody thinks the log starts there
+    dropped: usize,
+    filter: String,
+    shown: [bool; 4],
+    // every line is also kept there, the bound above doesn't apply to it
+    evidence: Option<Arc<EvidenceLog>>,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogView {
+    pub fn new() -> Self {
+        Self {
+            lines: VecDeque::with_capacity(MAX_LINES),
+            dropped: 0,
+            filter: String::new(),
+            shown: [true; 4],
+            evidence: None,
+        }
+    }
+
+    pub fn with_evidence(evidence: Option<Arc<EvidenceLog>>) -> Self {
+        Self { evidence, ..Self::new() }
+    }
+
+    pub fn push(&mut self, level: LogLevel, text: impl Into<String>) {
+        let text = text.into();
+        if let Some(evidence) = &self.evidence
+            && let Err(e) = evidence.log(&format!("{:?}", level), &text)
+        {
+            eprintln!("{}", e);
+        }
+
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(LogLine { at: chrono::Utc::now().timestamp(), level, text });
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(LogLevel::Info, text);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(LogLevel::Warning, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(LogLevel::Error, text);
+    }
+
+    fn is_shown(&self, line: &LogLine, filter: &str) -> bool {
+        let level = LogLevel::ALL.iter().position(|l| *l == line.level).unwrap_or(0);
+        self.shown[level] && (filter.is_empty() || line.text.to_lowercase().contains(filter))
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.filter);
+            for (i, level) in LogLevel::ALL.iter().enumerate() {
+                ui.checkbox(&mut self.shown[i], format!("{:?}", level));
+            }
+        });
+
+        if self.dropped > 0 {
+            ui.label(format!("({} older lines dropped)", self.dropped));
+        }
+
+        let filter = self.filter.trim().to_lowercase();
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in self.lines.iter().filter(|l| self.is_shown(l, &filter)) {
+                    let time = chrono::DateTime::from_timestamp(line.at, 0)
+                        .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+                        .unwrap_or_default();
+                    ui.colored_label(line.level.color(), egui::RichText::new(format!("{} {}", time, line.text)).monospace());
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_is_bounded() {
+        let mut log = LogView::new();
+        for i in 0..MAX_LINES + 5 {
+            log.info(format!("line {}", i));
+        }
+
+        assert_eq!(log.lines.len(), MAX_LINES);
+        assert_eq!(log.dropped, 5);
+        assert_eq!(log.lines.front().map(|l| l.text.as_str()), Some("line 5"));
+        assert_eq!(log.lines.back().map(|l| l.text.as_str()), Some(format!("line {}", MAX_LINES + 4).as_str()));
+    }
+
+    #[test]
+    fn filter_and_level_toggles() {
+        let mut log = LogView::new();
+        log.info("[dns]: Proxy Started");
+        log.error("[network]: Lockdown Failed");
+        log.push(LogLevel::Violation, "BANNED WINDOW: 'Discord'");
+
+        let shown = |log: &LogView, filter: &str| {
+            log.lines.iter().filter(|l| log.is_shown(l, filter)).map(|l| l.level).collect::<Vec<LogLevel>>()
+        };
+        assert_eq!(shown(&log, ""), vec![LogLevel::Info, LogLevel::Error, LogLevel::Violation]);
+        // the filter is lowercased by show, the lines are matched lowercased
+        assert_eq!(shown(&log, "discord"), vec![LogLevel::Violation]);
+
+        log.shown[LogLevel::ALL.iter().position(|l| *l == LogLevel::Violation).unwrap()] = false;
+        assert_eq!(shown(&log, "discord"), Vec::<LogLevel>::new());
+        assert_eq!(shown(&log, ""), vec![LogLevel::Info, LogLevel::Error]);
+    }
+}
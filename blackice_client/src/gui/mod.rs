@@ -0,0 +1,5 @@
+pub mod log_view;
+pub mod violations;
+
+pub use log_view::*;
+pub use violations::*;
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use eframe::egui;
+
+use crate::cloud_reporter::ViolationType;
+
+// violations grouped by kind instead of scrolling past in the log. the monitors re-report a
+// violation on every scan while it lasts, one that stopped showing up counts as resolved
+
+// a few monitor scan intervals
+const RESOLVED_AFTER_SECS: i64 = 15;
+// distinct violations kept, the least recently seen go first
+const MAX_GROUPS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationStatus {
+    Active,
+    Resolved,
+}
+
+#[derive(Debug, Clone)]
+pub struct ViolationGroup {
+    pub kind: ViolationType,
+    // message with the varying numbers (pids, counts) masked
+    pub signature: String,
+    pub last_message: String,
+    pub count: u64,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+impl ViolationGroup {
+    pub fn status(&self, now: i64) -> ViolationStatus {
+        if now - self.last_seen > RESOLVED_AFTER_SECS {
+            ViolationStatus::Resolved
+        } else {
+            ViolationStatus::Active
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ViolationBoard {
+    groups: HashMap<(ViolationType, String), ViolationGroup>,
+}
+
+impl ViolationBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, kind: ViolationType, message: &str, now: i64) {
+        let signature = violation_signature(message);
+        let group = self.groups.entry((kind, signature.clone())).or_insert_with(|| ViolationGroup {
+            kind,
+            signature,
+            last_message: String::new(),
+            count: 0,
+            first_seen: now,
+            last_seen: now,
+        });
+        group.count += 1;
+        group.last_seen = now;
+        group.last_message = message.to_string();
+
+        if self.groups.len() > MAX_GROUPS
+            && let Some(oldest) = self.groups.iter().min_by_key(|(_, g)| g.last_seen).map(|(k, _)| k.clone())
+        {
+            self.groups.remove(&oldest);
+        }
+    }
+
+    // per kind: (kind, total count, active groups), kinds in declaration order
+    pub fn totals(&self, now: i64) -> Vec<(ViolationType, u64, usize)> {
+        ViolationType::ALL
+            .iter()
+            .filter_map(|kind| {
+                let groups: Vec<&ViolationGroup> = self.groups.values().filter(|g| g.kind == *kind).collect();
+                if groups.is_empty() {
+                    return None;
+                }
+                let count = groups.iter().map(|g| g.count).sum();
+                let active = groups.iter().filter(|g| g.status(now) == ViolationStatus::Active).count();
+                Some((*kind, count, active))
+            })
+            .collect()
+    }
+
+    // most recently seen first
+    pub fn groups_of(&self, kind: ViolationType) -> Vec<&ViolationGroup> {
+        let mut groups: Vec<&ViolationGroup> = self.groups.values().filter(|g| g.kind == kind).collect();
+        groups.sort_by_key(|g| std::cmp::Reverse(g.last_seen));
+        groups
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui) {
+        let now = chrono::Utc::now().timestamp();
+        let totals = self.totals(now);
+
+        if totals.is_empty() {
+            ui.colored_label(egui::Color32::GREEN, "No violations");
+            return;
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for (kind, count, active) in totals {
+                let header = format!("{:?}: {} ({} active)", kind, count, active);
+                egui::CollapsingHeader::new(header).default_open(active > 0).show(ui, |ui| {
+                    for group in self.groups_of(kind) {
+                        let (status, color) = match group.status(now) {
+                            ViolationStatus::Active => ("ACTIVE", egui::Color32::RED),
+                            ViolationStatus::Resolved => ("RESOLVED", egui::Color32::GRAY),
+                        };
+                        ui.colored_label(color, format!("[{}] x{} {}", status, group.count, group.last_message));
+                        ui.label(format!("      first {}, last {}", clock(group.first_seen), clock(group.last_seen)));
+                    }
+                });
+            }
+        });
+    }
+}
+
+// "BANNED WINDOW: 'discord' (PID: 4312)" and "... (PID: 9921)" are the same violation
+pub fn violation_signature(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut in_number = false;
+    for c in message.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                out.push('#');
+            }
+            in_number = true;
+        } else {
+            in_number = false;
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn clock(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_are_grouped_with_numbers_masked() {
+        let mut board = ViolationBoard::new();
+        board.record(ViolationType::Application, "BANNED WINDOW: 'discord' (PID: 4312)", 100);
+        board.record(ViolationType::Application, "BANNED WINDOW: 'discord' (PID: 9921)", 105);
+        board.record(ViolationType::Application, "BANNED WINDOW: 'chatgpt' (PID: 4312)", 106);
+        board.record(ViolationType::Environment, "BANNED WINDOW: 'discord' (PID: 4312)", 107);
+
+        let groups = board.groups_of(ViolationType::Application);
+        assert_eq!(groups.len(), 2);
+        let discord = groups.iter().find(|g| g.signature == "BANNED WINDOW: 'discord' (PID: #)").unwrap();
+        assert_eq!((discord.count, discord.first_seen, discord.last_seen), (2, 100, 105));
+        assert_eq!(discord.last_message, "BANNED WINDOW: 'discord' (PID: 9921)");
+        // most recently seen first
+        assert_eq!(groups[0].signature, "BANNED WINDOW: 'chatgpt' (PID: #)");
+        assert_eq!(board.groups_of(ViolationType::Environment).len(), 1);
+
+        assert_eq!(violation_signature("3 monitors, 1920x1080"), "# monitors, #x#");
+        assert_eq!(violation_signature("no numbers"), "no numbers");
+    }
+
+    #[test]
+    fn violations_resolve_once_they_stop_showing_up() {
+        let mut board = ViolationBoard::new();
+        board.record(ViolationType::Network, "DNS BLOCKED: example.com", 100);
+        board.record(ViolationType::Network, "DNS BLOCKED: example.org", 110);
+        board.record(ViolationType::Tamper, "WFP filter 'Block All Outbound' removed", 100);
+
+        let group = board.groups_of(ViolationType::Tamper)[0];
+        assert_eq!(group.status(100 + RESOLVED_AFTER_SECS), ViolationStatus::Active);
+        assert_eq!(group.status(100 + RESOLVED_AFTER_SECS + 1), ViolationStatus::Resolved);
+
+        assert_eq!(board.totals(110), vec![(ViolationType::Network, 2, 2), (ViolationType::Tamper, 1, 1)]);
+        let later = 110 + RESOLVED_AFTER_SECS;
+        assert_eq!(board.totals(later), vec![(ViolationType::Network, 2, 1), (ViolationType::Tamper, 1, 0)]);
+        // seen again, active again
+        board.record(ViolationType::Tamper, "WFP filter 'Block All Outbound' removed", later);
+        assert_eq!(board.totals(later)[1], (ViolationType::Tamper, 2, 1));
+    }
+
+    #[test]
+    fn least_recently_seen_group_is_evicted() {
+        let mut board = ViolationBoard::new();
+        for i in 0..MAX_GROUPS as i64 {
+            board.record(ViolationType::Application, &format!("BANNED APP: app-{}", char_name(i)), i);
+        }
+        // the oldest one comes back, the second oldest is now the least recently seen
+        board.record(ViolationType::Application, "BANNED APP: app-a", 1_000);
+        board.record(ViolationType::Other, "something new", 1_001);
+
+        assert_eq!(board.groups.len(), MAX_GROUPS);
+        let signatures: Vec<&str> = board.groups.values().map(|g| g.signature.as_str()).collect();
+        assert!(signatures.contains(&"BANNED APP: app-a"));
+        assert!(!signatures.contains(&"BANNED APP: app-b"));
+        assert!(signatures.contains(&"BANNED APP: app-c"));
+        assert!(signatures.contains(&"something new"));
+    }
+
+    // digits would be masked into one group, names made of letters stay apart
+    fn char_name(mut i: i64) -> String {
+        let mut name = String::new();
+        loop {
+            name.insert(0, (b'a' + (i % 26) as u8) as char);
+            i /= 26;
+            if i == 0 {
+                return name;
+            }
+        }
+    }
+}
@@ -1,36 +1,84 @@
 use eframe::egui;
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::sync::{
-    Arc,
+    Arc, Mutex,
     atomic::{AtomicBool, Ordering},
 };
+use std::mem::discriminant;
 use std::thread;
 
 pub mod applications;
+pub mod camera;
+pub mod cli;
 pub mod cloud_reporter;
 pub mod environment;
+pub mod evidence;
+pub mod exam;
+pub mod gui;
 pub mod network;
 
 pub use applications::*;
+pub use camera::*;
 pub use cloud_reporter::*;
 pub use environment::*;
+pub use evidence::*;
+pub use exam::*;
+pub use gui::*;
 pub use network::*;
 
 
 pub struct ProctorApp {
+    // decides when lockdown and monitoring run, the flags below only mirror what is running
+    exam: ExamMachine,
+    join_input: String,
+    student_input: String,
+    session_input: String,
+    // a join code is being redeemed
+    joining: bool,
+    join_error: Option<String>,
+
     net_active: bool,
     proc_active: bool,
     is_loading: bool,
-    logs: Vec<String>,
+    logs: LogView,
+    violations: ViolationBoard,
 
     watchdog_signal: Arc<AtomicBool>,
+    camera_signal: Arc<AtomicBool>,
+    // latest webcam snapshot result, shown while monitoring
+    last_camera: Option<FrameCheck>,
+    // one per lockdown, the proxy and TTL refresher of an earlier lockdown keep their own
     dns_signal: Arc<AtomicBool>,
+    dns_proxy: Option<thread::JoinHandle<()>>,
+    tamper_signal: Arc<AtomicBool>,
+    // re-lock automatically when the tamper watchdog finds drift (otherwise only report)
+    tamper_reapply: Arc<AtomicBool>,
 
     msg_sender: Sender<AppLogs>,
     msg_receiver: Receiver<AppLogs>,
     reporter_tx: Sender<LogEntry>,
+    // encrypted local copy of everything, None if it couldn't be created
+    evidence: Option<Arc<EvidenceLog>>,
+    // screenshots of violations, taken and uploaded off the UI thread
+    capture_tx: Sender<CaptureRequest>,
+    capture_policy: CapturePolicy,
+    // every evidence upload (screenshots, webcam frames) goes through it
+    uploads_tx: Sender<EvidenceUpload>,
+
+    // shared with the tamper watchdog, which re-reads (and can reapply) the filters
+    wfp_guard: Arc<Mutex<Option<network::WfpGuard>>>,
+    // set when the journal shows a previous run never restored the network
+    stale_lockdown: Option<network::JournalEntry>,
+    last_verification: Option<network::VerificationReport>,
+    // latest pre-exam checklist, shown until the exam starts
+    readiness: Option<ReadinessReport>,
 
-    wfp_guard: Option<network::WfpGuard>,
+    // None until the server sent one, or when the session is started manually
+    schedule: Option<ExamSchedule>,
+    schedule_signal: Arc<AtomicBool>,
+    // phase seen by the last tick, the end fires once per phase
+    schedule_phase: Option<SchedulePhase>,
+    end_warned: bool,
 }
 
 impl Drop for ProctorApp {
@@ -39,14 +87,19 @@ impl Drop for ProctorApp {
 
         // stop threads
         self.watchdog_signal.store(false, Ordering::Relaxed);
+        self.camera_signal.store(false, Ordering::Relaxed);
         self.dns_signal.store(false, Ordering::Relaxed);
+        self.tamper_signal.store(false, Ordering::Relaxed);
+        self.schedule_signal.store(false, Ordering::Relaxed);
 
         // restore internet
         if self.net_active {
             println!("Restoring Firewall Rules...");
-            if let Err(e) = network::reset_firewall() {
+            let mut guard = self.wfp_guard.lock().unwrap_or_else(|p| p.into_inner());
+            if let Err(e) = network::recover_network() {
                 eprintln!("FAILED TO RESTORE FIREWALL: {}", e);
             }
+            *guard = None;
         }
     }
 }
@@ -57,57 +110,222 @@ impl eframe::App for ProctorApp {
         while let Ok(msg) = self.msg_receiver.try_recv() {
             match msg {
                 AppLogs::Info(text) => {
-                    self.logs.push(format!("> {}", text));
+                    self.logs.info(format!("> {}", text));
                 },
 
                 AppLogs::Error(err) => {
-                    self.logs.push(format!("[ERROR]: {}", err));
+                    self.logs.error(format!("[ERROR]: {}", err));
                     self.is_loading = false; // re-enable buttons
                 },
 
-                AppLogs::Violation(v_type, text) => {
-                    let level_label = match v_type {
-                        ViolationType::Application => "VIOLATION_APP",
-                        ViolationType::Network => "VIOLATION_NET",
-                        ViolationType::Environment => "VIOLATION_ENV",
-                        ViolationType::Other => "VIOLATION_OTH",
-                    };
+                AppLogs::Violation(v_type, text) => self.on_violation(v_type, text, None),
 
-                    self.logs.push(format!("[{:?}] {}", v_type, text));
-
-                    // report to cloud server with category
-                    self.report(level_label, &text);
-                },
+                AppLogs::WindowViolation(v_type, text, window) => self.on_violation(v_type, text, Some(window)),
 
                 AppLogs::LockSuccess(guard) => {
-                    self.wfp_guard = Some(guard);
+                    network::journal::record(LockdownPhase::Locked);
+                    *self.wfp_guard.lock().unwrap_or_else(|p| p.into_inner()) = Some(guard);
                     self.net_active = true;
                     self.is_loading = false;
-                    self.logs.push("[network] [wfp]: NETWORK SECURED".into());
+                    self.logs.info("[network] [wfp]: NETWORK SECURED");
+                    self.report("LOCKDOWN_ACTIVE", "[network] [wfp]: NETWORK SECURED");
 
-                    // start DNS watchdog (has 60s refresher)
+                    // start DNS proxy (feeds resolved IPs into the allow-list)
+                    // and the TTL watchdog (re-resolves names as their records expire)
+                    self.start_dns_proxy();
                     self.start_dns_watchdog();
+                    self.start_tamper_watchdog();
+
+                    // check the block actually holds
+                    self.start_verification();
+                },
+
+                AppLogs::LockFailed(failure) => {
+                    self.net_active = false;
+                    self.is_loading = false;
+
+                    let summary = failure.summary();
+                    self.logs.error(format!("[ERROR]: {}", summary));
+                    if self.exam.state() == ExamState::Locked {
+                        self.dispatch(ExamEvent::LockdownFailed(summary.clone()));
+                    }
+
+                    // rollback left something behind, same recovery as after a crash
+                    let level = if failure.is_clean() {
+                        "LOCKDOWN_ROLLED_BACK"
+                    } else {
+                        self.stale_lockdown = network::journal::read();
+                        "LOCKDOWN_ROLLBACK_FAILED"
+                    };
+                    let detail = serde_json::to_string(&failure).unwrap_or(summary);
+                    self.report(level, &detail);
                 },
 
                 AppLogs::UnlockSuccess => {
-                    self.wfp_guard = None; // removes WFP filters
+                    network::journal::record(LockdownPhase::Idle);
+                    *self.wfp_guard.lock().unwrap_or_else(|p| p.into_inner()) = None; // removes WFP filters
                     self.net_active = false;
                     self.is_loading = false;
+                    self.stale_lockdown = None;
 
-                    // stop DNS watchdog
+                    // stop DNS proxy and watchdog
                     self.dns_signal.store(false, Ordering::Relaxed);
 
-                    self.logs.push("[network]: NETWORK UNLOCKED".into());
+                    self.last_verification = None;
+                    self.logs.info("[network]: NETWORK UNLOCKED");
                 },
+
+                AppLogs::Verification(report) => {
+                    let summary = format!("[network] [probe]: LOCKDOWN VERIFICATION {}", report.summary());
+                    if report.passed {
+                        self.logs.info(summary.clone());
+                    } else {
+                        self.logs.warn(summary.clone());
+                    }
+                    for r in report.results.iter().filter(|r| !r.passed) {
+                        self.logs.warn(format!(
+                            "[network] [probe]: {:?} -> {} expected {:?}, got {:?}",
+                            r.origin, r.label, r.expected, r.outcome
+                        ));
+                    }
+
+                    let level = if report.passed { "VERIFICATION_PASS" } else { "VERIFICATION_FAIL" };
+                    let detail = serde_json::to_string(&report).unwrap_or(summary);
+                    self.report(level, &detail);
+
+                    self.last_verification = Some(report);
+                },
+
+                AppLogs::Readiness(report) => {
+                    let summary = report.summary();
+                    if report.passed() {
+                        self.logs.info(summary.clone());
+                    } else {
+                        self.logs.warn(summary.clone());
+                    }
+
+                    // the first report of a session is its baseline, retries overwrite it
+                    let detail = serde_json::to_string(&report).unwrap_or(summary.clone());
+                    self.report("READINESS_BASELINE", &detail);
+
+                    let event = if report.passed() {
+                        ExamEvent::PreCheckPassed
+                    } else {
+                        ExamEvent::PreCheckFailed(summary)
+                    };
+                    self.readiness = Some(report);
+                    self.dispatch(event);
+                },
+
+                AppLogs::Schedule(schedule) => {
+                    let previous = self.schedule.as_ref().map(|s| s.extra_secs);
+                    match previous {
+                        None => self.logs.info(format!(
+                            "[exam] [schedule]: Exam window {} - {} (UTC)",
+                            timestamp_label(schedule.starts_at),
+                            timestamp_label(schedule.end())
+                        )),
+                        Some(extra) if extra != schedule.extra_secs => {
+                            let msg = format!(
+                                "[exam] [schedule]: Time extended by the proctor, new end {} (UTC)",
+                                timestamp_label(schedule.end())
+                            );
+                            self.logs.info(msg.clone());
+                            self.report("EXAM_EXTENDED", &msg);
+                        }
+                        _ => {}
+                    }
+                    self.schedule = Some(schedule);
+                },
+
+                AppLogs::Camera(check) => self.last_camera = Some(check),
+
+                AppLogs::Joined(result) => {
+                    self.joining = false;
+                    match result {
+                        Ok(joined) => {
+                            // everything sent from here on goes out with the student's own token
+                            set_server_token(Some(joined.token));
+                            self.join_input.clear();
+                            self.join_error = None;
+                            self.logs.info(format!(
+                                "[exam] [join]: Joined as {} ({}) in session {}",
+                                joined.name, joined.student_id, joined.session_id
+                            ));
+                            self.dispatch(ExamEvent::SignIn {
+                                student_id: joined.student_id,
+                                session_id: joined.session_id,
+                            });
+                        }
+                        Err(e) => {
+                            self.logs.error(format!("[ERROR]: {}", e));
+                            self.join_error = Some(e);
+                        }
+                    }
+                }
+
+                // the window closed while the submission was out, the network is already released
+                AppLogs::Exam(ExamEvent::Submitted) if self.exam.state() != ExamState::Submitting => {
+                    self.logs.info("[exam]: The submission reached the server after the exam ended");
+                }
+                AppLogs::Exam(ExamEvent::SubmitFailed(e)) if self.exam.state() != ExamState::Submitting => {
+                    self.logs.error(format!("[ERROR]: {}", e));
+                }
+                AppLogs::Exam(event) => self.dispatch(event),
             }
         }
 
+        self.tick_schedule();
+
         // egui UI Rendering
+        // ------------ Violations
+        egui::SidePanel::right("violations")
+            .resizable(true)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.heading("Violations");
+                ui.separator();
+                self.violations.show(ui);
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("");
             ui.add_space(10.0);
 
-            // ------------ Network Control
+            // ------------ Stale Lockdown Recovery
+            if let Some(stale) = &self.stale_lockdown
+                && !self.net_active
+            {
+                let phase = stale.phase;
+                ui.group(|ui| {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!("Previous session exited uncleanly ({:?}), network may still be locked", phase),
+                    );
+
+                    ui.add_space(5.0);
+                    if ui
+                        .add_enabled(
+                            !self.is_loading,
+                            egui::Button::new("Recover Network").min_size([ui.available_width(), 30.0].into()),
+                        )
+                        .clicked()
+                    {
+                        self.recover_network();
+                    }
+                });
+                ui.add_space(10.0);
+            }
+
+            // ------------ Exam
+            ui.group(|ui| {
+                ui.heading("Exam");
+                self.exam_ui(ui);
+            });
+
+            ui.add_space(10.0);
+
+            // ------------ Network Status
             ui.group(|ui| {
                 ui.heading("1. Network Access");
 
@@ -129,25 +347,43 @@ impl eframe::App for ProctorApp {
                     };
                     ui.colored_label(color, format!("Status: {}", status_text));
 
-                    ui.add_space(5.0);
-                    let btn_text = if self.net_active {
-                        "Restore Network"
-                    } else {
-                        "Block Network"
-                    };
+                    if self.net_active {
+                        match &self.last_verification {
+                            Some(report) => {
+                                let color = if report.passed {
+                                    egui::Color32::GREEN
+                                } else {
+                                    egui::Color32::RED
+                                };
+                                ui.colored_label(color, format!("Verification: {}", report.summary()));
+                            }
+                            None => {
+                                ui.label("Verification: Running...");
+                            }
+                        }
 
-                    if ui
-                        .add_sized([ui.available_width(), 30.0], egui::Button::new(btn_text))
-                        .clicked()
-                    {
-                        self.toggle_network();
+                        let mut reapply = self.tamper_reapply.load(Ordering::Relaxed);
+                        if ui.checkbox(&mut reapply, "Reapply Lockdown On Tampering").changed() {
+                            self.tamper_reapply.store(reapply, Ordering::Relaxed);
+                        }
+                    }
+
+                    // release failed after the exam ended, let the student retry it
+                    if self.net_active && self.exam.state() == ExamState::Released {
+                        ui.add_space(5.0);
+                        if ui
+                            .add_sized([ui.available_width(), 30.0], egui::Button::new("Restore Network"))
+                            .clicked()
+                        {
+                            self.toggle_network();
+                        }
                     }
                 }
             });
 
             ui.add_space(10.0);
 
-            // ------------ Processes Control
+            // ------------ Processes Status
             ui.group(|ui| {
                 ui.heading("2. Processes Monitor");
                 let status_text = if self.proc_active {
@@ -162,17 +398,9 @@ impl eframe::App for ProctorApp {
                 };
                 ui.colored_label(color, format!("Status: {}", status_text));
 
-                ui.add_space(5.0);
-                let btn_text = if self.proc_active {
-                    "⏹ Stop Monitor"
-                } else {
-                    "▶ Start Monitor"
-                };
-                if ui
-                    .add_sized([ui.available_width(), 30.0], egui::Button::new(btn_text))
-                    .clicked()
-                {
-                    self.toggle_process_monitor();
+                if let Some(check) = &self.last_camera {
+                    let color = if check.is_ok() { egui::Color32::GREEN } else { egui::Color32::RED };
+                    ui.colored_label(color, format!("Camera: {}", check.summary()));
                 }
             });
 
@@ -181,50 +409,99 @@ impl eframe::App for ProctorApp {
             ui.heading("Event Logs");
 
             // ------------ Logs
-            egui::ScrollArea::vertical()
-                .auto_shrink([false; 2])
-                .stick_to_bottom(true)
-                .show(ui, |ui| {
-                    for log in &self.logs {
-                        ui.monospace(log);
-                    }
-                });
+            self.logs.show(ui);
         });
 
-        // ensures smooth UI updates during loading/monitoring
-        if self.is_loading || self.proc_active {
+        // ensures smooth UI updates during loading/monitoring/checks
+        let waiting = matches!(self.exam.state(), ExamState::PreCheck | ExamState::Submitting);
+        if self.is_loading || self.joining || self.proc_active || waiting {
             ctx.request_repaint();
+        } else if self.schedule.is_some() {
+            // countdown, and the scheduled start / end fire without input
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
         }
     }
 }
 
 impl ProctorApp {
-    pub fn new(reporter_tx: Sender<LogEntry>) -> Self {
+    pub fn new(reporter_tx: Sender<LogEntry>, evidence: Option<Arc<EvidenceLog>>) -> Self {
         // channel for main and threads communication
         let (tx, rx) = channel::<AppLogs>();
+        let stale_lockdown = network::stale_lockdown();
+        let uploads_tx = EvidenceUploader::spawn(SERVER_URL.to_string(), evidence.clone(), tx.clone());
 
-        Self {
+        let mut app = Self {
+            exam: ExamMachine::new(),
+            join_input: String::new(),
+            student_input: String::new(),
+            session_input: String::new(),
+            joining: false,
+            join_error: None,
             net_active: false,
             proc_active: false,
             is_loading: false,
-            logs: vec!["[System Initialized]".into()],
+            logs: LogView::with_evidence(evidence.clone()),
+            violations: ViolationBoard::new(),
             watchdog_signal: Arc::new(AtomicBool::new(false)),
+            camera_signal: Arc::new(AtomicBool::new(false)),
+            last_camera: None,
             dns_signal: Arc::new(AtomicBool::new(false)),
+            dns_proxy: None,
+            tamper_signal: Arc::new(AtomicBool::new(false)),
+            tamper_reapply: Arc::new(AtomicBool::new(true)),
+            capture_tx: spawn_capture(Box::new(ScreenCapture), uploads_tx.clone(), tx.clone()),
+            capture_policy: CapturePolicy::new(),
+            uploads_tx,
             msg_sender: tx,
             msg_receiver: rx,
             reporter_tx,
-            wfp_guard: None,
+            evidence: evidence.clone(),
+            wfp_guard: Arc::new(Mutex::new(None)),
+            stale_lockdown: stale_lockdown.clone(),
+            last_verification: None,
+            readiness: None,
+            schedule: None,
+            schedule_signal: Arc::new(AtomicBool::new(false)),
+            schedule_phase: None,
+            end_warned: false,
+        };
+        app.logs.info("[System Initialized]");
+        match &evidence {
+            Some(log) => app.logs.info(format!("[evidence]: Session log at {}", log.path().display())),
+            None => app.logs.warn("[evidence]: Session log unavailable, events are only kept on the server"),
+        }
+
+        if let Some(stale) = stale_lockdown {
+            let since = chrono::DateTime::from_timestamp(stale.updated_at, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_else(|| "unknown".into());
+            let msg = format!(
+                "[network] [journal]: UNCLEAN EXIT DETECTED, previous session (pid {}) left the network {:?} since {}",
+                stale.pid, stale.phase, since
+            );
+
+            app.logs.error(format!("[ERROR]: {}", msg));
+            app.report("EVENT_UNCLEAN_EXIT", &msg);
         }
+
+        app
     }
 
     fn report(&self, level: &str, msg: &str) {
-        let log = LogEntry {
-            student_id: "student_123".to_string(), // Replace with dynamic ID
-            session_id: "session_abc".to_string(),
-            level: level.to_string(),
-            message: msg.to_string(),
-            timestamp: chrono::Utc::now().timestamp(),
-        };
+        self.report_as(
+            self.exam.student_id().unwrap_or("unknown"),
+            self.exam.session_id().unwrap_or("unknown"),
+            level,
+            msg,
+        );
+    }
+
+    fn report_as(&self, student_id: &str, session_id: &str, level: &str, msg: &str) {
+        self.send_report(student_id, session_id, level, msg, None);
+    }
+
+    fn send_report(&self, student_id: &str, session_id: &str, level: &str, msg: &str, violation_id: Option<String>) {
+        let log = self.log_entry(student_id, session_id, level, msg, violation_id);
 
         // .send() is non-blocking on unbounded channels, or mostly fast on buffered ones.
         // it simply pushes to the channel memory.
@@ -233,94 +510,481 @@ impl ProctorApp {
         }
     }
 
-    fn toggle_network(&mut self) {
-        if self.is_loading {
+    // writes the entry to the evidence log, the server copy refers to it
+    fn log_entry(
+        &self,
+        student_id: &str,
+        session_id: &str,
+        level: &str,
+        msg: &str,
+        violation_id: Option<String>,
+    ) -> LogEntry {
+        let evidence = self.evidence.as_ref().and_then(|log| {
+            let mut entry = EvidenceEntry::new(EvidenceKind::Report, level, msg);
+            entry.student_id = Some(student_id.to_string());
+            entry.session_id = Some(session_id.to_string());
+            log.append(&entry).map_err(|e| eprintln!("{}", e)).ok()
+        });
+
+        LogEntry {
+            student_id: student_id.to_string(),
+            session_id: session_id.to_string(),
+            level: level.to_string(),
+            message: msg.to_string(),
+            timestamp: chrono::Utc::now().timestamp(),
+            evidence_seq: evidence.as_ref().map(|e| e.seq),
+            evidence_hash: evidence.map(|e| e.hash),
+            violation_id,
+        }
+    }
+
+    // logs, groups and reports a violation, screenshots it when its kind is configured for that
+    fn on_violation(&mut self, v_type: ViolationType, text: String, window: Option<ScreenRect>) {
+        let level_label = match v_type {
+            ViolationType::Application => "VIOLATION_APP",
+            ViolationType::Network => "VIOLATION_NET",
+            ViolationType::Environment => "VIOLATION_ENV",
+            ViolationType::Tamper => "VIOLATION_TAMPER",
+            ViolationType::Other => "VIOLATION_OTH",
+        };
+        let now = chrono::Utc::now().timestamp();
+
+        self.logs.push(LogLevel::Violation, format!("[{:?}] {}", v_type, text));
+        self.violations.record(v_type, &text, now);
+
+        let student_id = self.exam.student_id().unwrap_or("unknown").to_string();
+        let session_id = self.exam.session_id().unwrap_or("unknown").to_string();
+        let violation_id = new_violation_id();
+
+        // nobody to attribute a screenshot to before sign-in
+        if self.exam.session_id().is_some()
+            && self.capture_policy.should_capture(v_type, &violation_signature(&text), now)
+        {
+            let request = CaptureRequest {
+                violation_id: violation_id.clone(),
+                student_id: student_id.clone(),
+                session_id: session_id.clone(),
+                kind: v_type,
+                message: text.clone(),
+                window,
+                at: now,
+            };
+            if self.capture_tx.send(request).is_err() {
+                self.logs.error("[ERROR]: [evidence]: Screenshot uploader stopped");
+            }
+        }
+
+        // report to cloud server with category
+        self.send_report(&student_id, &session_id, level_label, &text, Some(violation_id));
+    }
+
+    // feeds an event to the exam state machine and carries out the resulting actions
+    fn dispatch(&mut self, event: ExamEvent) {
+        // signing out forgets the ids, the transition itself still belongs to the student
+        let student = self.exam.student_id().unwrap_or("unknown").to_string();
+        let session = self.exam.session_id().unwrap_or("unknown").to_string();
+
+        let (transition, actions) = match self.exam.handle(event) {
+            Ok(result) => result,
+            Err(e) => {
+                self.logs.error(format!("[ERROR]: {}", e));
+                return;
+            }
+        };
+
+        self.logs.info(format!("[exam]: {}", transition));
+        if transition.from == ExamState::SignedOut {
+            self.start_schedule_poller();
+        } else if transition.to == ExamState::SignedOut {
+            self.schedule_signal.store(false, Ordering::Relaxed);
+            self.schedule = None;
+            self.schedule_phase = None;
+        }
+        let detail = serde_json::to_string(&transition).unwrap_or_else(|_| transition.to_string());
+        self.report_as(
+            self.exam.student_id().unwrap_or(&student),
+            self.exam.session_id().unwrap_or(&session),
+            "EXAM_TRANSITION",
+            &detail,
+        );
+
+        for action in actions {
+            self.perform(action);
+        }
+    }
+
+    // redeems the join code off the UI thread, the answer comes back as AppLogs::Joined
+    fn join(&mut self) {
+        if self.joining {
             return;
         }
-        self.is_loading = true;
+        self.joining = true;
+        self.join_error = None;
 
+        let code = self.join_input.clone();
         let tx = self.msg_sender.clone();
+        thread::spawn(move || {
+            tx.send(AppLogs::Joined(redeem_join_code(&code))).ok();
+        });
+    }
 
-        if !self.net_active {
-            // Locking Logic
-            self.log("[network]: Initializing Lockdown (Resolving DNS & Hashing Apps)...");
+    fn perform(&mut self, action: ExamAction) {
+        match action {
+            ExamAction::RunPreCheck => {
+                self.readiness = None;
+                let tx = self.msg_sender.clone();
+                thread::spawn(move || {
+                    tx.send(AppLogs::Readiness(environment::run_readiness_checks())).ok();
+                });
+            }
+            ExamAction::StartLockdown if !self.net_active => self.toggle_network(),
+            ExamAction::ReleaseLockdown if self.net_active => self.toggle_network(),
+            ExamAction::StartMonitoring if !self.proc_active => self.toggle_process_monitor(),
+            ExamAction::StopMonitoring if self.proc_active => self.toggle_process_monitor(),
+            // the answer comes back as Submitted / SubmitFailed
+            ExamAction::SubmitExam => {
+                let entry = self.log_entry(
+                    self.exam.student_id().unwrap_or("unknown"),
+                    self.exam.session_id().unwrap_or("unknown"),
+                    "EXAM_SUBMITTED",
+                    "[exam]: Exam submitted by student",
+                    None,
+                );
+                let tx = self.msg_sender.clone();
+                thread::spawn(move || {
+                    let event = match submit_exam(&entry) {
+                        Ok(()) => ExamEvent::Submitted,
+                        Err(e) => ExamEvent::SubmitFailed(e),
+                    };
+                    tx.send(AppLogs::Exam(event)).ok();
+                });
+            }
+            // already in the requested state
+            _ => {}
+        }
+    }
 
-            thread::spawn(move || {
-                // initialize WFP (App ID Hashing)
-                tx.send(AppLogs::Info(
-                    "[network] [wfp]: Generating App IDs...".into(),
-                ))
-                .ok();
+    // fires the scheduled start / end, called every frame
+    fn tick_schedule(&mut self) {
+        let Some(schedule) = &self.schedule else { return };
+        let now = schedule.now();
+        let phase = schedule.phase(now);
+        let can_join = schedule.can_join(now);
+        let entered = self.schedule_phase.is_none_or(|p| discriminant(&p) != discriminant(&phase));
+        self.schedule_phase = Some(phase);
 
-                let guard = match network::WfpGuard::new() {
-                    Ok(g) => {
-                        if let Err(e) = g.apply_ale_lockdown() {
-                            tx.send(AppLogs::Error(format!("[network] {}", e))).ok();
-                            return;
-                        }
-                        g
-                    }
-                    Err(e) => {
-                        tx.send(AppLogs::Error(format!("[network] {}", e))).ok();
-                        return;
-                    }
-                };
+        match (self.exam.state(), phase) {
+            // a failed lockdown isn't retried on its own, the student starts again manually
+            (ExamState::Ready, _) if can_join && !self.is_loading && self.exam.last_error().is_none() => {
+                self.logs.info("[exam] [schedule]: Exam started, locking down");
+                self.dispatch(ExamEvent::Start);
+            }
+            (ExamState::Locked, SchedulePhase::Ending { remaining }) if !self.end_warned => {
+                self.end_warned = true;
+                self.logs.warn(format!(
+                    "[exam] [schedule]: {} left, the exam is submitted automatically at the end",
+                    format_duration(remaining)
+                ));
+            }
+            // extended past the warning again
+            (_, SchedulePhase::Open { .. }) => self.end_warned = false,
+            // once per phase, a submission that failed in the grace is retried by the student
+            (ExamState::Locked, SchedulePhase::Grace { .. } | SchedulePhase::Over)
+            | (ExamState::Submitting, SchedulePhase::Over)
+                if entered =>
+            {
+                self.logs.warn("[exam] [schedule]: Time is up");
+                self.dispatch(ExamEvent::TimeUp);
+                // past the grace the network is released without waiting for the submission
+                if phase == SchedulePhase::Over && self.exam.state() == ExamState::Submitting {
+                    self.dispatch(ExamEvent::TimeUp);
+                }
+            }
+            _ => {}
+        }
+    }
 
-                // enable firewall (DNS Resolution)
-                tx.send(AppLogs::Info(
-                    "[network] [firewall rules]: Applying IP Rules...".into(),
-                ))
-                .ok();
+    fn schedule_ui(&self, ui: &mut egui::Ui) {
+        let Some(schedule) = &self.schedule else { return };
+        let now = schedule.now();
 
-                match network::apply_rules() {
-                    Ok(msg) => {
-                        tx.send(AppLogs::Info(format!(
-                            "[network] [firewall rules]: {}",
-                            msg
-                        )))
-                        .ok();
-                        // on success, send the guard to main thread
-                        tx.send(AppLogs::LockSuccess(guard)).ok();
+        let (text, color) = match schedule.phase(now) {
+            SchedulePhase::Upcoming { starts_in } => {
+                (format!("Exam starts in {}", format_duration(starts_in)), egui::Color32::GRAY)
+            }
+            SchedulePhase::Open { remaining } => {
+                (format!("Time remaining: {}", format_duration(remaining)), egui::Color32::GREEN)
+            }
+            SchedulePhase::Ending { remaining } => {
+                (format!("Time remaining: {} - ENDING SOON", format_duration(remaining)), egui::Color32::YELLOW)
+            }
+            SchedulePhase::Grace { remaining } => {
+                (format!("Time is up, submitting ({} grace left)", format_duration(remaining)), egui::Color32::RED)
+            }
+            SchedulePhase::Over => ("Exam window closed".to_string(), egui::Color32::RED),
+        };
+        ui.colored_label(color, text);
+
+        if self.exam.state() == ExamState::Ready && now >= schedule.starts_at && !schedule.can_join(now) {
+            ui.colored_label(egui::Color32::RED, "The late entry period is over, contact your proctor");
+        }
+    }
+
+    fn exam_ui(&mut self, ui: &mut egui::Ui) {
+        let full_width = |ui: &egui::Ui| [ui.available_width(), 30.0];
+
+        if let Some(err) = self.exam.last_error() {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+        self.schedule_ui(ui);
+
+        match self.exam.state() {
+            ExamState::SignedOut => {
+                if let Some(err) = &self.join_error {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Join Code:");
+                    ui.text_edit_singleline(&mut self.join_input);
+                });
+
+                ui.add_space(5.0);
+                let join = ui.add_enabled(!self.joining, egui::Button::new("Join Exam").min_size(full_width(ui).into()));
+                if join.clicked() {
+                    self.join();
+                }
+                if self.joining {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Joining...");
+                    });
+                }
+
+                // machines set up with a lab token (BLACKICE_TOKEN) can still sign in by id
+                ui.collapsing("Sign in with student and session ID", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Student ID:");
+                        ui.text_edit_singleline(&mut self.student_input);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Session ID:");
+                        ui.text_edit_singleline(&mut self.session_input);
+                    });
+
+                    ui.add_space(5.0);
+                    if ui.add_sized(full_width(ui), egui::Button::new("Sign In")).clicked() {
+                        self.dispatch(ExamEvent::SignIn {
+                            student_id: self.student_input.clone(),
+                            session_id: self.session_input.clone(),
+                        });
                     }
-                    Err(e) => {
-                        tx.send(AppLogs::Error(format!("[network] [firewall rules]: {}", e)))
-                            .ok();
+                });
+            }
+
+            ExamState::PreCheck => {
+                if self.exam.last_error().is_some() {
+                    if ui.add_sized(full_width(ui), egui::Button::new("Retry Checks")).clicked() {
+                        self.dispatch(ExamEvent::RetryPreCheck);
                     }
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Running Pre-Exam Checks...");
+                    });
+                }
+                if ui.button("Sign Out").clicked() {
+                    self.dispatch(ExamEvent::SignOut);
+                }
+                self.readiness_ui(ui);
+            }
+
+            ExamState::Ready => {
+                ui.label(format!(
+                    "Signed in as {} (session {}), all checks passed",
+                    self.exam.student_id().unwrap_or_default(),
+                    self.exam.session_id().unwrap_or_default()
+                ));
+                self.readiness_ui(ui);
+
+                ui.add_space(5.0);
+                // scheduled exams start on their own, the button is only for retries inside the window
+                let now_open = self.schedule.as_ref().is_none_or(|s| s.can_join(s.now()));
+                let start = ui.add_enabled(now_open && !self.is_loading, egui::Button::new("Start Exam").min_size(full_width(ui).into()));
+                if start.clicked() {
+                    self.dispatch(ExamEvent::Start);
+                }
+                if ui.button("Sign Out").clicked() {
+                    self.dispatch(ExamEvent::SignOut);
                 }
+            }
+
+            ExamState::Locked => {
+                ui.colored_label(egui::Color32::YELLOW, "Exam In Progress");
+
+                ui.add_space(5.0);
+                let submit = ui.add_enabled(!self.is_loading, egui::Button::new("Submit Exam").min_size(full_width(ui).into()));
+                if submit.clicked() {
+                    self.dispatch(ExamEvent::Submit);
+                }
+            }
+
+            ExamState::Submitting => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Submitting...");
+                });
+            }
+
+            ExamState::Released => {
+                ui.colored_label(egui::Color32::GREEN, "Exam Finished");
+                if ui.add_enabled(!self.net_active, egui::Button::new("Sign Out")).clicked() {
+                    self.dispatch(ExamEvent::SignOut);
+                }
+            }
+        }
+    }
+
+    fn readiness_ui(&self, ui: &mut egui::Ui) {
+        let Some(report) = &self.readiness else { return };
+
+        ui.add_space(5.0);
+        ui.label("Pre-Exam Checklist:");
+        for item in &report.items {
+            let (mark, color) = match item.status {
+                CheckStatus::Pass => ("PASS", egui::Color32::GREEN),
+                CheckStatus::Warn => ("WARN", egui::Color32::YELLOW),
+                CheckStatus::Fail => ("FAIL", egui::Color32::RED),
+            };
+            ui.colored_label(color, format!("[{}] {:?} / {}: {}", mark, item.category, item.name, item.detail));
+            if let Some(hint) = &item.hint {
+                ui.label(format!("      -> {}", hint));
+            }
+        }
+    }
+
+    fn start_schedule_poller(&mut self) {
+        let (Some(session), Some(student)) = (self.exam.session_id(), self.exam.student_id()) else { return };
+        let (session, student) = (session.to_string(), student.to_string());
+
+        // a poller left over from a previous sign-in exits on its next wake-up
+        self.schedule_signal = Arc::new(AtomicBool::new(true));
+        let signal = self.schedule_signal.clone();
+        let tx = self.msg_sender.clone();
+
+        thread::spawn(move || {
+            exam::run_schedule_poller(signal, tx, session, student);
+        });
+    }
+
+    fn toggle_network(&mut self) {
+        if self.is_loading {
+            return;
+        }
+        self.is_loading = true;
+
+        let tx = self.msg_sender.clone();
+
+        if !self.net_active {
+            // Locking Logic
+            self.log("[network]: Initializing Lockdown (Resolving DNS & Hashing Apps)...");
+
+            // all or nothing, a failed stage rolls back every stage before it
+            thread::spawn(move || match network::run_lockdown(&tx) {
+                Ok(guard) => tx.send(AppLogs::LockSuccess(guard)).ok(),
+                Err(failure) => tx.send(AppLogs::LockFailed(failure)).ok(),
             });
         } else {
             // UnLocking Logic
             self.log("[network]: Disabling Locks...");
-            thread::spawn(move || match network::reset_firewall() {
-                Ok(_) => tx.send(AppLogs::UnlockSuccess).ok(),
-                Err(e) => tx
-                    .send(AppLogs::Error(format!(
-                        "[network] [firewall rules] Unlock Failed: {}",
-                        e
-                    )))
-                    .ok(),
+            network::journal::record(LockdownPhase::Unlocking);
+
+            // stop the tamper watchdog first, removing our own rules is not tampering
+            self.tamper_signal.store(false, Ordering::Relaxed);
+            let wfp_guard = self.wfp_guard.clone();
+
+            thread::spawn(move || {
+                // waits out a tamper check (or reapply) that is still running
+                let _guard = wfp_guard.lock().unwrap_or_else(|p| p.into_inner());
+                match network::reset_firewall() {
+                    Ok(_) => tx.send(AppLogs::UnlockSuccess).ok(),
+                    Err(e) => tx
+                        .send(AppLogs::Error(format!(
+                            "[network] [firewall rules] Unlock Failed: {}",
+                            e
+                        )))
+                        .ok(),
+                }
             });
         }
     }
 
+    // restores whatever a killed / crashed session left behind
+    fn recover_network(&mut self) {
+        if self.is_loading {
+            return;
+        }
+        self.is_loading = true;
+        self.log("[network] [journal]: Recovering Network From Previous Session...");
+
+        let tx = self.msg_sender.clone();
+        thread::spawn(move || match network::recover_network() {
+            Ok(msg) => {
+                tx.send(AppLogs::Info(format!("[network] [journal]: {}", msg))).ok();
+                tx.send(AppLogs::UnlockSuccess).ok()
+            }
+            Err(e) => tx
+                .send(AppLogs::Error(format!("[network] [journal]: Recovery Failed: {}", e)))
+                .ok(),
+        });
+    }
+
+    fn start_verification(&mut self) {
+        let tx = self.msg_sender.clone();
+        let upstreams = network::upstream_servers();
+
+        thread::spawn(move || {
+            // give the DNS proxy a moment to bind before resolving through it
+            thread::sleep(std::time::Duration::from_secs(1));
+            tx.send(AppLogs::Verification(network::verify_lockdown(&upstreams))).ok();
+        });
+    }
+
+    fn start_dns_proxy(&mut self) {
+        self.dns_signal.store(false, Ordering::Relaxed);
+        self.dns_signal = Arc::new(AtomicBool::new(true));
+        let signal = self.dns_signal.clone();
+        let tx = self.msg_sender.clone();
+        let upstreams = network::upstream_servers();
+        let previous = self.dns_proxy.take();
+
+        self.dns_proxy = Some(thread::spawn(move || {
+            // the proxy of the last lockdown has to let go of port 53 first
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+            network::run_dns_proxy(signal, tx, upstreams);
+        }));
+    }
+
     fn start_dns_watchdog(&mut self) {
-        self.dns_signal.store(true, Ordering::Relaxed);
         let signal = self.dns_signal.clone();
+        let tx = self.msg_sender.clone();
+        let upstreams = network::upstream_servers();
 
         thread::spawn(move || {
-            while signal.load(Ordering::Relaxed) {
-                thread::sleep(std::time::Duration::from_secs(60));
+            network::run_ttl_refresher(signal, tx, upstreams);
+        });
+    }
 
-                if !signal.load(Ordering::Relaxed) {
-                    break;
-                }
+    fn start_tamper_watchdog(&mut self) {
+        self.tamper_signal.store(true, Ordering::Relaxed);
+        let signal = self.tamper_signal.clone();
+        let tx = self.msg_sender.clone();
+        let guard = self.wfp_guard.clone();
+        let reapply = self.tamper_reapply.clone();
 
-                if let Err(e) = network::refresh_whitelist() {
-                    eprintln!("[ERROR] DNS Watchdog Error: {}", e);
-                } else {
-                    println!("DNS Watchdog: Rules Refreshed.");
-                }
-            }
+        thread::spawn(move || {
+            network::run_tamper_watchdog(signal, tx, guard, reapply);
         });
     }
 
@@ -380,14 +1044,43 @@ impl ProctorApp {
                     thread::sleep(std::time::Duration::from_millis(500));
                 }
             });
+            self.start_camera();
         } else {
             self.proc_active = false;
             self.watchdog_signal.store(false, Ordering::Relaxed);
+            self.camera_signal.store(false, Ordering::Relaxed);
+            self.last_camera = None;
             self.log("[processes]: Stopping Monitor Thread...");
         }
     }
 
+    // webcam snapshots, only for sessions whose schedule asks for them
+    fn start_camera(&mut self) {
+        let Some(policy) = self.schedule.as_ref().and_then(|s| s.webcam) else { return };
+        let (Some(student_id), Some(session_id)) = (self.exam.student_id(), self.exam.session_id()) else { return };
+
+        self.camera_signal.store(true, Ordering::Relaxed);
+        let target = UploadTarget {
+            student_id: student_id.to_string(),
+            session_id: session_id.to_string(),
+            uploads: self.uploads_tx.clone(),
+        };
+        let _ = camera::spawn_camera_monitor(
+            || MediaFoundationCamera::open().map(|c| Box::new(c) as Box<dyn FrameSource>),
+            policy,
+            Some(target),
+            self.camera_signal.clone(),
+            self.msg_sender.clone(),
+        );
+    }
+
     fn log(&mut self, msg: &str) {
-        self.logs.push(format!("> {}", msg));
+        self.logs.info(format!("> {}", msg));
     }
 }
+
+fn timestamp_label(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
@@ -1,16 +1,39 @@
 use eframe::{egui, egui::IconData};
-use blackice_proctor::{ProctorApp, network, ReporterActor};
+use std::sync::Arc;
+use blackice_proctor::{cli, EvidenceLog, ProctorApp, network, ReporterActor, SERVER_URL};
 
 fn main() -> Result<(), eframe::Error> {
-    let original_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |panic_info| {
-        eprintln!("[main]: PANIC DETECTED! EMERGENCY FIREWALL RESET INITIATED");
-        let _ = network::reset_firewall(); 
-        original_hook(panic_info);
-    }));
+    // helper started by the post-lockdown probe, must not touch the network state (no panic hook)
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(network::probe::HELPER_SUBCOMMAND) {
+        std::process::exit(network::probe::run_helper(args.get(2).map(String::as_str).unwrap_or("[]")));
+    }
+
+    // read-only commands leave a lockdown owned by another process alone, even when panicking
+    let command = args.get(1).map(String::as_str);
+    if !command.is_some_and(|c| cli::READ_ONLY_COMMANDS.contains(&c)) {
+        let original_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic_info| {
+            eprintln!("[main]: PANIC DETECTED! EMERGENCY FIREWALL RESET INITIATED");
+            let _ = network::recover_network(); 
+            original_hook(panic_info);
+        }));
+    }
+
+    // headless mode (`check`, `lock`, `unlock`, `monitor`, `recover`), JSON on stdout, no GUI
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
 
     let icon = load_icon(include_bytes!("./app_icon.png"));
-    let reporter_tx = ReporterActor::spawn("http://localhost:3000/api/logs".to_string());
+    let evidence = match EvidenceLog::create() {
+        Ok(log) => Some(Arc::new(log)),
+        Err(e) => {
+            eprintln!("[main]: {}", e);
+            None
+        }
+    };
+    let reporter_tx = ReporterActor::spawn(format!("{}/api/logs", SERVER_URL), evidence.clone());
     
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -22,7 +45,7 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "BlackICE Proctor",
         options,
-        Box::new(|_cc| Ok(Box::new(ProctorApp::new(reporter_tx)))),
+        Box::new(|_cc| Ok(Box::new(ProctorApp::new(reporter_tx, evidence)))),
     )
 }
 
@@ -0,0 +1,238 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use windows::core::{GUID, PWSTR};
+use windows::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS, NO_ERROR};
+use windows::Win32::NetworkManagement::IpHelper::*;
+use windows::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+use windows::Win32::Networking::WinSock::{AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN, SOCKADDR_IN6};
+use super::error::NetworkError;
+use super::snapshot;
+
+// points every active adapter at the local DNS proxy and remembers what was there before
+
+// static name servers per adapter before we touched them (empty string = DHCP / automatic)
+// guid is kept as text so the list can be persisted in the network snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdapterDns {
+    pub guid: String,
+    pub name_server_v4: String,
+    pub name_server_v6: String,
+}
+
+static SAVED_ADAPTERS: Mutex<Vec<AdapterDns>> = Mutex::new(Vec::new());
+// DNS servers the adapters were really using, the proxy forwards allowed queries here
+static UPSTREAM_SERVERS: Mutex<Vec<IpAddr>> = Mutex::new(Vec::new());
+
+pub const PROXY_ADDR_V4: Ipv4Addr = Ipv4Addr::LOCALHOST;
+pub const PROXY_ADDR_V6: Ipv6Addr = Ipv6Addr::LOCALHOST;
+
+
+// captures upstream resolvers and switches all active adapters to the loopback proxy
+pub fn redirect_system_dns() -> Result<Vec<IpAddr>, NetworkError> {
+    let adapters = unsafe { active_adapters()? };
+
+    let mut upstreams: Vec<IpAddr> = Vec::new();
+    for (_, servers) in &adapters {
+        for ip in servers {
+            if !ip.is_loopback() && !upstreams.contains(ip) {
+                upstreams.push(*ip);
+            }
+        }
+    }
+
+    if upstreams.is_empty() {
+        return Err(NetworkError::Adapter("No upstream DNS servers found on active adapters".into()));
+    }
+
+    let mut saved = SAVED_ADAPTERS.lock().unwrap();
+    for (guid, _) in adapters {
+        let guid_text = format!("{:?}", guid);
+
+        // only save once, a second redirect must not overwrite the original settings with ours
+        if saved.iter().any(|s| s.guid == guid_text) {
+            continue;
+        }
+
+        unsafe {
+            let entry = AdapterDns {
+                guid: guid_text,
+                name_server_v4: get_name_server(guid, false),
+                name_server_v6: get_name_server(guid, true),
+            };
+
+            // persist before switching, a crash right after must still be recoverable
+            snapshot::record_dns_adapters(std::slice::from_ref(&entry));
+
+            set_name_server(guid, false, &PROXY_ADDR_V4.to_string())?;
+            set_name_server(guid, true, &PROXY_ADDR_V6.to_string())?;
+            saved.push(entry);
+        }
+    }
+
+    *UPSTREAM_SERVERS.lock().unwrap() = upstreams.clone();
+    Ok(upstreams)
+}
+
+// puts back the name servers captured by redirect_system_dns, returns how many adapters were restored
+pub fn restore_system_dns() -> usize {
+    let mut saved = match SAVED_ADAPTERS.lock() {
+        Ok(s) => s,
+        // called from the panic hook too, a poisoned lock still holds valid data
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let adapters: Vec<AdapterDns> = saved.drain(..).collect();
+    restore_adapters(&adapters)
+}
+
+// writes back a list of saved adapter settings (also used with a snapshot from a previous run)
+pub fn restore_adapters(adapters: &[AdapterDns]) -> usize {
+    let mut restored = 0;
+    for adapter in adapters {
+        let guid = match GUID::try_from(adapter.guid.as_str()) {
+            Ok(g) => g,
+            Err(_) => continue,
+        };
+
+        unsafe {
+            let v4 = set_name_server(guid, false, &adapter.name_server_v4);
+            let v6 = set_name_server(guid, true, &adapter.name_server_v6);
+            if v4.is_ok() && v6.is_ok() {
+                restored += 1;
+            } else {
+                eprintln!("[dns]: Failed to restore DNS for adapter {}", adapter.guid);
+            }
+        }
+    }
+
+    restored
+}
+
+pub fn upstream_servers() -> Vec<IpAddr> {
+    UPSTREAM_SERVERS.lock().map(|u| u.clone()).unwrap_or_default()
+}
+
+
+// Helpers functions
+
+// (adapter GUID, effective DNS servers) for every adapter that is up, loopback excluded
+unsafe fn active_adapters() -> Result<Vec<(GUID, Vec<IpAddr>)>, NetworkError> {
+    unsafe {
+        let mut buf_len: u32 = 15000;
+        let mut buffer: Vec<u8> = vec![0; buf_len as usize];
+        let mut ptr = buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH;
+
+        let mut ret = GetAdaptersAddresses(AF_UNSPEC.0 as u32, GAA_FLAG_INCLUDE_PREFIX, None, Some(ptr), &mut buf_len);
+
+        if ret == ERROR_BUFFER_OVERFLOW.0 {
+            buffer.resize(buf_len as usize, 0);
+            ptr = buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH;
+            ret = GetAdaptersAddresses(AF_UNSPEC.0 as u32, GAA_FLAG_INCLUDE_PREFIX, None, Some(ptr), &mut buf_len);
+        }
+
+        if ret != NO_ERROR.0 && ret != ERROR_SUCCESS.0 {
+            return Err(NetworkError::Adapter(format!("GetAdaptersAddresses failed: Code {}", ret)));
+        }
+
+        let mut adapters = Vec::new();
+        let mut current = ptr;
+        while !current.is_null() {
+            let adapter = &*current;
+            current = adapter.Next;
+
+            if adapter.OperStatus != IfOperStatusUp || adapter.IfType == IF_TYPE_SOFTWARE_LOOPBACK {
+                continue;
+            }
+
+            // AdapterName is the interface GUID in "{xxxxxxxx-...}" form
+            let name = adapter.AdapterName.to_string().unwrap_or_default();
+            let guid = match GUID::try_from(name.trim_matches(|c| c == '{' || c == '}')) {
+                Ok(g) => g,
+                Err(_) => continue,
+            };
+
+            let mut servers = Vec::new();
+            let mut dns = adapter.FirstDnsServerAddress;
+            while !dns.is_null() {
+                if let Some(ip) = sockaddr_to_ip(&(*dns).Address) {
+                    servers.push(ip);
+                }
+                dns = (*dns).Next;
+            }
+
+            adapters.push((guid, servers));
+        }
+
+        Ok(adapters)
+    }
+}
+
+unsafe fn sockaddr_to_ip(address: &windows::Win32::Networking::WinSock::SOCKET_ADDRESS) -> Option<IpAddr> {
+    unsafe {
+        let sockaddr = address.lpSockaddr;
+        if sockaddr.is_null() {
+            return None;
+        }
+
+        match (*sockaddr).sa_family {
+            f if f == AF_INET => {
+                let v4 = &*(sockaddr as *const SOCKADDR_IN);
+                Some(IpAddr::V4(Ipv4Addr::from(v4.sin_addr.S_un.S_addr.to_ne_bytes())))
+            }
+            f if f == AF_INET6 => {
+                let v6 = &*(sockaddr as *const SOCKADDR_IN6);
+                Some(IpAddr::V6(Ipv6Addr::from(v6.sin6_addr.u.Byte)))
+            }
+            _ => None,
+        }
+    }
+}
+
+unsafe fn get_name_server(guid: GUID, ipv6: bool) -> String {
+    unsafe {
+        let mut settings = DNS_INTERFACE_SETTINGS {
+            Version: DNS_INTERFACE_SETTINGS_VERSION1,
+            Flags: if ipv6 { DNS_SETTING_IPV6 as u64 } else { 0 },
+            ..Default::default()
+        };
+
+        if GetInterfaceDnsSettings(guid, &mut settings) != ERROR_SUCCESS {
+            return String::new();
+        }
+
+        let name_server = if settings.NameServer.is_null() {
+            String::new()
+        } else {
+            settings.NameServer.to_string().unwrap_or_default()
+        };
+
+        FreeInterfaceDnsSettings(&mut settings);
+        name_server
+    }
+}
+
+// an empty name_server resets the adapter back to automatic (DHCP) DNS
+unsafe fn set_name_server(guid: GUID, ipv6: bool, name_server: &str) -> Result<(), NetworkError> {
+    unsafe {
+        let mut wide: Vec<u16> = name_server.encode_utf16().chain(std::iter::once(0)).collect();
+
+        let mut flags = DNS_SETTING_NAMESERVER as u64;
+        if ipv6 {
+            flags |= DNS_SETTING_IPV6 as u64;
+        }
+
+        let settings = DNS_INTERFACE_SETTINGS {
+            Version: DNS_INTERFACE_SETTINGS_VERSION1,
+            Flags: flags,
+            NameServer: PWSTR(wide.as_mut_ptr()),
+            ..Default::default()
+        };
+
+        let err = SetInterfaceDnsSettings(guid, &settings);
+        if err != ERROR_SUCCESS {
+            return Err(NetworkError::Adapter(format!("SetInterfaceDnsSettings failed: Code {}", err.0)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, Sender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::dns_config::{PROXY_ADDR_V4, PROXY_ADDR_V6};
+use super::{dns_wire, doh, firewall_rules, ttl_resolver, whitelist};
+use crate::cloud_reporter::{AppLogs, ViolationType};
+
+// local DNS forwarder, whitelisted names are forwarded upstream and their addresses
+// go straight into the firewall allow-list, everything else gets NXDOMAIN
+
+const DNS_PORT: u16 = 53;
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+// how often the serving loop wakes up to check the stop signal
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// the OS resolver retries a lot, only report the same denied name once per window
+const DENIED_REPORT_WINDOW: Duration = Duration::from_secs(30);
+// upstream lookups in flight per socket, and queued behind them
+const FORWARD_WORKERS: usize = 8;
+const FORWARD_QUEUE: usize = 64;
+
+struct Lookup {
+    packet: Vec<u8>,
+    question: dns_wire::Question,
+    client: SocketAddr,
+}
+
+
+// blocks until keep_running is cleared, serves both loopback addresses
+pub fn run_dns_proxy(keep_running: Arc<AtomicBool>, tx: Sender<AppLogs>, upstreams: Vec<IpAddr>) {
+    let v4 = match bind(IpAddr::V4(PROXY_ADDR_V4)) {
+        Ok(s) => s,
+        Err(e) => {
+            let _ = tx.send(AppLogs::Error(format!("[network] [dns]: Proxy failed to start: {}", e)));
+            return;
+        }
+    };
+
+    // IPv6 is best effort, some machines have it disabled entirely
+    let v6 = match bind(IpAddr::V6(PROXY_ADDR_V6)) {
+        Ok(v6) => {
+            let (signal, tx, upstreams) = (keep_running.clone(), tx.clone(), upstreams.clone());
+            Some(thread::spawn(move || serve(v6, signal, tx, upstreams)))
+        }
+        Err(e) => {
+            println!("[dns]: IPv6 proxy socket unavailable: {}", e);
+            None
+        }
+    };
+
+    let _ = tx.send(AppLogs::Info(format!(
+        "[network] [dns]: DNS Proxy Active (upstream: {})",
+        upstreams.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(", ")
+    )));
+
+    serve(v4, keep_running, tx.clone(), upstreams);
+    // both sockets are closed when this returns, a new proxy can bind right away
+    if let Some(v6) = v6 {
+        let _ = v6.join();
+    }
+
+    let _ = tx.send(AppLogs::Info("[network] [dns]: DNS Proxy Stopped".into()));
+}
+
+fn bind(ip: IpAddr) -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(SocketAddr::new(ip, DNS_PORT))?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+fn serve(socket: UdpSocket, keep_running: Arc<AtomicBool>, tx: Sender<AppLogs>, upstreams: Vec<IpAddr>) {
+    let socket = Arc::new(socket);
+    let mut buffer = [0u8; 4096];
+    let mut denied_seen: HashMap<String, Instant> = HashMap::new();
+
+    // forwarded on a few workers so a slow upstream doesn't stall other lookups
+    let (lookups, queue) = sync_channel::<Lookup>(FORWARD_QUEUE);
+    let queue = Arc::new(Mutex::new(queue));
+    let workers: Vec<JoinHandle<()>> = (0..FORWARD_WORKERS)
+        .map(|_| {
+            let (queue, socket, tx, upstreams) = (queue.clone(), socket.clone(), tx.clone(), upstreams.clone());
+            thread::spawn(move || forward_lookups(queue, socket, tx, upstreams))
+        })
+        .collect();
+
+    while keep_running.load(Ordering::Relaxed) {
+        let (len, client) = match socket.recv_from(&mut buffer) {
+            Ok(r) => r,
+            // timeout, loop around and check the signal
+            Err(_) => continue,
+        };
+
+        let packet = buffer[..len].to_vec();
+        let question = match dns_wire::parse_query(&packet) {
+            Some(q) => q,
+            None => continue,
+        };
+
+        // resolver endpoints are refused even when a wildcard would allow them
+        let is_resolver = doh::is_doh_resolver_host(&question.name);
+
+        if is_resolver || !whitelist::is_whitelisted(&question.name) {
+            let _ = socket.send_to(&dns_wire::nxdomain(&packet, &question), client);
+
+            let now = Instant::now();
+            let recently_reported = denied_seen
+                .get(&question.name)
+                .is_some_and(|t| now.duration_since(*t) < DENIED_REPORT_WINDOW);
+
+            if !recently_reported {
+                denied_seen.insert(question.name.clone(), now);
+                let text = if is_resolver {
+                    format!("[network] [doh] DoH RESOLVER LOOKUP BLOCKED: '{}'", question.name)
+                } else {
+                    format!("[network] [dns] DENIED DNS QUERY: '{}'", question.name)
+                };
+                let _ = tx.send(AppLogs::Violation(ViolationType::Network, text));
+            }
+            continue;
+        }
+
+        // the client asks again when it gets no answer
+        if let Err(TrySendError::Full(lookup)) = lookups.try_send(Lookup { packet, question, client }) {
+            eprintln!("[dns]: Too many lookups in flight, dropped '{}'", lookup.question.name);
+        }
+    }
+
+    // the workers finish what they started, the socket is free once they are done
+    drop(lookups);
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+// worker: forwards queued lookups until the serving loop stops
+fn forward_lookups(
+    queue: Arc<Mutex<Receiver<Lookup>>>,
+    socket: Arc<UdpSocket>,
+    tx: Sender<AppLogs>,
+    upstreams: Vec<IpAddr>,
+) {
+    loop {
+        // the lock is only held while waiting for the next lookup
+        let lookup = match queue.lock().unwrap_or_else(|p| p.into_inner()).recv() {
+            Ok(lookup) => lookup,
+            Err(_) => return,
+        };
+        let Lookup { packet, question, client } = lookup;
+
+        let response = match forward(&packet, question.id, &upstreams) {
+            Some(r) => dns_wire::fit_truncated(&r),
+            None => {
+                eprintln!("[dns]: No upstream answered for '{}'", question.name);
+                continue;
+            }
+        };
+
+        // open the firewall before the browser gets the answer, otherwise the first connect races the rule
+        ttl_resolver::record(&question.name, &dns_wire::parse_addresses(&response));
+        match firewall_rules::sync_allowed_ips() {
+            Ok(Some((added, _))) if added > 0 => {
+                let _ = tx.send(AppLogs::Info(format!(
+                    "[network] [dns]: '{}' resolved, {} new IPs allowed",
+                    question.name, added
+                )));
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("[dns]: Failed to update allow-list: {}", e),
+        }
+
+        let _ = socket.send_to(&response, client);
+    }
+}
+
+// tries every upstream in order, returns the first response matching the query id
+pub(super) fn forward(packet: &[u8], id: u16, upstreams: &[IpAddr]) -> Option<Vec<u8>> {
+    for upstream in upstreams {
+        let local: SocketAddr = match upstream {
+            IpAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+            IpAddr::V6(_) => "[::]:0".parse().unwrap(),
+        };
+
+        let socket = match UdpSocket::bind(local) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        let _ = socket.set_read_timeout(Some(UPSTREAM_TIMEOUT));
+
+        if socket.send_to(packet, SocketAddr::new(*upstream, DNS_PORT)).is_err() {
+            continue;
+        }
+
+        let mut buffer = [0u8; 4096];
+        if let Ok(len) = socket.recv(&mut buffer)
+            && len >= 2
+            && u16::from_be_bytes([buffer[0], buffer[1]]) == id
+        {
+            return Some(buffer[..len].to_vec());
+        }
+    }
+    None
+}
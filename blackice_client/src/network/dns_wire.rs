@@ -0,0 +1,315 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// minimal DNS wire format helpers (RFC 1035), only what the proxy needs:
+// read the question of a query, build NXDOMAIN replies and pull A/AAAA records out of answers
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+
+const HEADER_LEN: usize = 12;
+const RCODE_NXDOMAIN: u8 = 3;
+// truncated, in the first flags byte
+const FLAG_TC: u8 = 0x02;
+// guards against compression pointer loops in hostile packets
+const MAX_POINTER_JUMPS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub id: u16,
+    pub name: String,
+    pub qtype: u16,
+    // offset right after the question section
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Answer {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+// builds a recursive query for one name, used when we refresh records ourselves
+pub fn build_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    // RD = 1, QDCOUNT = 1
+    let mut packet = vec![(id >> 8) as u8, id as u8, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+
+    for label in name.trim_end_matches('.').split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // class IN
+    packet
+}
+
+// returns the question of a standard query, None for anything malformed.
+// only the first question would be checked against the whitelist, so exactly one is allowed
+pub fn parse_query(packet: &[u8]) -> Option<Question> {
+    if packet.len() < HEADER_LEN || read_u16(packet, 4)? != 1 {
+        return None;
+    }
+
+    // QR bit set means this is a response, not a query
+    if packet[2] & 0x80 != 0 {
+        return None;
+    }
+
+    let (name, pos) = read_name(packet, HEADER_LEN)?;
+    let qtype = read_u16(packet, pos)?;
+    read_u16(packet, pos + 2)?; // qclass
+
+    Some(Question {
+        id: read_u16(packet, 0)?,
+        name,
+        qtype,
+        end: pos + 4,
+    })
+}
+
+// echoes the question back with rcode NXDOMAIN and no records
+pub fn nxdomain(query: &[u8], question: &Question) -> Vec<u8> {
+    let mut reply = query[..question.end].to_vec();
+
+    // QR = 1, keep opcode and RD from the query
+    reply[2] = 0x80 | (query[2] & 0x79);
+    // RA = 1
+    reply[3] = 0x80 | RCODE_NXDOMAIN;
+
+    // QDCOUNT = 1, ANCOUNT = NSCOUNT = ARCOUNT = 0
+    reply[4..12].copy_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    reply
+}
+
+// collects every A / AAAA record from the answer section of a response
+pub fn parse_addresses(response: &[u8]) -> Vec<Answer> {
+    parse_answers(response).map(|(answers, _, _)| answers).unwrap_or_default()
+}
+
+// the proxy has no TCP listener, a client told to retry over TCP would get nothing. a truncated
+// answer keeps its complete answer records and goes out without TC
+pub fn fit_truncated(response: &[u8]) -> Vec<u8> {
+    if response.len() < HEADER_LEN || response[2] & FLAG_TC == 0 {
+        return response.to_vec();
+    }
+    let Some((_, complete, end)) = parse_answers(response) else {
+        return response.to_vec();
+    };
+
+    let mut reply = response[..end].to_vec();
+    reply[2] &= !FLAG_TC;
+    reply[6..8].copy_from_slice(&complete.to_be_bytes());
+    // NSCOUNT = ARCOUNT = 0
+    reply[8..12].copy_from_slice(&[0, 0, 0, 0]);
+    reply
+}
+
+// stops at the first answer record that is cut off. returns the addresses, the number of
+// complete records and the offset after the last one
+fn parse_answers(response: &[u8]) -> Option<(Vec<Answer>, u16, usize)> {
+    let qdcount = read_u16(response, 4)?;
+    let ancount = read_u16(response, 6)?;
+    let mut pos = HEADER_LEN;
+
+    for _ in 0..qdcount {
+        let (_, next) = read_name(response, pos)?;
+        pos = next + 4;
+    }
+    if pos > response.len() {
+        return None;
+    }
+
+    let mut answers = Vec::new();
+    let mut complete = 0;
+    while complete < ancount {
+        let Some((rtype, ttl, rdata, end)) = read_record(response, pos) else { break };
+
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => answers.push(Answer {
+                ip: IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+                ttl,
+            }),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                answers.push(Answer { ip: IpAddr::V6(Ipv6Addr::from(octets)), ttl });
+            }
+            _ => {}
+        }
+
+        complete += 1;
+        pos = end;
+    }
+
+    Some((answers, complete, pos))
+}
+
+// type, ttl, data and the offset after a resource record
+fn read_record(packet: &[u8], pos: usize) -> Option<(u16, u32, &[u8], usize)> {
+    let (_, next) = read_name(packet, pos)?;
+    let rtype = read_u16(packet, next)?;
+    let ttl = read_u32(packet, next + 4)?;
+    let rdlen = read_u16(packet, next + 8)? as usize;
+    let rdata = packet.get(next + 10..next + 10 + rdlen)?;
+    Some((rtype, ttl, rdata, next + 10 + rdlen))
+}
+
+// reads a (possibly compressed) domain name, returns it lowercased and the offset after it
+fn read_name(packet: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *packet.get(pos)? as usize;
+
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+
+        // compression pointer (two high bits set)
+        if len & 0xC0 == 0xC0 {
+            let target = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+            jumps += 1;
+            if jumps > MAX_POINTER_JUMPS {
+                return None;
+            }
+            pos = target;
+            continue;
+        }
+
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        pos += 1 + len;
+    }
+
+    Some((labels.join("."), end.unwrap_or(pos)))
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    let bytes = packet.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(packet: &[u8], pos: usize) -> Option<u32> {
+    let bytes = packet.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the query turned into a response with the given A records, names as pointers to the question
+    fn response(query: &[u8], addresses: &[[u8; 4]], truncated: bool) -> Vec<u8> {
+        let mut packet = query.to_vec();
+        packet[2] = 0x81 | if truncated { FLAG_TC } else { 0 };
+        packet[3] = 0x80;
+        packet[6..8].copy_from_slice(&(addresses.len() as u16).to_be_bytes());
+        for ip in addresses {
+            packet.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+            packet.extend_from_slice(&TYPE_A.to_be_bytes());
+            packet.extend_from_slice(&1u16.to_be_bytes());
+            packet.extend_from_slice(&300u32.to_be_bytes());
+            packet.extend_from_slice(&4u16.to_be_bytes());
+            packet.extend_from_slice(ip);
+        }
+        packet
+    }
+
+    #[test]
+    fn parses_a_standard_query() {
+        let query = build_query(0xBEEF, "Assets.LeetCode.com.", TYPE_AAAA);
+        let question = parse_query(&query).unwrap();
+        assert_eq!(question.id, 0xBEEF);
+        assert_eq!(question.name, "assets.leetcode.com");
+        assert_eq!(question.qtype, TYPE_AAAA);
+        assert_eq!(question.end, query.len());
+    }
+
+    #[test]
+    fn rejects_anything_but_one_question() {
+        let query = build_query(1, "leetcode.com", TYPE_A);
+
+        let mut none = query.clone();
+        none[5] = 0;
+        assert!(parse_query(&none).is_none());
+
+        // a second question would never be checked against the whitelist
+        let mut two = query.clone();
+        two[5] = 2;
+        two.extend_from_slice(&query[HEADER_LEN..]);
+        assert!(parse_query(&two).is_none());
+
+        let mut reply = query.clone();
+        reply[2] |= 0x80;
+        assert!(parse_query(&reply).is_none());
+
+        assert!(parse_query(&query[..query.len() - 1]).is_none());
+        assert!(parse_query(&query[..HEADER_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn rejects_pointer_loops() {
+        let mut query = build_query(1, "a", TYPE_A);
+        // the name points at itself
+        query.truncate(HEADER_LEN);
+        query.extend_from_slice(&[0xC0, HEADER_LEN as u8, 0, 1, 0, 1]);
+        assert!(parse_query(&query).is_none());
+    }
+
+    #[test]
+    fn nxdomain_echoes_the_question() {
+        let mut query = build_query(0x1234, "example.com", TYPE_A);
+        // an EDNS record after the question is not echoed
+        query[11] = 1;
+        query.extend_from_slice(&[0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 0]);
+        let question = parse_query(&query).unwrap();
+        let reply = nxdomain(&query, &question);
+
+        assert_eq!(&reply[..2], &[0x12, 0x34]);
+        // QR and RD set, RCODE NXDOMAIN with RA
+        assert_eq!(reply[2], 0x81);
+        assert_eq!(reply[3], 0x80 | RCODE_NXDOMAIN);
+        assert_eq!(&reply[4..12], &[0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&reply[HEADER_LEN..], &query[HEADER_LEN..question.end]);
+    }
+
+    #[test]
+    fn parses_addresses_of_a_response() {
+        let query = build_query(7, "leetcode.com", TYPE_A);
+        let answers = parse_addresses(&response(&query, &[[1, 2, 3, 4], [5, 6, 7, 8]], false));
+        let ips: Vec<IpAddr> = answers.iter().map(|a| a.ip).collect();
+        assert_eq!(ips, ["1.2.3.4".parse::<IpAddr>().unwrap(), "5.6.7.8".parse().unwrap()]);
+        assert!(answers.iter().all(|a| a.ttl == 300));
+    }
+
+    #[test]
+    fn truncated_response_keeps_the_complete_records() {
+        let query = build_query(7, "leetcode.com", TYPE_A);
+        let full = response(&query, &[[1, 2, 3, 4], [5, 6, 7, 8]], true);
+        // the second record is cut off
+        let cut = &full[..full.len() - 2];
+
+        let fitted = fit_truncated(cut);
+        assert_eq!(fitted[2] & FLAG_TC, 0);
+        assert_eq!(&fitted[6..8], &[0, 1]);
+        assert_eq!(fitted.len(), full.len() - 16);
+        let ips: Vec<IpAddr> = parse_addresses(&fitted).iter().map(|a| a.ip).collect();
+        assert_eq!(ips, ["1.2.3.4".parse::<IpAddr>().unwrap()]);
+        assert_eq!(parse_addresses(cut).len(), 1);
+    }
+
+    #[test]
+    fn untruncated_response_is_left_alone() {
+        let query = build_query(7, "leetcode.com", TYPE_A);
+        let full = response(&query, &[[1, 2, 3, 4]], false);
+        assert_eq!(fit_truncated(&full), full);
+    }
+}
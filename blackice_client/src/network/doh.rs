@@ -0,0 +1,113 @@
+use std::net::IpAddr;
+
+// known DNS-over-HTTPS / DNS-over-TLS resolvers, a browser with secure DNS enabled would
+// otherwise resolve anything through an IP that happens to be allowed for a whitelisted CDN
+
+// DNS-over-TLS port, blocked for every process at the WFP layer
+pub const DOT_PORT: u16 = 853;
+
+// resolver host names, subdomains are matched too
+pub const DOH_RESOLVER_HOSTS: &[&str] = &[
+    // google
+    "dns.google",
+    "dns.google.com",
+    "dns64.dns.google",
+    // cloudflare
+    "cloudflare-dns.com",
+    "one.one.one.one",
+    "1dot1dot1dot1.cloudflare-dns.com",
+    // quad9
+    "dns.quad9.net",
+    "dns9.quad9.net",
+    "dns10.quad9.net",
+    "dns11.quad9.net",
+    // cisco opendns
+    "doh.opendns.com",
+    "doh.familyshield.opendns.com",
+    // adguard
+    "dns.adguard.com",
+    "dns.adguard-dns.com",
+    // nextdns / controld
+    "dns.nextdns.io",
+    "dns.controld.com",
+    "freedns.controld.com",
+    // others shipped in browser secure DNS presets
+    "doh.cleanbrowsing.org",
+    "doh.dns.sb",
+    "dns.mullvad.net",
+    "doh.mullvad.net",
+    "dns.alidns.com",
+    "doh.pub",
+    "ordns.he.net",
+    "doh.libredns.gr",
+];
+
+// resolver anycast addresses, never allowed in the TCP whitelist even if a whitelisted host resolves to them
+pub const DOH_RESOLVER_IPS: &[&str] = &[
+    // cloudflare
+    "1.1.1.1", "1.0.0.1", "1.1.1.2", "1.0.0.2", "1.1.1.3", "1.0.0.3",
+    "2606:4700:4700::1111", "2606:4700:4700::1001",
+    "2606:4700:4700::1112", "2606:4700:4700::1002",
+    "2606:4700:4700::1113", "2606:4700:4700::1003",
+    // google
+    "8.8.8.8", "8.8.4.4",
+    "2001:4860:4860::8888", "2001:4860:4860::8844",
+    // quad9
+    "9.9.9.9", "149.112.112.112", "9.9.9.10", "149.112.112.10", "9.9.9.11", "149.112.112.11",
+    "2620:fe::fe", "2620:fe::9", "2620:fe::10", "2620:fe::11",
+    // cisco opendns
+    "208.67.222.222", "208.67.220.220", "208.67.222.123", "208.67.220.123",
+    "2620:119:35::35", "2620:119:53::53",
+    // adguard
+    "94.140.14.14", "94.140.15.15", "94.140.14.15", "94.140.15.16",
+    "2a10:50c0::ad1:ff", "2a10:50c0::ad2:ff",
+    // cleanbrowsing
+    "185.228.168.168", "185.228.169.168", "185.228.168.9", "185.228.169.9",
+    // nextdns
+    "45.90.28.0", "45.90.30.0",
+    // mullvad
+    "194.242.2.2", "194.242.2.3",
+    // alidns
+    "223.5.5.5", "223.6.6.6",
+];
+
+
+pub fn is_doh_resolver_ip(ip: &IpAddr) -> bool {
+    DOH_RESOLVER_IPS
+        .iter()
+        .filter_map(|s| s.parse::<IpAddr>().ok())
+        .any(|known| known == *ip)
+}
+
+// "dns.google" and "eu.dns.nextdns.io" style names, checked before the whitelist
+pub fn is_doh_resolver_host(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+
+    DOH_RESOLVER_HOSTS.iter().any(|host| {
+        name == *host
+            || (name.len() > host.len() + 1
+                && name.ends_with(host)
+                && name.as_bytes()[name.len() - host.len() - 1] == b'.')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolver_hosts_and_their_subdomains_match() {
+        assert!(is_doh_resolver_host("dns.google"));
+        assert!(is_doh_resolver_host("DNS.Google."));
+        assert!(is_doh_resolver_host("eu.dns.google"));
+        assert!(!is_doh_resolver_host("notdns.google"));
+        assert!(!is_doh_resolver_host("google"));
+        assert!(!is_doh_resolver_host("www.google.com"));
+        assert!(!is_doh_resolver_host("leetcode.com"));
+    }
+
+    #[test]
+    fn every_listed_host_matches_itself() {
+        assert!(DOH_RESOLVER_HOSTS.iter().all(|h| is_doh_resolver_host(h)));
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+// everything the lockdown can fail with, callers match on the kind instead of parsing strings
+
+#[derive(Debug)]
+pub enum NetworkError {
+    // opening the engine, adding / enumerating / deleting filters, net event subscription
+    WfpEngine { context: String, code: u32 },
+    // FwpmTransactionBegin0 / FwpmTransactionCommit0
+    WfpTransaction { context: &'static str, code: u32 },
+    // FwpmGetAppIdFromFileName0 failed for a binary that has to be permitted
+    AppId { path: String, code: u32 },
+    // no whitelisted host resolved, nothing was changed
+    DnsResolution(String),
+    // reading or switching adapter DNS servers
+    Adapter(String),
+    // Windows Firewall COM calls (INetFwPolicy2 / INetFwRule)
+    Com(windows::core::Error),
+    // firewall state that can't be used as is (no snapshot, empty allow-list ...)
+    Policy(String),
+    // snapshot / journal files
+    Io(std::io::Error),
+}
+
+// ERROR_ACCESS_DENIED, BFE and the firewall refuse non elevated callers with it
+const ACCESS_DENIED: u32 = 5;
+
+impl NetworkError {
+    pub fn wfp(context: impl Into<String>, code: u32) -> Self {
+        NetworkError::WfpEngine { context: context.into(), code }
+    }
+
+    // true if running elevated would have avoided the error
+    pub fn is_access_denied(&self) -> bool {
+        match self {
+            NetworkError::WfpEngine { code, .. }
+            | NetworkError::WfpTransaction { code, .. }
+            | NetworkError::AppId { code, .. } => *code == ACCESS_DENIED,
+            // E_ACCESSDENIED
+            NetworkError::Com(e) => e.code().0 as u32 == 0x80070005,
+            NetworkError::Io(e) => e.kind() == std::io::ErrorKind::PermissionDenied,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::WfpEngine { context, code } => write!(f, "[wfp]: {}: Code {}", context, code),
+            NetworkError::WfpTransaction { context, code } => {
+                write!(f, "[wfp]: Transaction {} Failed: Code {}", context, code)
+            }
+            NetworkError::AppId { path, code } => {
+                write!(f, "[wfp]: Could not generate App ID for {}: Code {}", path, code)
+            }
+            NetworkError::DnsResolution(msg) => write!(f, "[dns]: {}", msg),
+            NetworkError::Adapter(msg) => write!(f, "[dns]: {}", msg),
+            NetworkError::Com(e) => write!(f, "[firewall]: {}", e),
+            NetworkError::Policy(msg) => write!(f, "[firewall]: {}", msg),
+            NetworkError::Io(e) => write!(f, "[io]: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetworkError::Com(e) => Some(e),
+            NetworkError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<windows::core::Error> for NetworkError {
+    fn from(e: windows::core::Error) -> Self {
+        NetworkError::Com(e)
+    }
+}
+
+impl From<std::io::Error> for NetworkError {
+    fn from(e: std::io::Error) -> Self {
+        NetworkError::Io(e)
+    }
+}
@@ -1,68 +1,256 @@
-use std::net::ToSocketAddrs;
-use windows::core::{BSTR, Result, HRESULT, Error};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::Mutex;
+use windows::core::BSTR;
 use windows::Win32::System::Com::*;
 use windows::Win32::NetworkManagement::WindowsFirewall::*;
+use super::error::NetworkError;
+use super::snapshot::{self, FirewallSnapshot};
+use super::{dns_config, doh, ttl_resolver, whitelist};
 
 
 const RULE_NAME: &str = "BlackICE_Firewall_TCP_Whitelist";
 const DNS_RULE_NAME: &str = "BlackICE_Firewall_DNS_Whitelist";
 const DHCP_RULE_NAME: &str = "BlackICE_Firewall_DHCP_Whitelist";
 
-const WHITELIST_DOMAINS: &[&str] = &[
-    // target sites
-    "codeforces.com:443",
-    "www.codeforces.com:443",
-    "leetcode.com:443",
-    "www.leetcode.com:443",
+// IPs currently written to the TCP whitelist rule, only rewritten when the resolver's set changes
+static ALLOWED_IPS: Mutex<BTreeSet<IpAddr>> = Mutex::new(BTreeSet::new());
 
-    // cloudflare security (codeforces uses it for auth)
-    "challenges.cloudflare.com:443",
 
-    // google recaptcha
-    "www.google.com:443",
-    "www.gstatic.com:443",
-    "fonts.gstatic.com:443",
-    "recaptcha.net:443",
-    "www.recaptcha.net:443",
+// this function resolves IPs and applies the Block Policy
+pub fn apply_rules() -> Result<String, NetworkError> {
+    unsafe {
+        // initialize COM library (imp for Windows APIs)
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    }
 
-    // CDNs
-    "cdnjs.cloudflare.com:443",
-    "fonts.googleapis.com:443",
-    "assets.leetcode.com:443",
-];
+    // resolve domains to IPs (seeds the TTL resolver), nothing is touched if this fails
+    ttl_resolver::reset();
+    seed_exact_hosts()?;
 
+    let ip_addresses = allowed_set();
+    if ip_addresses.is_empty() {
+        return Err(NetworkError::DnsResolution(
+            "Every resolved address belongs to a DNS-over-HTTPS resolver".into(),
+        ));
+    }
+
+    let count = ip_addresses.len();
+    let ip_list_str = join_ips(&ip_addresses);
+    // held until the rules are written, the set only changes once they are in place
+    let mut allowed = ALLOWED_IPS.lock().unwrap_or_else(|p| p.into_inner());
+
+    unsafe {
+        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
+        let rules: INetFwRules = policy.Rules()?;
+
+        // snapshot the original state before touching anything. a snapshot already on disk means
+        // an earlier lockdown was never restored, that one still holds the real original state
+        let snapshot = match FirewallSnapshot::load() {
+            Some(existing) => existing,
+            None => {
+                let fresh = FirewallSnapshot::capture(&policy)?;
+                fresh.save()?;
+                fresh
+            }
+        };
+        snapshot::disable_conflicting_rules(&policy, &snapshot)?;
+
+        // clean up old rules and write ours
+        add_lockdown_rules(&rules, &ip_list_str)?;
+
+        // set default policy to block
+        enable_strict_blocking(&policy)?;
+    }
+
+    *allowed = ip_addresses;
+    Ok(format!("[network] [firewall rules] Secure Mode Active. Allowed {} IPs.", count))
+}
+
+
+// this function re-reads the firewall state and lists everything that drifted from the lockdown.
+// an empty list means the rules, defaults and profiles are exactly as apply_rules left them
+pub fn check_rules() -> Result<Vec<String>, NetworkError> {
+    let mut drift = Vec::new();
+
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
+        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
+        let rules: INetFwRules = policy.Rules()?;
+
+        for profile in snapshot::PROFILES {
+            if !policy.get_FirewallEnabled(profile)?.as_bool() {
+                drift.push(format!("Firewall disabled for profile {}", profile.0));
+            }
+            if policy.get_DefaultOutboundAction(profile)? != NET_FW_ACTION_BLOCK {
+                drift.push(format!("Default outbound action changed for profile {}", profile.0));
+            }
+        }
+
+        // holding the lock keeps sync_allowed_ips from rewriting the rule mid-check
+        let allowed = ALLOWED_IPS.lock().unwrap_or_else(|p| p.into_inner());
+        for name in [RULE_NAME, DNS_RULE_NAME, DHCP_RULE_NAME] {
+            let rule = match rules.Item(&BSTR::from(name)) {
+                Ok(r) => r,
+                Err(_) => {
+                    drift.push(format!("Rule '{}' deleted", name));
+                    continue;
+                }
+            };
+
+            if !rule.Enabled()?.as_bool() {
+                drift.push(format!("Rule '{}' disabled", name));
+            }
+            if rule.Action()? != NET_FW_ACTION_ALLOW || rule.Direction()? != NET_FW_RULE_DIR_OUT {
+                drift.push(format!("Rule '{}' modified", name));
+            }
+            if name == RULE_NAME && parse_remote_addresses(&rule.RemoteAddresses()?.to_string()) != *allowed {
+                drift.push(format!("Rule '{}' remote addresses modified", name));
+            }
+        }
+        drop(allowed);
+
+        for key in snapshot::find_conflicting_rules(&policy)? {
+            drift.push(format!("Outbound allow rule '{}' enabled", key.name));
+        }
+    }
+
+    Ok(drift)
+}
+
+
+// this function puts the lockdown back after tampering without resolving anything again:
+// profiles on, defaults to block, new allow rules off, our rules rewritten from the current set
+pub fn enforce_rules() -> Result<String, NetworkError> {
+    let ip_list_str = {
+        let allowed = ALLOWED_IPS.lock().unwrap_or_else(|p| p.into_inner());
+        if allowed.is_empty() {
+            return Err(NetworkError::Policy("Lockdown not active, nothing to enforce".into()));
+        }
+        join_ips(&allowed)
+    };
 
-// this function resolves IPs and applies the Block Policy
-pub fn apply_rules() -> Result<String> {
     unsafe {
-        // initialize COM library (imp for Windows APIs)
         let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
+        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
+        let rules: INetFwRules = policy.Rules()?;
+
+        for profile in snapshot::PROFILES {
+            policy.put_FirewallEnabled(profile, true.into())?;
+        }
+        enable_strict_blocking(&policy)?;
+
+        let conflicting = snapshot::find_conflicting_rules(&policy)?;
+        let disabled = snapshot::disable_new_conflicting_rules(&policy, &conflicting)?;
+
+        add_lockdown_rules(&rules, &ip_list_str)?;
+
+        Ok(format!(
+            "[network] [firewall rules] Lockdown Reapplied ({} allow rules disabled).",
+            disabled
+        ))
     }
+}
+
+
+// this function restores network access to the state captured before the lockdown
+pub fn reset_firewall() -> Result<String, NetworkError> {
+    let snapshot = FirewallSnapshot::load();
+
+    let message = unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        
+        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
+        let rules: INetFwRules = policy.Rules()?;
+
+        // remove our rules
+        let _ = rules.Remove(&BSTR::from(RULE_NAME));
+        let _ = rules.Remove(&BSTR::from(DNS_RULE_NAME));
+        let _ = rules.Remove(&BSTR::from(DHCP_RULE_NAME));
 
-    // resolve domains to IPs
-    let mut ip_addresses = Vec::new();
-    for domain in WHITELIST_DOMAINS {
-        if let Ok(addrs) = domain.to_socket_addrs() {
-            for addr in addrs {
-                ip_addresses.push(addr.ip().to_string());
+        // restore the original per-profile defaults and re-enable the rules we disabled.
+        // without a snapshot we never changed the defaults, so they are left as they are
+        match &snapshot {
+            Some(snap) => {
+                let reenabled = snapshot::restore(&policy, snap)?;
+                format!("Internet Restored. Original Firewall Policy Restored ({} rules re-enabled).", reenabled)
             }
+            None => "Internet Restored. No Snapshot, Default Policy Untouched.".to_string(),
         }
+    };
+
+    ALLOWED_IPS.lock().unwrap_or_else(|p| p.into_inner()).clear();
+    ttl_resolver::clear();
+
+    // point adapters back at their original DNS servers, the persisted list goes last since
+    // it wins over anything captured in this process (e.g. a redirect on top of a stale lockdown)
+    dns_config::restore_system_dns();
+    if let Some(snap) = &snapshot {
+        dns_config::restore_adapters(&snap.dns_adapters);
     }
 
-    if ip_addresses.is_empty() {
-        return Ok("[error]: Could not resolve any IPs (VPN might be active, check DNS?)".to_string());
+    // only forget the snapshot once everything above went through
+    FirewallSnapshot::discard();
+    
+    Ok(message)
+}
+
+
+// this function re-resolves the exact hosts through the OS and pushes the result to the rule
+pub fn refresh_whitelist() -> Result<String, NetworkError> {
+    seed_exact_hosts()?;
+
+    match sync_allowed_ips()? {
+        Some((added, removed)) => Ok(format!(
+            "[network] [firewall rules] Firewall Rules Updated (Dynamic DNS), +{} / -{} IPs.",
+            added, removed
+        )),
+        None => Ok("[network] [firewall rules] Firewall Rules Unchanged.".to_string()),
     }
+}
 
-    let ip_list_str = ip_addresses.join(",");
-    let count = ip_addresses.len();
+
+// this function writes the resolver's current address set to the TCP rule, but only if it differs
+// from what the rule already holds. returns (added, removed) or None when nothing changed
+pub fn sync_allowed_ips() -> Result<Option<(usize, usize)>, NetworkError> {
+    let current = allowed_set();
+
+    // held until the rule is written so check_rules never sees the set and the rule disagree
+    let mut allowed = ALLOWED_IPS.lock().unwrap_or_else(|p| p.into_inner());
+
+    // an empty RemoteAddresses means "any", never write that, keep the last good set instead
+    if current.is_empty() || *allowed == current {
+        return Ok(None);
+    }
+
+    let added = current.difference(&allowed).count();
+    let removed = allowed.difference(&current).count();
+    let ip_list_str = join_ips(&current);
 
     unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
         let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
         let rules: INetFwRules = policy.Rules()?;
+        let rule = rules.Item(&BSTR::from(RULE_NAME))?;
+        rule.SetRemoteAddresses(&BSTR::from(ip_list_str))?;
+    }
+
+    *allowed = current;
+    Ok(Some((added, removed)))
+}
+
+
+// Helpers functions
 
-        // clean up old rules
+// removes and recreates the three BlackICE rules
+unsafe fn add_lockdown_rules(rules: &INetFwRules, ip_list_str: &str) -> windows::core::Result<()> {
+    unsafe {
         let _ = rules.Remove(&BSTR::from(RULE_NAME));
         let _ = rules.Remove(&BSTR::from(DNS_RULE_NAME));
+        let _ = rules.Remove(&BSTR::from(DHCP_RULE_NAME));
 
         // Whitelist rule (TCP)
         let new_rule: INetFwRule = CoCreateInstance(&NetFwRule, None, CLSCTX_ALL)?;
@@ -72,12 +260,16 @@ pub fn apply_rules() -> Result<String> {
         new_rule.SetDirection(NET_FW_RULE_DIR_OUT)?;
         new_rule.SetAction(NET_FW_ACTION_ALLOW)?;
         new_rule.SetEnabled(true.into())?;
-        new_rule.SetRemoteAddresses(&BSTR::from(&ip_list_str))?;
+        new_rule.SetRemoteAddresses(&BSTR::from(ip_list_str))?;
         rules.Add(&new_rule)?;
 
-        // DNS rule (UDP 53)
+        // DNS rule (UDP 53), only the proctor itself may talk to upstream resolvers,
+        // everyone else has to go through the local DNS proxy
+        let exe_path = std::env::current_exe().map(|p| p.display().to_string()).unwrap_or_default();
         let dns_rule: INetFwRule = CoCreateInstance(&NetFwRule, None, CLSCTX_ALL)?;
         dns_rule.SetName(&BSTR::from(DNS_RULE_NAME))?;
+        dns_rule.SetDescription(&BSTR::from("Allow the BlackICE DNS proxy to reach upstream resolvers"))?;
+        dns_rule.SetApplicationName(&BSTR::from(exe_path))?;
         dns_rule.SetProtocol(NET_FW_IP_PROTOCOL_UDP.0)?;
         dns_rule.SetRemotePorts(&BSTR::from("53"))?;
         dns_rule.SetDirection(NET_FW_RULE_DIR_OUT)?;
@@ -96,88 +288,66 @@ pub fn apply_rules() -> Result<String> {
         dhcp_rule.SetAction(NET_FW_ACTION_ALLOW)?;
         dhcp_rule.SetEnabled(true.into())?;
         rules.Add(&dhcp_rule)?;
-
-        // set default policy to block
-        enable_strict_blocking(&policy)?;
-    }
-
-    Ok(format!("[network] [firewall rules] Secure Mode Active. Allowed {} IPs.", count))
-}
-
-
-// this function restores network access
-pub fn reset_firewall() -> Result<String> {
-    unsafe {
-        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
-        
-        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
-        let rules: INetFwRules = policy.Rules()?;
-
-        // restore default policy to ALLOW
-        policy.put_DefaultOutboundAction(NET_FW_PROFILE2_DOMAIN, NET_FW_ACTION_ALLOW)?;
-        policy.put_DefaultOutboundAction(NET_FW_PROFILE2_PRIVATE, NET_FW_ACTION_ALLOW)?;
-        policy.put_DefaultOutboundAction(NET_FW_PROFILE2_PUBLIC, NET_FW_ACTION_ALLOW)?;
-
-        // remove our rules
-        let _ = rules.Remove(&BSTR::from(RULE_NAME));
-        let _ = rules.Remove(&BSTR::from(DNS_RULE_NAME));
-        let _ = rules.Remove(&BSTR::from(DHCP_RULE_NAME));
     }
-    
-    Ok("Internet Restored. Default Policy: ALLOW.".to_string())
+    Ok(())
 }
 
-
-// this function is called by the background thread to update IPs without breaking the connection
-pub fn refresh_whitelist() -> Result<String> {
-    unsafe { let _ = CoInitializeEx(None, COINIT_MULTITHREADED); }
-
-    let ip_list_str = match resolve_all_domains() {
-        Ok(s) => s,
-        Err(e) => return Ok(format!("[network] [firewall rules] DNS Refresh Skipped: {}", e.message())),
-    };
-
-    unsafe {
-        let policy: INetFwPolicy2 = CoCreateInstance(&NetFwPolicy2, None, CLSCTX_ALL)?;
-        let rules: INetFwRules = policy.Rules()?;
-
-        match rules.Item(&BSTR::from(RULE_NAME)) {
-            Ok(rule) => {
-                rule.SetRemoteAddresses(&BSTR::from(ip_list_str))?;
-                Ok("[network] [firewall rules] Firewall Rules Updated (Dynamic DNS).".to_string())
-            },
-            Err(_) => {
-                apply_rules()
+// resolves the exact hosts through the OS, only an error when not a single one resolved
+fn seed_exact_hosts() -> Result<usize, NetworkError> {
+    let mut resolved = 0;
+    let mut failed = Vec::new();
+
+    for host in whitelist::exact_hosts() {
+        match (host, whitelist::WHITELIST_PORT).to_socket_addrs() {
+            Ok(addrs) => {
+                let ips: Vec<IpAddr> = addrs.map(|a| a.ip()).collect();
+                if ips.is_empty() {
+                    failed.push(host.to_string());
+                    continue;
+                }
+                ttl_resolver::seed(host, &ips);
+                resolved += 1;
             }
+            Err(e) => failed.push(format!("{} ({})", host, e)),
         }
     }
-}
 
-
-// Helpers functions
-fn resolve_all_domains() -> Result<String> {
-    let mut ip_addresses = Vec::new();
-    let mut resolved_count = 0;
-
-    for domain in WHITELIST_DOMAINS {
-        // use std::net logic, mapped to windows::core::Error if it fails completely
-        if let Ok(addrs) = domain.to_socket_addrs() {
-            for addr in addrs {
-                ip_addresses.push(addr.ip().to_string());
-            }
-            resolved_count += 1;
-        }
+    if resolved == 0 {
+        return Err(NetworkError::DnsResolution(format!(
+            "Could not resolve any whitelisted host (VPN might be active, check DNS?): {}",
+            failed.join(", ")
+        )));
     }
+    if !failed.is_empty() {
+        println!("[network] [dns]: Could not resolve {}", failed.join(", "));
+    }
+    Ok(resolved)
+}
 
-    if ip_addresses.is_empty() {
-        return Err(Error::new(HRESULT(0x80004005_u32 as i32), "DNS Resolution Failed"));
+// resolver addresses minus DoH / DoT resolvers (a whitelisted CDN host may share anycast IPs with one)
+fn allowed_set() -> BTreeSet<IpAddr> {
+    let mut ips = ttl_resolver::current_addresses();
+    let before = ips.len();
+    ips.retain(|ip| !doh::is_doh_resolver_ip(ip));
+    if ips.len() != before {
+        println!("[network] [doh]: Excluded {} resolver IPs from allow-list", before - ips.len());
     }
+    ips
+}
+
+fn join_ips(ips: &BTreeSet<IpAddr>) -> String {
+    ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(",")
+}
 
-    Ok(ip_addresses.join(","))
+// Windows hands addresses back with a mask ("1.2.3.4/255.255.255.255"), "*" means any
+fn parse_remote_addresses(list: &str) -> BTreeSet<IpAddr> {
+    list.split(',')
+        .filter_map(|entry| entry.split('/').next()?.trim().parse().ok())
+        .collect()
 }
 
 
-unsafe fn enable_strict_blocking(policy: &INetFwPolicy2) -> Result<()> {
+unsafe fn enable_strict_blocking(policy: &INetFwPolicy2) -> windows::core::Result<()> {
     policy.put_DefaultOutboundAction(NET_FW_PROFILE2_DOMAIN, NET_FW_ACTION_BLOCK)?;
     policy.put_DefaultOutboundAction(NET_FW_PROFILE2_PRIVATE, NET_FW_ACTION_BLOCK)?;
     policy.put_DefaultOutboundAction(NET_FW_PROFILE2_PUBLIC, NET_FW_ACTION_BLOCK)?;
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use super::error::NetworkError;
+use super::{firewall_rules, snapshot};
+
+// lockdown journal, written *before* every network state change. killing the client from
+// Task Manager skips Drop and the panic hook, the journal is what the next launch looks at
+
+const JOURNAL_FILE: &str = "lockdown_journal.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockdownPhase {
+    Idle,
+    Locking,
+    Locked,
+    Unlocking,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub phase: LockdownPhase,
+    pub pid: u32,
+    pub updated_at: i64,
+}
+
+// persists the phase we are about to enter
+pub fn record(phase: LockdownPhase) {
+    let entry = JournalEntry {
+        phase,
+        pid: std::process::id(),
+        updated_at: chrono::Utc::now().timestamp(),
+    };
+
+    let result = fs::create_dir_all(snapshot::state_dir()).and_then(|_| {
+        let data = serde_json::to_string(&entry).map_err(std::io::Error::other)?;
+        let tmp = journal_path().with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, journal_path())
+    });
+
+    if let Err(e) = result {
+        eprintln!("[network] [journal]: Failed to write journal: {}", e);
+    }
+}
+
+pub fn read() -> Option<JournalEntry> {
+    let data = fs::read_to_string(journal_path()).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+// Some(entry) if a previous run left the network anything but idle (or left a snapshot behind)
+pub fn stale_lockdown() -> Option<JournalEntry> {
+    match read() {
+        Some(entry) if entry.phase != LockdownPhase::Idle && entry.pid != std::process::id() => Some(entry),
+        // a snapshot without a journal: crashed between snapshot and the first journal write
+        None if snapshot::has_stale_snapshot() => Some(JournalEntry {
+            phase: LockdownPhase::Locking,
+            pid: 0,
+            updated_at: 0,
+        }),
+        _ => None,
+    }
+}
+
+// restores the pre-lockdown network state and marks the journal idle
+pub fn recover_network() -> Result<String, NetworkError> {
+    let msg = firewall_rules::reset_firewall()?;
+    record(LockdownPhase::Idle);
+    Ok(msg)
+}
+
+fn journal_path() -> PathBuf {
+    snapshot::state_dir().join(JOURNAL_FILE)
+}
@@ -0,0 +1,162 @@
+use std::sync::mpsc::Sender;
+use serde::{Deserialize, Serialize};
+
+use super::error::NetworkError;
+use super::journal::{self, LockdownPhase};
+use super::{dns_config, firewall_rules, WfpGuard};
+use crate::cloud_reporter::AppLogs;
+
+// the lockdown as one transaction across WFP, Windows Firewall and adapter DNS. when a stage
+// fails every stage before it is undone in reverse order, the machine ends up either fully
+// locked or back where it started (or the journal says it isn't)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockdownStage {
+    WfpEngine,
+    WfpFilters,
+    FirewallRules,
+    SystemDns,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockdownFailure {
+    pub stage: LockdownStage,
+    pub error: String,
+    pub access_denied: bool,
+    // stages undone, in the order they were undone
+    pub rolled_back: Vec<LockdownStage>,
+    pub rollback_errors: Vec<String>,
+}
+
+impl LockdownFailure {
+    // true if the machine is back to its pre-lockdown state
+    pub fn is_clean(&self) -> bool {
+        self.rollback_errors.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        let hint = if self.access_denied { " (run BlackICE as Administrator)" } else { "" };
+        let state = if self.is_clean() {
+            "Network Left Unchanged".to_string()
+        } else {
+            format!("ROLLBACK INCOMPLETE, RECOVER NETWORK: {}", self.rollback_errors.join("; "))
+        };
+        format!("[network] Lockdown Failed at {:?}: {}{}. {}", self.stage, self.error, hint, state)
+    }
+}
+
+struct Lockdown {
+    guard: Option<WfpGuard>,
+    completed: Vec<LockdownStage>,
+}
+
+
+// runs every stage, on success the guard holding the WFP session is handed back
+pub fn run_lockdown(tx: &Sender<AppLogs>) -> Result<WfpGuard, LockdownFailure> {
+    journal::record(LockdownPhase::Locking);
+
+    let mut lockdown = Lockdown { guard: None, completed: Vec::new() };
+    match lockdown.run(tx) {
+        Ok(()) => match lockdown.guard.take() {
+            Some(guard) => Ok(guard),
+            None => Err(lockdown.rollback(
+                LockdownStage::WfpEngine,
+                NetworkError::Policy("WFP session missing after lockdown".into()),
+            )),
+        },
+        Err((stage, e)) => Err(lockdown.rollback(stage, e)),
+    }
+}
+
+impl Lockdown {
+    fn run(&mut self, tx: &Sender<AppLogs>) -> Result<(), (LockdownStage, NetworkError)> {
+        // initialize WFP (App ID Hashing)
+        info(tx, "[network] [wfp]: Generating App IDs...");
+        let guard = WfpGuard::new().map_err(|e| (LockdownStage::WfpEngine, e))?;
+        self.completed.push(LockdownStage::WfpEngine);
+        let guard = self.guard.insert(guard);
+
+        // aborts its own transaction on failure, nothing to undo for this stage then
+        guard.apply_ale_lockdown().map_err(|e| (LockdownStage::WfpFilters, e))?;
+        self.completed.push(LockdownStage::WfpFilters);
+
+        // DoT is blocked either way, this only adds reporting of the attempts
+        if let Err(e) = guard.watch_dot_attempts(tx.clone()) {
+            info(tx, &format!("[network] {}", e));
+        }
+
+        // enable firewall (DNS Resolution)
+        info(tx, "[network] [firewall rules]: Applying IP Rules...");
+        let msg = firewall_rules::apply_rules().map_err(|e| (LockdownStage::FirewallRules, e))?;
+        self.completed.push(LockdownStage::FirewallRules);
+        info(tx, &format!("[network] [firewall rules]: {}", msg));
+
+        // route all name resolution through the local DNS proxy
+        let upstreams = dns_config::redirect_system_dns().map_err(|e| (LockdownStage::SystemDns, e))?;
+        self.completed.push(LockdownStage::SystemDns);
+        info(tx, &format!(
+            "[network] [dns]: System DNS redirected to proxy ({} upstream servers)",
+            upstreams.len()
+        ));
+
+        Ok(())
+    }
+
+    fn rollback(&mut self, failed: LockdownStage, error: NetworkError) -> LockdownFailure {
+        let mut undo = self.completed.clone();
+        // these two can fail half way, the failing stage itself is undone too.
+        // a resolution failure happens before apply_rules touches the firewall
+        let partial = match failed {
+            LockdownStage::FirewallRules => !matches!(error, NetworkError::DnsResolution(_)),
+            LockdownStage::SystemDns => true,
+            _ => false,
+        };
+        if partial {
+            undo.push(failed);
+        }
+
+        let mut rolled_back = Vec::new();
+        let mut rollback_errors = Vec::new();
+        for stage in undo.into_iter().rev() {
+            let result = match stage {
+                LockdownStage::SystemDns => {
+                    dns_config::restore_system_dns();
+                    Ok(())
+                }
+                // also restores the snapshot's adapter DNS and discards the snapshot
+                LockdownStage::FirewallRules => firewall_rules::reset_firewall().map(|_| ()),
+                LockdownStage::WfpFilters => match self.guard.as_mut() {
+                    Some(guard) => guard.remove_filters(),
+                    None => Ok(()),
+                },
+                // closing the dynamic session drops anything it still owns
+                LockdownStage::WfpEngine => {
+                    self.guard = None;
+                    Ok(())
+                }
+            };
+
+            match result {
+                Ok(()) => rolled_back.push(stage),
+                Err(e) => rollback_errors.push(format!("{:?}: {}", stage, e)),
+            }
+        }
+
+        // a failed rollback keeps the journal at Locking so recovery is offered
+        if rollback_errors.is_empty() {
+            journal::record(LockdownPhase::Idle);
+        }
+
+        LockdownFailure {
+            stage: failed,
+            error: error.to_string(),
+            access_denied: error.is_access_denied(),
+            rolled_back,
+            rollback_errors,
+        }
+    }
+}
+
+fn info(tx: &Sender<AppLogs>, msg: &str) {
+    let _ = tx.send(AppLogs::Info(msg.to_string()));
+}
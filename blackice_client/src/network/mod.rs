@@ -1,5 +1,26 @@
+pub mod dns_config;
+pub mod dns_proxy;
+pub mod dns_wire;
+pub mod doh;
+pub mod error;
 pub mod firewall_rules;
+pub mod journal;
+pub mod lockdown;
+pub mod probe;
+pub mod snapshot;
+pub mod tamper;
+pub mod ttl_resolver;
 pub mod wfp;
+pub mod whitelist;
 
-pub use firewall_rules::{apply_rules, reset_firewall, refresh_whitelist};
+pub use dns_config::{redirect_system_dns, restore_system_dns, upstream_servers};
+pub use dns_proxy::run_dns_proxy;
+pub use error::NetworkError;
+pub use snapshot::{has_stale_snapshot, FirewallSnapshot};
+pub use firewall_rules::{apply_rules, check_rules, enforce_rules, reset_firewall, refresh_whitelist, sync_allowed_ips};
+pub use journal::{recover_network, stale_lockdown, JournalEntry, LockdownPhase};
+pub use lockdown::{run_lockdown, LockdownFailure, LockdownStage};
+pub use probe::{verify_lockdown, VerificationReport};
+pub use tamper::run_tamper_watchdog;
+pub use ttl_resolver::run_ttl_refresher;
 pub use wfp::*;
\ No newline at end of file
@@ -0,0 +1,356 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use super::{dns_proxy, dns_wire, whitelist};
+
+// post-lockdown self check: connects to a non-whitelisted and a whitelisted target from the
+// proctor itself and from a helper process that is neither a browser nor the proctor binary
+
+// not on the whitelist, resolved through the upstream servers since the proxy would NXDOMAIN it
+const BLOCKED_PROBE_HOST: &str = "example.com";
+const PROBE_PORT: u16 = 443;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+// subcommand the helper copy is started with
+pub const HELPER_SUBCOMMAND: &str = "probe-connect";
+const HELPER_EXE: &str = "blackice_probe_helper.exe";
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Expectation {
+    Reachable,
+    Blocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeOrigin {
+    // the proctor process, permitted by the WFP app filter
+    Proctor,
+    // a renamed copy of the proctor, stands in for any other application
+    Helper,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeOutcome {
+    Connected,
+    // RST from the far end, the packet left the machine
+    Refused,
+    Blocked(String),
+    TimedOut,
+    Unresolved(String),
+}
+
+impl ProbeOutcome {
+    // true if traffic made it off the machine
+    pub fn reached(&self) -> bool {
+        matches!(self, ProbeOutcome::Connected | ProbeOutcome::Refused)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeTarget {
+    pub label: String,
+    pub addr: Option<SocketAddr>,
+    pub expect: Expectation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub origin: ProbeOrigin,
+    pub label: String,
+    pub addr: Option<SocketAddr>,
+    pub expected: Expectation,
+    pub outcome: ProbeOutcome,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub checked_at: i64,
+    pub passed: bool,
+    pub results: Vec<ProbeResult>,
+}
+
+impl VerificationReport {
+    pub fn new(results: Vec<ProbeResult>) -> Self {
+        Self {
+            checked_at: chrono::Utc::now().timestamp(),
+            passed: !results.is_empty() && results.iter().all(|r| r.passed),
+            results,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let ok = self.results.iter().filter(|r| r.passed).count();
+        format!(
+            "{} ({}/{} probes)",
+            if self.passed { "PASSED" } else { "FAILED" },
+            ok,
+            self.results.len()
+        )
+    }
+}
+
+
+// runs every probe, the helper results are merged in (or reported as one failed probe)
+pub fn verify_lockdown(upstreams: &[IpAddr]) -> VerificationReport {
+    let proctor_targets = probe_targets(upstreams);
+    let mut results = run_probes(ProbeOrigin::Proctor, &proctor_targets, PROBE_TIMEOUT);
+
+    // from any other process both targets must be unreachable
+    let helper_targets: Vec<ProbeTarget> = proctor_targets
+        .into_iter()
+        .map(|t| ProbeTarget { expect: Expectation::Blocked, ..t })
+        .collect();
+
+    match spawn_helper(&helper_targets) {
+        Ok(helper_results) => results.extend(helper_results),
+        Err(e) => results.push(ProbeResult {
+            origin: ProbeOrigin::Helper,
+            label: "helper".into(),
+            addr: None,
+            expected: Expectation::Blocked,
+            outcome: ProbeOutcome::Unresolved(e),
+            passed: false,
+        }),
+    }
+
+    VerificationReport::new(results)
+}
+
+// connects to each target and grades it against its expectation
+pub fn run_probes(origin: ProbeOrigin, targets: &[ProbeTarget], timeout: Duration) -> Vec<ProbeResult> {
+    targets
+        .iter()
+        .map(|target| {
+            let outcome = match target.addr {
+                Some(addr) => connect(addr, timeout),
+                None => ProbeOutcome::Unresolved(format!("no address for {}", target.label)),
+            };
+            let passed = match target.expect {
+                Expectation::Reachable => outcome.reached(),
+                // an unresolved target proves nothing about the block
+                Expectation::Blocked => {
+                    !outcome.reached() && !matches!(outcome, ProbeOutcome::Unresolved(_))
+                }
+            };
+
+            ProbeResult {
+                origin,
+                label: target.label.clone(),
+                addr: target.addr,
+                expected: target.expect,
+                outcome,
+                passed,
+            }
+        })
+        .collect()
+}
+
+pub fn connect(addr: SocketAddr, timeout: Duration) -> ProbeOutcome {
+    match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => ProbeOutcome::Connected,
+        Err(e) => match e.kind() {
+            ErrorKind::ConnectionRefused => ProbeOutcome::Refused,
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => ProbeOutcome::TimedOut,
+            // WFP / firewall drops surface as WSAEACCES (PermissionDenied)
+            _ => ProbeOutcome::Blocked(e.to_string()),
+        },
+    }
+}
+
+// entry point of the helper process, prints its results as JSON on stdout
+pub fn run_helper(targets_json: &str) -> i32 {
+    let targets: Vec<ProbeTarget> = match serde_json::from_str(targets_json) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("[probe]: Invalid targets: {}", e);
+            return 2;
+        }
+    };
+
+    let results = run_probes(ProbeOrigin::Helper, &targets, PROBE_TIMEOUT);
+    match serde_json::to_string(&results) {
+        Ok(json) => {
+            println!("{}", json);
+            0
+        }
+        Err(_) => 1,
+    }
+}
+
+
+// Helpers functions
+
+fn probe_targets(upstreams: &[IpAddr]) -> Vec<ProbeTarget> {
+    let allowed_host = whitelist::exact_hosts().next().unwrap_or_default();
+
+    vec![
+        ProbeTarget {
+            label: BLOCKED_PROBE_HOST.into(),
+            addr: resolve_upstream(BLOCKED_PROBE_HOST, upstreams),
+            expect: Expectation::Blocked,
+        },
+        ProbeTarget {
+            // resolved through the proxy like a browser would, which also puts it on the allow-list
+            label: allowed_host.into(),
+            addr: (allowed_host, PROBE_PORT)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.find(|a| a.is_ipv4())),
+            expect: Expectation::Reachable,
+        },
+    ]
+}
+
+fn resolve_upstream(host: &str, upstreams: &[IpAddr]) -> Option<SocketAddr> {
+    let id = 0x5052;
+    let query = dns_wire::build_query(id, host, dns_wire::TYPE_A);
+    let response = dns_proxy::forward(&query, id, upstreams)?;
+
+    dns_wire::parse_addresses(&response)
+        .first()
+        .map(|answer| SocketAddr::new(answer.ip, PROBE_PORT))
+}
+
+// a copy under another name has its own WFP app id, so it is not covered by the proctor permit
+fn spawn_helper(targets: &[ProbeTarget]) -> Result<Vec<ProbeResult>, String> {
+    use std::os::windows::process::CommandExt;
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let helper = std::env::temp_dir().join(HELPER_EXE);
+    std::fs::copy(&exe, &helper).map_err(|e| format!("copy helper: {}", e))?;
+
+    let targets_json = serde_json::to_string(targets).map_err(|e| e.to_string())?;
+    let output = Command::new(&helper)
+        .arg(HELPER_SUBCOMMAND)
+        .arg(targets_json)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    let _ = std::fs::remove_file(&helper);
+
+    let output = output.map_err(|e| format!("spawn helper: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("helper exited with {}", output.status));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("helper output: {}", e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    // a local listener stands in for a whitelisted server
+    fn local_server() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        (listener, addr)
+    }
+
+    fn target(addr: Option<SocketAddr>, expect: Expectation) -> ProbeTarget {
+        ProbeTarget { label: "test".into(), addr, expect }
+    }
+
+    #[test]
+    fn connect_reaches_a_listening_server() {
+        let (_listener, addr) = local_server();
+        assert_eq!(connect(addr, Duration::from_secs(1)), ProbeOutcome::Connected);
+    }
+
+    #[test]
+    fn connect_to_a_closed_port_is_refused() {
+        let (listener, addr) = local_server();
+        drop(listener);
+        assert_eq!(connect(addr, Duration::from_secs(1)), ProbeOutcome::Refused);
+    }
+
+    #[test]
+    fn reachable_target_passes() {
+        let (_listener, addr) = local_server();
+        let results = run_probes(
+            ProbeOrigin::Proctor,
+            &[target(Some(addr), Expectation::Reachable)],
+            Duration::from_secs(1),
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].outcome, ProbeOutcome::Connected);
+        assert!(results[0].passed);
+    }
+
+    #[test]
+    fn reachable_server_fails_when_it_should_be_blocked() {
+        let (_listener, addr) = local_server();
+        let results = run_probes(
+            ProbeOrigin::Helper,
+            &[target(Some(addr), Expectation::Blocked)],
+            Duration::from_secs(1),
+        );
+        assert!(!results[0].passed);
+    }
+
+    #[test]
+    fn refused_counts_as_reached() {
+        // the RST came from the far end, so the packet was not blocked
+        let (listener, addr) = local_server();
+        drop(listener);
+        let results = run_probes(
+            ProbeOrigin::Helper,
+            &[target(Some(addr), Expectation::Blocked)],
+            Duration::from_secs(1),
+        );
+        assert_eq!(results[0].outcome, ProbeOutcome::Refused);
+        assert!(!results[0].passed);
+    }
+
+    #[test]
+    fn unroutable_target_passes_as_blocked() {
+        // TEST-NET-1 is never routed, the connect times out or fails locally like a dropped packet
+        let addr = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), PROBE_PORT));
+        let results = run_probes(
+            ProbeOrigin::Helper,
+            &[target(Some(addr), Expectation::Blocked)],
+            Duration::from_millis(300),
+        );
+        assert!(!results[0].outcome.reached());
+        assert!(results[0].passed);
+    }
+
+    #[test]
+    fn unresolved_target_proves_nothing() {
+        let results = run_probes(
+            ProbeOrigin::Proctor,
+            &[target(None, Expectation::Blocked)],
+            Duration::from_secs(1),
+        );
+        assert!(matches!(results[0].outcome, ProbeOutcome::Unresolved(_)));
+        assert!(!results[0].passed);
+    }
+
+    #[test]
+    fn report_passes_only_if_every_probe_does() {
+        let (_listener, addr) = local_server();
+        let reachable = run_probes(
+            ProbeOrigin::Proctor,
+            &[target(Some(addr), Expectation::Reachable)],
+            Duration::from_secs(1),
+        );
+        assert!(VerificationReport::new(reachable.clone()).passed);
+
+        let mut mixed = reachable;
+        mixed.extend(run_probes(
+            ProbeOrigin::Proctor,
+            &[target(None, Expectation::Reachable)],
+            Duration::from_secs(1),
+        ));
+        let report = VerificationReport::new(mixed);
+        assert!(!report.passed);
+        assert_eq!(report.summary(), "FAILED (1/2 probes)");
+
+        assert!(!VerificationReport::new(Vec::new()).passed);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use windows::core::{Interface, Result, BSTR};
+use windows::Win32::NetworkManagement::WindowsFirewall::*;
+use windows::Win32::System::Ole::IEnumVARIANT;
+use windows::Win32::System::Variant::{VariantClear, VARIANT, VT_DISPATCH};
+use super::dns_config::AdapterDns;
+use super::error::NetworkError;
+
+// what the machine looked like before the first lockdown, written to disk before apply_rules
+// touches anything so unlock / panic / next launch can put back exactly this state
+
+const SNAPSHOT_FILE: &str = "network_snapshot.json";
+// our own rules are removed by name, never part of the snapshot
+const OWN_RULE_PREFIX: &str = "BlackICE_";
+// "Core Networking" group (DHCP, DNS, NDP ...), left alone so the adapter keeps working
+const CORE_NETWORKING_GROUP: &str = "@FirewallAPI.dll,-25000";
+
+pub(super) const PROFILES: [NET_FW_PROFILE_TYPE2; 3] = [
+    NET_FW_PROFILE2_DOMAIN,
+    NET_FW_PROFILE2_PRIVATE,
+    NET_FW_PROFILE2_PUBLIC,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileDefault {
+    pub profile: i32,
+    pub outbound_action: i32,
+    // the lockdown turns a disabled profile back on (see firewall_rules::enforce_rules)
+    #[serde(default = "default_enabled")]
+    pub firewall_enabled: bool,
+}
+
+// enough fields to find the same rule again, names alone are not unique in Windows Firewall
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleKey {
+    pub name: String,
+    pub application: String,
+    pub grouping: String,
+    pub protocol: i32,
+    pub local_ports: String,
+    pub remote_ports: String,
+    pub remote_addresses: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallSnapshot {
+    pub taken_at: i64,
+    pub default_outbound: Vec<ProfileDefault>,
+    // enabled outbound ALLOW rules that would punch holes through the lockdown, disabled while locked
+    pub conflicting_rules: Vec<RuleKey>,
+    // adapter DNS before the proxy redirect, filled in by dns_config
+    #[serde(default)]
+    pub dns_adapters: Vec<AdapterDns>,
+}
+
+impl FirewallSnapshot {
+    // reads the per-profile defaults and conflicting rules, changes nothing
+    pub fn capture(policy: &INetFwPolicy2) -> Result<Self> {
+        unsafe {
+            let mut default_outbound = Vec::new();
+            for profile in PROFILES {
+                let action = policy.get_DefaultOutboundAction(profile)?;
+                let enabled = policy.get_FirewallEnabled(profile)?.as_bool();
+                default_outbound.push(ProfileDefault {
+                    profile: profile.0,
+                    outbound_action: action.0,
+                    firewall_enabled: enabled,
+                });
+            }
+
+            Ok(Self {
+                taken_at: chrono::Utc::now().timestamp(),
+                default_outbound,
+                conflicting_rules: find_conflicting_rules(policy)?,
+                dns_adapters: Vec::new(),
+            })
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        let data = fs::read_to_string(snapshot_path()).ok()?;
+        match serde_json::from_str(&data) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                eprintln!("[network] [snapshot]: Ignoring unreadable snapshot: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        fs::create_dir_all(state_dir())?;
+        let data = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+
+        // write then rename so a crash mid-write never leaves a half snapshot behind
+        let tmp = snapshot_path().with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, snapshot_path())
+    }
+
+    pub fn discard() {
+        let _ = fs::remove_file(snapshot_path());
+    }
+}
+
+
+// %ProgramData%\BlackICE, machine wide so an elevated relaunch by another admin still finds it
+pub fn state_dir() -> PathBuf {
+    let base = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".to_string());
+    PathBuf::from(base).join("BlackICE")
+}
+
+pub fn snapshot_path() -> PathBuf {
+    state_dir().join(SNAPSHOT_FILE)
+}
+
+// a snapshot on disk at startup means the last lockdown was never restored
+pub fn has_stale_snapshot() -> bool {
+    snapshot_path().exists()
+}
+
+// adds the pre-redirect adapter DNS settings to the persisted snapshot
+pub fn record_dns_adapters(adapters: &[AdapterDns]) {
+    if let Some(mut snapshot) = FirewallSnapshot::load() {
+        for adapter in adapters {
+            if !snapshot.dns_adapters.iter().any(|a| a.guid == adapter.guid) {
+                snapshot.dns_adapters.push(adapter.clone());
+            }
+        }
+        if let Err(e) = snapshot.save() {
+            eprintln!("[network] [snapshot]: Failed to persist DNS settings: {}", e);
+        }
+    }
+}
+
+// enabled outbound ALLOW rules (other than ours and Core Networking) as they are right now
+pub fn find_conflicting_rules(policy: &INetFwPolicy2) -> Result<Vec<RuleKey>> {
+    let mut found = Vec::new();
+    unsafe {
+        for rule in enum_rules(&policy.Rules()?)? {
+            if is_conflicting(&rule) {
+                found.push(rule_key(&rule)?);
+            }
+        }
+    }
+    Ok(found)
+}
+
+// disables every conflicting rule listed in the snapshot, returns how many were switched off
+pub fn disable_conflicting_rules(policy: &INetFwPolicy2, snapshot: &FirewallSnapshot) -> Result<usize> {
+    set_rules_enabled(policy, &snapshot.conflicting_rules, false)
+}
+
+// disables rules that showed up (or were re-enabled) mid-exam, they are added to the persisted
+// snapshot first so unlock turns them back on like the ones found at lockdown
+pub fn disable_new_conflicting_rules(policy: &INetFwPolicy2, keys: &[RuleKey]) -> std::result::Result<usize, NetworkError> {
+    if let Some(mut snapshot) = FirewallSnapshot::load() {
+        for key in keys {
+            if !snapshot.conflicting_rules.contains(key) {
+                snapshot.conflicting_rules.push(key.clone());
+            }
+        }
+        snapshot.save()?;
+    }
+
+    Ok(set_rules_enabled(policy, keys, false)?)
+}
+
+// puts back the exact per-profile defaults and re-enables the rules we disabled
+pub fn restore(policy: &INetFwPolicy2, snapshot: &FirewallSnapshot) -> Result<usize> {
+    unsafe {
+        for default in &snapshot.default_outbound {
+            policy.put_DefaultOutboundAction(
+                NET_FW_PROFILE_TYPE2(default.profile),
+                NET_FW_ACTION(default.outbound_action),
+            )?;
+            if !default.firewall_enabled {
+                policy.put_FirewallEnabled(NET_FW_PROFILE_TYPE2(default.profile), false.into())?;
+            }
+        }
+    }
+
+    set_rules_enabled(policy, &snapshot.conflicting_rules, true)
+}
+
+
+// Helpers functions
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn set_rules_enabled(policy: &INetFwPolicy2, keys: &[RuleKey], enabled: bool) -> Result<usize> {
+    if keys.is_empty() {
+        return Ok(0);
+    }
+
+    let mut changed = 0;
+    unsafe {
+        for rule in enum_rules(&policy.Rules()?)? {
+            let key = match rule_key(&rule) {
+                Ok(k) => k,
+                Err(_) => continue,
+            };
+            if keys.contains(&key) && rule.SetEnabled(enabled.into()).is_ok() {
+                changed += 1;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn is_conflicting(rule: &INetFwRule) -> bool {
+    unsafe {
+        let outbound_allow = rule.Enabled().map(|e| e.as_bool()).unwrap_or(false)
+            && rule.Direction().map(|d| d == NET_FW_RULE_DIR_OUT).unwrap_or(false)
+            && rule.Action().map(|a| a == NET_FW_ACTION_ALLOW).unwrap_or(false);
+
+        if !outbound_allow {
+            return false;
+        }
+
+        let name = rule.Name().map(|n| n.to_string()).unwrap_or_default();
+        let grouping = rule.Grouping().map(|g| g.to_string()).unwrap_or_default();
+        !name.starts_with(OWN_RULE_PREFIX) && !grouping.eq_ignore_ascii_case(CORE_NETWORKING_GROUP)
+    }
+}
+
+fn rule_key(rule: &INetFwRule) -> Result<RuleKey> {
+    unsafe {
+        let text = |value: Result<BSTR>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        Ok(RuleKey {
+            name: rule.Name()?.to_string(),
+            application: text(rule.ApplicationName()),
+            grouping: text(rule.Grouping()),
+            protocol: rule.Protocol().unwrap_or_default(),
+            local_ports: text(rule.LocalPorts()),
+            remote_ports: text(rule.RemotePorts()),
+            remote_addresses: text(rule.RemoteAddresses()),
+        })
+    }
+}
+
+// INetFwRules only exposes its items through an IEnumVARIANT of IDispatch pointers
+unsafe fn enum_rules(rules: &INetFwRules) -> Result<Vec<INetFwRule>> {
+    unsafe {
+        let enumerator: IEnumVARIANT = rules._NewEnum()?.cast()?;
+        let mut found = Vec::new();
+
+        loop {
+            let mut items = [VARIANT::default()];
+            let mut fetched = 0u32;
+            if enumerator.Next(&mut items, &mut fetched).is_err() || fetched == 0 {
+                break;
+            }
+
+            let item = &items[0].Anonymous.Anonymous;
+            if item.vt == VT_DISPATCH
+                && let Some(dispatch) = item.Anonymous.pdispVal.as_ref()
+                && let Ok(rule) = dispatch.cast::<INetFwRule>()
+            {
+                found.push(rule);
+            }
+
+            let _ = VariantClear(&mut items[0]);
+        }
+
+        Ok(found)
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{firewall_rules, WfpGuard};
+use crate::cloud_reporter::{AppLogs, ViolationType};
+
+// re-reads everything the lockdown put in place. a student with admin rights can delete
+// the whitelist rule, flip a default policy or remove our WFP filters mid-exam
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// upper bound for the sleep so the stop signal is noticed
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+
+// blocks until keep_running is cleared. the guard lock is held for a whole check (and reapply),
+// unlock takes the same lock first so a check never races the rules being removed
+pub fn run_tamper_watchdog(
+    keep_running: Arc<AtomicBool>,
+    tx: Sender<AppLogs>,
+    guard: Arc<Mutex<Option<WfpGuard>>>,
+    reapply: Arc<AtomicBool>,
+) {
+    let mut last_drift: Vec<String> = Vec::new();
+    let mut next_check = Instant::now() + CHECK_INTERVAL;
+
+    while keep_running.load(Ordering::Relaxed) {
+        if Instant::now() < next_check {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        next_check = Instant::now() + CHECK_INTERVAL;
+
+        let mut guard = guard.lock().unwrap_or_else(|p| p.into_inner());
+        if !keep_running.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // a firewall we can't read (service stopped) counts as tampering too
+        let rule_drift = firewall_rules::check_rules()
+            .unwrap_or_else(|e| vec![format!("Firewall state unreadable: {}", e)]);
+        let filter_drift = match guard.as_ref() {
+            Some(g) => g.check_filters().unwrap_or_else(|e| vec![e.to_string()]),
+            None => Vec::new(),
+        };
+
+        let drift: Vec<String> = rule_drift.iter().chain(filter_drift.iter()).cloned().collect();
+        if drift.is_empty() {
+            last_drift.clear();
+            continue;
+        }
+
+        // the same drift is reported once, not every few seconds while it persists
+        if drift != last_drift {
+            let _ = tx.send(AppLogs::Violation(
+                ViolationType::Tamper,
+                format!("[network] [tamper] CRITICAL: LOCKDOWN TAMPERING DETECTED: {}", drift.join("; ")),
+            ));
+        }
+
+        if !reapply.load(Ordering::Relaxed) {
+            last_drift = drift;
+            continue;
+        }
+
+        if !rule_drift.is_empty() {
+            match firewall_rules::enforce_rules() {
+                Ok(msg) => { let _ = tx.send(AppLogs::Info(msg)); }
+                Err(e) => { let _ = tx.send(AppLogs::Error(format!("[network] [tamper]: Reapply Failed: {}", e))); }
+            }
+        }
+        if !filter_drift.is_empty() && let Some(g) = guard.as_mut() {
+            match g.reapply_lockdown() {
+                Ok(_) => { let _ = tx.send(AppLogs::Info("[network] [wfp]: WFP Lockdown Reapplied".into())); }
+                Err(e) => { let _ = tx.send(AppLogs::Error(format!("[network] [tamper]: Reapply Failed: {}", e))); }
+            }
+        }
+
+        // repaired, tampering again afterwards is a new incident
+        last_drift.clear();
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::dns_wire::{self, Answer};
+use super::{dns_proxy, firewall_rules};
+use crate::cloud_reporter::AppLogs;
+
+// keeps every allowed address alive only as long as its DNS record says it may,
+// each name is re-queried when its shortest TTL runs out instead of polling everything
+
+// addresses that vanish from an answer stay allowed this long, open connections keep working
+pub const GRACE_WINDOW: Duration = Duration::from_secs(120);
+// TTL assumed for addresses resolved through the OS (to_socket_addrs has no TTL)
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+// clamp so a TTL of 0 doesn't spin and a TTL of a day doesn't let stale IPs live forever
+const MIN_REFRESH: Duration = Duration::from_secs(5);
+const MAX_REFRESH: Duration = Duration::from_secs(300);
+// names learned from the proxy are dropped when nobody asked for them this long
+const IDLE_NAME_TIMEOUT: Duration = Duration::from_secs(600);
+// upper bound for the refresher sleep so the stop signal is noticed
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static RESOLVER: Mutex<Option<TtlResolver>> = Mutex::new(None);
+
+struct TrackedName {
+    // address -> allowed until (record expiry + grace)
+    addresses: HashMap<IpAddr, Instant>,
+    next_refresh: Instant,
+    last_requested: Instant,
+    // exact whitelist hosts are refreshed for the whole exam
+    pinned: bool,
+}
+
+pub struct TtlResolver {
+    names: HashMap<String, TrackedName>,
+    grace: Duration,
+}
+
+impl TtlResolver {
+    pub fn new(grace: Duration) -> Self {
+        Self { names: HashMap::new(), grace }
+    }
+
+    // merges a fresh answer for `name`, addresses missing from it keep their old expiry (grace)
+    pub fn observe(&mut self, name: &str, answers: &[Answer], pinned: bool, now: Instant) {
+        let grace = self.grace;
+        let entry = self.names.entry(name.to_string()).or_insert_with(|| TrackedName {
+            addresses: HashMap::new(),
+            next_refresh: now,
+            last_requested: now,
+            pinned,
+        });
+        entry.pinned |= pinned;
+
+        // empty answer (upstream timeout / SERVFAIL): retry soon, old addresses run out on their own
+        let mut shortest = if answers.is_empty() { MIN_REFRESH } else { MAX_REFRESH };
+        for answer in answers {
+            let ttl = Duration::from_secs(answer.ttl as u64);
+            shortest = shortest.min(ttl);
+            entry.addresses.insert(answer.ip, now + ttl + grace);
+        }
+
+        entry.next_refresh = now + shortest.clamp(MIN_REFRESH, MAX_REFRESH);
+    }
+
+    // called for every proxied lookup so names in use keep being refreshed
+    pub fn touch(&mut self, name: &str, now: Instant) {
+        if let Some(entry) = self.names.get_mut(name) {
+            entry.last_requested = now;
+        }
+    }
+
+    // names whose TTL ran out, idle unpinned names are forgotten instead
+    pub fn due_names(&mut self, now: Instant) -> Vec<String> {
+        self.names.retain(|_, n| n.pinned || now.duration_since(n.last_requested) < IDLE_NAME_TIMEOUT);
+
+        self.names
+            .iter()
+            .filter(|(_, n)| n.next_refresh <= now)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.names.values().map(|n| n.next_refresh).min()
+    }
+
+    // drops addresses past expiry + grace and returns what is still allowed
+    pub fn address_set(&mut self, now: Instant) -> BTreeSet<IpAddr> {
+        for entry in self.names.values_mut() {
+            entry.addresses.retain(|_, until| *until > now);
+        }
+
+        self.names
+            .values()
+            .flat_map(|n| n.addresses.keys().copied())
+            .collect()
+    }
+}
+
+
+// fresh resolver for a new lockdown
+pub fn reset() {
+    *RESOLVER.lock().unwrap_or_else(|p| p.into_inner()) = Some(TtlResolver::new(GRACE_WINDOW));
+}
+
+pub fn clear() {
+    *RESOLVER.lock().unwrap_or_else(|p| p.into_inner()) = None;
+}
+
+// seeds addresses resolved without TTL information (initial lockdown resolution)
+pub fn seed(name: &str, ips: &[IpAddr]) {
+    let answers: Vec<Answer> = ips
+        .iter()
+        .map(|ip| Answer { ip: *ip, ttl: DEFAULT_TTL.as_secs() as u32 })
+        .collect();
+    with_resolver(|r| r.observe(name, &answers, true, Instant::now()));
+}
+
+// records an answer the proxy just handed out
+pub fn record(name: &str, answers: &[Answer]) {
+    let now = Instant::now();
+    with_resolver(|r| {
+        r.observe(name, answers, false, now);
+        r.touch(name, now);
+    });
+}
+
+pub fn current_addresses() -> BTreeSet<IpAddr> {
+    with_resolver(|r| r.address_set(Instant::now())).unwrap_or_default()
+}
+
+fn with_resolver<T>(f: impl FnOnce(&mut TtlResolver) -> T) -> Option<T> {
+    RESOLVER.lock().unwrap_or_else(|p| p.into_inner()).as_mut().map(f)
+}
+
+
+// blocks until keep_running is cleared, re-queries names as their TTLs expire
+pub fn run_ttl_refresher(keep_running: Arc<AtomicBool>, tx: Sender<AppLogs>, upstreams: Vec<IpAddr>) {
+    let mut query_id: u16 = 0x4b1c;
+
+    while keep_running.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        let due = with_resolver(|r| r.due_names(now)).unwrap_or_default();
+
+        for name in &due {
+            let mut answers = Vec::new();
+            for qtype in [dns_wire::TYPE_A, dns_wire::TYPE_AAAA] {
+                query_id = query_id.wrapping_add(1);
+                let query = dns_wire::build_query(query_id, name, qtype);
+                if let Some(response) = dns_proxy::forward(&query, query_id, &upstreams) {
+                    answers.extend(dns_wire::parse_addresses(&response));
+                }
+            }
+
+            with_resolver(|r| r.observe(name, &answers, false, Instant::now()));
+        }
+
+        match firewall_rules::sync_allowed_ips() {
+            Ok(Some((added, removed))) => {
+                let _ = tx.send(AppLogs::Info(format!(
+                    "[network] [dns]: Allow-list updated (+{} / -{} IPs)",
+                    added, removed
+                )));
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[dns]: Failed to sync allow-list: {}", e),
+        }
+
+        // sleep until the next record expires, capped so the stop signal is noticed
+        let wait = with_resolver(|r| r.next_refresh())
+            .flatten()
+            .map(|t| t.saturating_duration_since(Instant::now()))
+            .unwrap_or(POLL_INTERVAL)
+            .min(POLL_INTERVAL);
+        thread::sleep(wait.max(Duration::from_millis(100)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: Duration = Duration::from_secs(30);
+
+    fn answer(ip: &str, ttl: u32) -> Answer {
+        Answer { ip: ip.parse().unwrap(), ttl }
+    }
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn ips(set: &BTreeSet<IpAddr>) -> Vec<String> {
+        set.iter().map(|ip| ip.to_string()).collect()
+    }
+
+    #[test]
+    fn name_is_due_when_its_shortest_ttl_runs_out() {
+        let mut resolver = TtlResolver::new(GRACE);
+        let t0 = Instant::now();
+        resolver.observe("leetcode.com", &[answer("1.1.1.1", 60), answer("2.2.2.2", 30)], true, t0);
+
+        assert_eq!(resolver.next_refresh(), Some(t0 + secs(30)));
+        assert!(resolver.due_names(t0 + secs(29)).is_empty());
+        assert_eq!(resolver.due_names(t0 + secs(30)), ["leetcode.com"]);
+    }
+
+    #[test]
+    fn refresh_interval_is_clamped() {
+        let mut resolver = TtlResolver::new(GRACE);
+        let t0 = Instant::now();
+        resolver.observe("zero.test", &[answer("1.1.1.1", 0)], true, t0);
+        resolver.observe("day.test", &[answer("2.2.2.2", 86_400)], true, t0);
+        // nothing came back, retried soon
+        resolver.observe("failed.test", &[], true, t0);
+
+        assert_eq!(resolver.names["zero.test"].next_refresh, t0 + MIN_REFRESH);
+        assert_eq!(resolver.names["day.test"].next_refresh, t0 + MAX_REFRESH);
+        assert_eq!(resolver.names["failed.test"].next_refresh, t0 + MIN_REFRESH);
+    }
+
+    #[test]
+    fn missing_addresses_live_out_their_ttl_and_grace() {
+        let mut resolver = TtlResolver::new(GRACE);
+        let t0 = Instant::now();
+        resolver.observe("leetcode.com", &[answer("1.1.1.1", 60), answer("2.2.2.2", 60)], true, t0);
+        // the next answer only has one of them
+        resolver.observe("leetcode.com", &[answer("1.1.1.1", 60)], true, t0 + secs(60));
+
+        assert_eq!(ips(&resolver.address_set(t0 + secs(89))), ["1.1.1.1", "2.2.2.2"]);
+        assert_eq!(ips(&resolver.address_set(t0 + secs(90))), ["1.1.1.1"]);
+        assert!(resolver.address_set(t0 + secs(150)).is_empty());
+    }
+
+    #[test]
+    fn idle_proxy_names_are_forgotten_pinned_ones_kept() {
+        let mut resolver = TtlResolver::new(GRACE);
+        let t0 = Instant::now();
+        resolver.observe("codeforces.com", &[answer("1.1.1.1", 60)], true, t0);
+        resolver.observe("assets.leetcode.com", &[answer("2.2.2.2", 60)], false, t0);
+        resolver.observe("cdn.leetcode.com", &[answer("3.3.3.3", 60)], false, t0);
+        resolver.touch("cdn.leetcode.com", t0 + secs(300));
+
+        let mut due = resolver.due_names(t0 + IDLE_NAME_TIMEOUT);
+        due.sort();
+        assert_eq!(due, ["cdn.leetcode.com", "codeforces.com"]);
+        assert!(!resolver.names.contains_key("assets.leetcode.com"));
+    }
+
+    #[test]
+    fn a_proxied_lookup_does_not_unpin_a_name() {
+        let mut resolver = TtlResolver::new(GRACE);
+        let t0 = Instant::now();
+        resolver.observe("codeforces.com", &[answer("1.1.1.1", 60)], true, t0);
+        resolver.observe("codeforces.com", &[answer("1.1.1.1", 60)], false, t0);
+
+        assert_eq!(resolver.due_names(t0 + IDLE_NAME_TIMEOUT * 2), ["codeforces.com"]);
+    }
+}
@@ -1,10 +1,15 @@
 use std::ffi::c_void;
 use std::mem::zeroed;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::Path;
+use std::sync::mpsc::Sender;
 use windows::core::{GUID, PWSTR};
-use windows::Win32::Foundation::{ERROR_SUCCESS, HANDLE};
+use windows::Win32::Foundation::{ERROR_SUCCESS, FWP_E_FILTER_NOT_FOUND, HANDLE};
 use windows::Win32::NetworkManagement::WindowsFilteringPlatform::*;
 use windows::Win32::System::Rpc::RPC_C_AUTHN_WINNT;
+use super::doh::DOT_PORT;
+use super::error::NetworkError;
+use crate::cloud_reporter::{AppLogs, ViolationType};
 
 
 const PROCTOR_PROVIDER_KEY: GUID = GUID::from_u128(0x4B6E8F31_2C5A_4B9A_9F0A_1B2C3D4E5F6A);
@@ -17,8 +22,8 @@ const ALLOWED_APPS_EXACT: &[&str] = &[
     r"C:\Program Files\Microsoft\Edge\Application\msedge.exe",
     r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
     r"C:\Program Files\Mozilla Firefox\firefox.exe",
-    // DEBUG: for mongodb connection add some logic to allow this app
-    // r"C:\Users\YourName\...\traffic_control.exe"
+    // the proctor binary itself is permitted at runtime (see allowed_apps), it forwards DNS
+    // for the local proxy and talks to the reporting server
     
     // system processes needed for Wifi/DNS
     r"C:\Windows\System32\svchost.exe", 
@@ -28,12 +33,19 @@ const ALLOWED_APPS_EXACT: &[&str] = &[
 
 pub struct WfpGuard {
     engine_handle: HANDLE,
+    // every filter this session added (key, display name), compared against BFE by check_filters
+    filters: Vec<(GUID, String)>,
+    // net event subscription reporting blocked DNS-over-TLS attempts (see watch_dot_attempts)
+    dot_events: Option<(HANDLE, *mut Sender<AppLogs>)>,
+    // FWPM_ENGINE_COLLECT_NET_EVENTS before watch_dot_attempts turned it on, put back on Drop.
+    // None when it was already on
+    collect_events_before: Option<u32>,
 }
 
 unsafe impl Send for WfpGuard {}
 
 impl WfpGuard {
-    pub fn new() -> Result<Self, String> {
+    pub fn new() -> Result<Self, NetworkError> {
         unsafe {
             let mut handle = HANDLE::default();
             
@@ -51,19 +63,51 @@ impl WfpGuard {
             );
 
             if err != ERROR_SUCCESS.0 {
-                return Err(format!("[wfp]: Failed to open WFP Engine: Code {}", err));
+                return Err(NetworkError::wfp("Failed to open WFP Engine", err));
             }
 
-            Ok(Self { engine_handle: handle })
+            Ok(Self { engine_handle: handle, filters: Vec::new(), dot_events: None, collect_events_before: None })
         }
     }
 
-    pub fn apply_ale_lockdown(&self) -> Result<(), String> {
+    // every filter goes in one transaction so BFE never holds half a lockdown
+    pub fn apply_ale_lockdown(&mut self) -> Result<(), NetworkError> {
+        self.in_transaction(|guard| unsafe { guard.add_lockdown_filters() })
+    }
+
+    // runs `f` in one BFE transaction, any failure (the commit included) aborts it
+    // and self.filters goes back to what BFE still holds
+    fn in_transaction(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), NetworkError>,
+    ) -> Result<(), NetworkError> {
         unsafe {
-            // atart transaction
+            // start transaction
             let err = FwpmTransactionBegin0(self.engine_handle, 0);
-            if err != ERROR_SUCCESS.0 { return Err("[wfp]: Transaction Begin Failed".into()); }
+            if err != ERROR_SUCCESS.0 {
+                return Err(NetworkError::WfpTransaction { context: "Begin", code: err });
+            }
+
+            let committed = self.filters.clone();
+            let result = f(self).and_then(|_| {
+                let err = FwpmTransactionCommit0(self.engine_handle);
+                if err != ERROR_SUCCESS.0 {
+                    return Err(NetworkError::WfpTransaction { context: "Commit", code: err });
+                }
+                Ok(())
+            });
 
+            if result.is_err() {
+                let _ = FwpmTransactionAbort0(self.engine_handle);
+                self.filters = committed;
+            }
+            result
+        }
+    }
+
+    // provider, sublayer and filters of the lockdown, called inside the transaction
+    unsafe fn add_lockdown_filters(&mut self) -> Result<(), NetworkError> {
+        unsafe {
             // register provider
             let mut provider: FWPM_PROVIDER0 = zeroed();
             provider.providerKey = PROCTOR_PROVIDER_KEY;
@@ -90,8 +134,25 @@ impl WfpGuard {
                 None
             )?;
 
-            // permit whitelist filter
-            for (i, app_path) in ALLOWED_APPS_EXACT.iter().enumerate() {
+            // block DNS-over-TLS for every process, browsers included. weight is above the
+            // app permits so an allowed browser can't reach a DoT resolver either
+            for (name, layer) in [
+                ("Block DNS-over-TLS V4", FWPM_LAYER_ALE_AUTH_CONNECT_V4),
+                ("Block DNS-over-TLS V6", FWPM_LAYER_ALE_AUTH_CONNECT_V6),
+            ] {
+                let mut condition: FWPM_FILTER_CONDITION0 = zeroed();
+                condition.fieldKey = FWPM_CONDITION_IP_REMOTE_PORT;
+                condition.matchType = FWP_MATCH_EQUAL;
+                condition.conditionValue.r#type = FWP_UINT16;
+                condition.conditionValue.Anonymous.uint16 = DOT_PORT;
+
+                self.add_filter(name, layer, FWP_ACTION_BLOCK, 15, Some(&[condition]))?;
+            }
+
+            // permit whitelist filter, a browser that can't be permitted is skipped but the
+            // proctor itself has to be (it forwards DNS for the proxy)
+            let own_exe = std::env::current_exe().map(|p| p.display().to_string()).unwrap_or_default();
+            for (i, app_path) in allowed_apps().iter().enumerate() {
                 if !Path::new(app_path).exists() {
                     println!("Skipping missing app: {}", app_path);
                     continue; 
@@ -113,7 +174,7 @@ impl WfpGuard {
                         &filter_name,
                         FWPM_LAYER_ALE_AUTH_CONNECT_V4,
                         FWP_ACTION_PERMIT,
-                        15,
+                        14,
                         Some(&[condition])
                     );
 
@@ -123,26 +184,157 @@ impl WfpGuard {
                     if let Err(e) = res {
                         println!("Failed to whitelist {}: {}", app_path, e);
                     }
+                } else if *app_path == own_exe {
+                    return Err(NetworkError::AppId { path: app_path.clone(), code: err });
                 } else {
                     println!("Could not generate App ID for {}. Code: {}", app_path, err);
                 }
             }
 
-            let err = FwpmTransactionCommit0(self.engine_handle);
-            if err != ERROR_SUCCESS.0 { return Err("[wfp]: WFP Commit Failed".into()); }
+            Ok(())
+        }
+    }
+
+    // deletes the filters this session added, rolls back a committed lockdown
+    pub fn remove_filters(&mut self) -> Result<(), NetworkError> {
+        let mut result = Ok(());
+        for (key, name) in std::mem::take(&mut self.filters) {
+            let err = unsafe { FwpmFilterDeleteByKey0(self.engine_handle, &key) };
+            if err != ERROR_SUCCESS.0 && err != FWP_E_FILTER_NOT_FOUND.0 as u32 && result.is_ok() {
+                result = Err(NetworkError::wfp(format!("Failed to delete filter '{}'", name), err));
+            }
+        }
+        result
+    }
+
+    // lists what drifted since the lockdown: our filters that are gone and
+    // foreign filters that were slipped in under our provider / sublayer
+    pub fn check_filters(&self) -> Result<Vec<String>, NetworkError> {
+        let present = self.enum_own_filters()?;
+        let mut drift = Vec::new();
+
+        for (key, name) in &self.filters {
+            if !present.iter().any(|(k, _)| k == key) {
+                drift.push(format!("WFP filter '{}' removed", name));
+            }
+        }
+        for (key, name) in &present {
+            if !self.filters.iter().any(|(k, _)| k == key) {
+                drift.push(format!("Unknown WFP filter '{}' in proctor sublayer", name));
+            }
+        }
+
+        Ok(drift)
+    }
+
+    // deletes whatever is left under our provider / sublayer and applies the lockdown again,
+    // both in one transaction: if anything fails the tampered lockdown stays instead of none
+    pub fn reapply_lockdown(&mut self) -> Result<(), NetworkError> {
+        self.in_transaction(|guard| {
+            for (key, name) in guard.enum_own_filters()? {
+                let err = unsafe { FwpmFilterDeleteByKey0(guard.engine_handle, &key) };
+                if err != ERROR_SUCCESS.0 && err != FWP_E_FILTER_NOT_FOUND.0 as u32 {
+                    return Err(NetworkError::wfp(format!("Failed to delete filter '{}'", name), err));
+                }
+            }
+            guard.filters.clear();
+            unsafe { guard.add_lockdown_filters() }
+        })
+    }
+
+    // subscribes to WFP drop events on the DoT port, every blocked attempt is reported as a violation.
+    // net event collection is a machine wide BFE option, it is switched on here and back off on Drop
+    pub fn watch_dot_attempts(&mut self, tx: Sender<AppLogs>) -> Result<(), NetworkError> {
+        if self.dot_events.is_some() {
+            return Ok(());
+        }
+
+        unsafe {
+            let before = self.collect_net_events()?;
+            if before == 0 {
+                self.set_collect_net_events(1)
+                    .map_err(|err| NetworkError::wfp("Failed to enable net event collection", err))?;
+                self.collect_events_before = Some(before);
+            }
+
+            let mut condition: FWPM_FILTER_CONDITION0 = zeroed();
+            condition.fieldKey = FWPM_CONDITION_IP_REMOTE_PORT;
+            condition.matchType = FWP_MATCH_EQUAL;
+            condition.conditionValue.r#type = FWP_UINT16;
+            condition.conditionValue.Anonymous.uint16 = DOT_PORT;
+
+            let mut template: FWPM_NET_EVENT_ENUM_TEMPLATE0 = zeroed();
+            template.numFilterConditions = 1;
+            template.filterCondition = &mut condition;
+
+            let mut subscription: FWPM_NET_EVENT_SUBSCRIPTION0 = zeroed();
+            subscription.enumTemplate = &mut template;
+
+            // the callback runs on a BFE worker thread, it gets its own sender until Drop
+            let context = Box::into_raw(Box::new(tx));
+            let mut events_handle = HANDLE::default();
 
+            let err = FwpmNetEventSubscribe0(
+                self.engine_handle,
+                &subscription,
+                Some(dot_event_callback),
+                Some(context as *const c_void),
+                &mut events_handle,
+            );
+
+            if err != ERROR_SUCCESS.0 {
+                drop(Box::from_raw(context));
+                self.restore_collect_net_events();
+                return Err(NetworkError::wfp("Failed to subscribe to net events", err));
+            }
+
+            self.dot_events = Some((events_handle, context));
             Ok(())
         }
     }
 
+    unsafe fn collect_net_events(&self) -> Result<u32, NetworkError> {
+        unsafe {
+            let mut value: *mut FWP_VALUE0 = std::ptr::null_mut();
+            let err = FwpmEngineGetOption0(self.engine_handle, FWPM_ENGINE_COLLECT_NET_EVENTS, &mut value);
+            if err != ERROR_SUCCESS.0 || value.is_null() {
+                return Err(NetworkError::wfp("Failed to read net event collection", err));
+            }
+            let current = if (*value).r#type == FWP_UINT32 { (*value).Anonymous.uint32 } else { 0 };
+            FwpmFreeMemory0(&mut value as *mut _ as *mut *mut c_void);
+            Ok(current)
+        }
+    }
+
+    unsafe fn set_collect_net_events(&self, on: u32) -> Result<(), u32> {
+        unsafe {
+            let mut value: FWP_VALUE0 = zeroed();
+            value.r#type = FWP_UINT32;
+            value.Anonymous.uint32 = on;
+            match FwpmEngineSetOption0(self.engine_handle, FWPM_ENGINE_COLLECT_NET_EVENTS, &value) {
+                err if err == ERROR_SUCCESS.0 => Ok(()),
+                err => Err(err),
+            }
+        }
+    }
+
+    // puts the option back the way watch_dot_attempts found it
+    fn restore_collect_net_events(&mut self) {
+        if let Some(before) = self.collect_events_before.take()
+            && let Err(err) = unsafe { self.set_collect_net_events(before) }
+        {
+            eprintln!("[wfp]: Failed to restore net event collection: 0x{:08X}", err);
+        }
+    }
+
     unsafe fn add_filter(
-        &self,
+        &mut self,
         name: &str,
         layer_key: GUID,
         action_type: FWP_ACTION_TYPE,
         weight_uint8: u8,
         conditions: Option<&[FWPM_FILTER_CONDITION0]>,
-    ) -> Result<(), String> {
+    ) -> Result<(), NetworkError> {
         let mut filter: FWPM_FILTER0 = zeroed();
         filter.filterKey = GUID::new().unwrap();
         filter.providerKey = &PROCTOR_PROVIDER_KEY as *const GUID as *mut GUID;
@@ -162,15 +354,71 @@ impl WfpGuard {
 
         let err = FwpmFilterAdd0(self.engine_handle, &filter, None, None);
         if err != ERROR_SUCCESS.0 {
-            return Err(format!("[wfp] Failed to add filter '{}': {}", name, err));
+            return Err(NetworkError::wfp(format!("Failed to add filter '{}'", name), err));
         }
+        self.filters.push((filter.filterKey, name.to_string()));
         Ok(())
     }
+
+    // every filter in BFE that carries our provider or sits in our sublayer
+    fn enum_own_filters(&self) -> Result<Vec<(GUID, String)>, NetworkError> {
+        const PAGE: u32 = 256;
+
+        unsafe {
+            let mut enum_handle = HANDLE::default();
+            let err = FwpmFilterCreateEnumHandle0(self.engine_handle, None, &mut enum_handle);
+            if err != ERROR_SUCCESS.0 {
+                return Err(NetworkError::wfp("Failed to enumerate filters", err));
+            }
+
+            let mut found = Vec::new();
+            let mut result = Ok(());
+            loop {
+                let mut entries: *mut *mut FWPM_FILTER0 = std::ptr::null_mut();
+                let mut count = 0u32;
+                let err = FwpmFilterEnum0(self.engine_handle, enum_handle, PAGE, &mut entries, &mut count);
+                if err != ERROR_SUCCESS.0 {
+                    result = Err(NetworkError::wfp("Failed to enumerate filters", err));
+                    break;
+                }
+
+                for i in 0..count as usize {
+                    let filter = &**entries.add(i);
+                    let ours = filter.subLayerKey == PROCTOR_SUBLAYER_KEY
+                        || (!filter.providerKey.is_null() && *filter.providerKey == PROCTOR_PROVIDER_KEY);
+                    if ours {
+                        let name = if filter.displayData.name.is_null() {
+                            String::new()
+                        } else {
+                            filter.displayData.name.to_string().unwrap_or_default()
+                        };
+                        found.push((filter.filterKey, name));
+                    }
+                }
+
+                if !entries.is_null() {
+                    FwpmFreeMemory0(&mut entries as *mut *mut *mut FWPM_FILTER0 as *mut *mut c_void);
+                }
+                if count < PAGE {
+                    break;
+                }
+            }
+
+            let _ = FwpmFilterDestroyEnumHandle0(self.engine_handle, enum_handle);
+            result.map(|_| found)
+        }
+    }
 }
 
 impl Drop for WfpGuard {
     fn drop(&mut self) {
         unsafe {
+            // unsubscribe first, it waits for in flight callbacks so the sender can be freed after
+            if let Some((events_handle, context)) = self.dot_events.take() {
+                let _ = FwpmNetEventUnsubscribe0(self.engine_handle, events_handle);
+                drop(Box::from_raw(context));
+            }
+            self.restore_collect_net_events();
             let _ = FwpmEngineClose0(self.engine_handle);
         }
     }
@@ -178,6 +426,61 @@ impl Drop for WfpGuard {
 
 // Helper functions
 
+unsafe extern "system" fn dot_event_callback(context: *mut c_void, event: *const FWPM_NET_EVENT1) {
+    unsafe {
+        if context.is_null() || event.is_null() || (*event).r#type != FWPM_NET_EVENT_TYPE_CLASSIFY_DROP {
+            return;
+        }
+
+        let header = &(*event).header;
+        if header.flags & FWPM_NET_EVENT_FLAG_REMOTE_PORT_SET == 0 || header.remotePort != DOT_PORT {
+            return;
+        }
+
+        let remote = if header.flags & FWPM_NET_EVENT_FLAG_REMOTE_ADDR_SET == 0 {
+            "unknown".to_string()
+        } else if header.ipVersion == FWP_IP_VERSION_V4 {
+            // WFP hands out IPv4 addresses in host byte order
+            IpAddr::V4(Ipv4Addr::from(header.Anonymous2.remoteAddrV4)).to_string()
+        } else {
+            IpAddr::V6(Ipv6Addr::from(header.Anonymous2.remoteAddrV6.byteArray16)).to_string()
+        };
+
+        let app = if header.flags & FWPM_NET_EVENT_FLAG_APP_ID_SET != 0 {
+            app_id_to_string(&header.appId)
+        } else {
+            "unknown app".to_string()
+        };
+
+        let tx = &*(context as *const Sender<AppLogs>);
+        let _ = tx.send(AppLogs::Violation(
+            ViolationType::Network,
+            format!("[network] [doh] DNS-over-TLS ATTEMPT BLOCKED: '{}' -> {}:{}", app, remote, DOT_PORT),
+        ));
+    }
+}
+
+// WFP app ids are the NT device path of the binary as a NUL terminated UTF-16 blob
+unsafe fn app_id_to_string(blob: &FWP_BYTE_BLOB) -> String {
+    if blob.data.is_null() || blob.size < 2 {
+        return "unknown app".to_string();
+    }
+
+    unsafe {
+        let wide = std::slice::from_raw_parts(blob.data as *const u16, blob.size as usize / 2);
+        let end = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+        String::from_utf16_lossy(&wide[..end])
+    }
+}
+
+fn allowed_apps() -> Vec<String> {
+    let mut apps: Vec<String> = ALLOWED_APPS_EXACT.iter().map(|s| s.to_string()).collect();
+    if let Ok(exe) = std::env::current_exe() {
+        apps.push(exe.display().to_string());
+    }
+    apps
+}
+
 // wide strings (Windows uses UTF-16)
 fn wstr(s: &str) -> PWSTR {
     let encoded: Vec<u16> = s.encode_utf16().chain(std::iter::once(0)).collect();
@@ -0,0 +1,78 @@
+// allowed host names, exact hosts are resolved up front during lockdown
+// wildcard entries ("*.example.com") can only be resolved through the DNS proxy
+pub const WHITELIST_HOSTS: &[&str] = &[
+    // target sites
+    "codeforces.com",
+    "www.codeforces.com",
+    "*.codeforces.com",
+    "leetcode.com",
+    "www.leetcode.com",
+    "*.leetcode.com",
+
+    // cloudflare security (codeforces uses it for auth)
+    "challenges.cloudflare.com",
+
+    // google recaptcha
+    "www.google.com",
+    "www.gstatic.com",
+    "fonts.gstatic.com",
+    "recaptcha.net",
+    "www.recaptcha.net",
+
+    // CDNs
+    "cdnjs.cloudflare.com",
+    "fonts.googleapis.com",
+    "assets.leetcode.com",
+];
+
+// port used when pre-resolving the exact hosts (HTTPS only)
+pub const WHITELIST_PORT: u16 = 443;
+
+
+// exact (non wildcard) hosts, used for the initial resolution
+pub fn exact_hosts() -> impl Iterator<Item = &'static str> {
+    WHITELIST_HOSTS.iter().copied().filter(|h| !h.starts_with("*."))
+}
+
+// true if the queried name is allowed by an exact entry or a wildcard entry
+// "*.leetcode.com" matches "assets.leetcode.com" but not "leetcode.com" itself
+pub fn is_whitelisted(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+
+    WHITELIST_HOSTS.iter().any(|pattern| match pattern.strip_prefix("*.") {
+        Some(suffix) => name.len() > suffix.len() + 1
+            && name.ends_with(suffix)
+            && name.as_bytes()[name.len() - suffix.len() - 1] == b'.',
+        None => name == *pattern,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_entries_match_in_any_case() {
+        assert!(is_whitelisted("codeforces.com"));
+        assert!(is_whitelisted("CodeForces.COM."));
+        assert!(is_whitelisted("challenges.cloudflare.com"));
+        assert!(!is_whitelisted("cloudflare.com"));
+        assert!(!is_whitelisted("example.com"));
+        assert!(!is_whitelisted(""));
+    }
+
+    #[test]
+    fn wildcards_match_subdomains_only() {
+        assert!(is_whitelisted("assets.leetcode.com"));
+        assert!(is_whitelisted("a.b.leetcode.com"));
+        assert!(!is_whitelisted("evilleetcode.com"));
+        assert!(!is_whitelisted(".leetcode.com"));
+        assert!(!is_whitelisted("leetcode.com.evil.com"));
+    }
+
+    #[test]
+    fn exact_hosts_skip_wildcards() {
+        assert!(exact_hosts().all(|h| !h.contains('*')));
+        assert!(exact_hosts().any(|h| h == "leetcode.com"));
+    }
+}
@@ -0,0 +1,348 @@
+// proctor dashboard: sessions -> students of a session -> timeline of a student.
+// the current view is kept in the url hash and refreshed every few seconds.
+// every api call carries the bearer token from the sign-in, kept for the browser tab only
+
+const REFRESH_MS = 5000;
+
+const $ = (id) => document.getElementById(id);
+
+class Unauthorized extends Error {}
+
+let token = sessionStorage.getItem("token");
+// evidence id -> object url, images are fetched with the token and kept across refreshes
+const images = new Map();
+
+function el(tag, attrs = {}, ...children) {
+  const node = document.createElement(tag);
+  for (const [key, value] of Object.entries(attrs)) {
+    if (key === "onclick") node.onclick = value;
+    else node.setAttribute(key, value);
+  }
+  for (const child of children) {
+    if (child === null || child === undefined) continue;
+    node.append(child instanceof Node ? child : String(child));
+  }
+  return node;
+}
+
+function time(secs) {
+  if (!secs) return "-";
+  return new Date(secs * 1000).toLocaleTimeString();
+}
+
+function ago(secs) {
+  if (!secs) return "never";
+  const diff = Math.max(0, Math.floor(Date.now() / 1000 - secs));
+  if (diff < 60) return diff + "s ago";
+  if (diff < 3600) return Math.floor(diff / 60) + "m ago";
+  return Math.floor(diff / 3600) + "h ago";
+}
+
+function riskClass(score) {
+  if (score >= 50) return "high";
+  if (score > 0) return "medium";
+  return "low";
+}
+
+function route() {
+  const parts = location.hash.replace(/^#\/?/, "").split("/").filter(Boolean).map(decodeURIComponent);
+  return { session: parts[0], student: parts[1] };
+}
+
+function go(session, student) {
+  const parts = [session, student].filter(Boolean).map(encodeURIComponent);
+  location.hash = "#/" + parts.join("/");
+}
+
+async function api(url, options = {}) {
+  const headers = { ...(options.headers || {}) };
+  if (token) headers["Authorization"] = "Bearer " + token;
+  const res = await fetch(url, { ...options, headers });
+  if (res.status === 401) throw new Unauthorized();
+  if (!res.ok) throw new Error(url + ": " + res.status);
+  return res;
+}
+
+async function fetchJson(url) {
+  return (await api(url)).json();
+}
+
+function signedOut() {
+  token = null;
+  sessionStorage.removeItem("token");
+  sessionStorage.removeItem("username");
+  for (const url of images.values()) URL.revokeObjectURL(url);
+  images.clear();
+  $("account").hidden = true;
+  show("login-view");
+}
+
+async function login(event) {
+  event.preventDefault();
+  try {
+    const res = await api("/api/auth/login", {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ username: $("login-username").value, password: $("login-password").value }),
+    });
+    token = (await res.json()).token;
+    sessionStorage.setItem("token", token);
+    sessionStorage.setItem("username", $("login-username").value);
+    $("login-password").value = "";
+    $("error").hidden = true;
+    refresh();
+  } catch (err) {
+    $("error").textContent = err instanceof Unauthorized ? "Wrong username or password" : err.message;
+    $("error").hidden = false;
+  }
+}
+
+async function logout() {
+  try {
+    await api("/api/auth/logout", { method: "POST" });
+  } catch (_) {
+    // signed out locally either way
+  }
+  signedOut();
+}
+
+function show(view) {
+  for (const id of ["login-view", "sessions-view", "students-view", "timeline-view"]) {
+    $(id).hidden = id !== view;
+  }
+}
+
+function crumbs({ session, student }) {
+  const nav = $("crumbs");
+  nav.replaceChildren(el("a", { onclick: () => go() }, "Sessions"));
+  if (session) nav.append(" / ", el("a", { onclick: () => go(session) }, session));
+  if (student) nav.append(" / ", student);
+}
+
+async function renderSessions() {
+  const sessions = await fetchJson("/api/dashboard/sessions");
+  const rows = sessions.map((s) =>
+    el("tr", {},
+      el("td", {}, el("a", { onclick: () => go(s.session_id) }, s.session_id)),
+      el("td", {}, s.starts_at ? time(s.starts_at) + " - " + time(s.ends_at) : "-"),
+      el("td", {}, s.students),
+      el("td", {}, s.online),
+      el("td", {}, s.flagged),
+      el("td", {}, ago(s.last_activity)),
+    ));
+  if (rows.length === 0) rows.push(el("tr", {}, el("td", { colspan: 6 }, "No active sessions")));
+  $("sessions").replaceChildren(...rows);
+  show("sessions-view");
+}
+
+async function renderStudents(session) {
+  const students = await fetchJson("/api/dashboard/sessions/" + encodeURIComponent(session) + "/students");
+  $("students-title").textContent = "Session " + session;
+  $("session-report").onclick = () => download("/api/sessions/" + encodeURIComponent(session) + "/report.json", session + "-report.json");
+  const rows = students.map((s) =>
+    el("tr", {},
+      el("td", {}, el("a", { onclick: () => go(session, s.student_id) }, s.student_id), s.name ? " " + s.name : ""),
+      el("td", { class: s.online ? "online" : "offline" }, s.online ? "online" : "offline"),
+      el("td", {}, s.state || "-", s.extra_secs ? " (+" + Math.round(s.extra_secs / 60) + " min)" : ""),
+      el("td", {}, ago(s.last_heartbeat)),
+      el("td", {}, el("span", { class: "risk " + riskClass(s.risk_score) }, s.risk_score)),
+      el("td", {},
+        el("ul", { class: "violations" },
+          ...s.latest_violations.map((v) => el("li", {}, time(v.at) + " " + v.message)))),
+    ));
+  if (rows.length === 0) rows.push(el("tr", {}, el("td", { colspan: 6 }, "No students yet")));
+  $("students").replaceChildren(...rows);
+  await renderAlerts(session);
+  show("students-view");
+}
+
+// alerts that aren't resolved yet, above the students
+async function renderAlerts(session) {
+  const alerts = (await fetchJson("/api/alerts?session_id=" + encodeURIComponent(session)))
+    .filter((a) => a.status !== "resolved");
+  const rows = alerts.map((a) =>
+    el("tr", {},
+      el("td", {}, el("span", { class: "severity " + a.severity }, a.severity), " " + a.rule_name),
+      el("td", {}, el("a", { onclick: () => go(session, a.student_id) }, a.student_id)),
+      el("td", {}, a.message, a.count > 1 ? " (x" + a.count + ")" : ""),
+      el("td", {}, ago(a.last_at)),
+      el("td", {}, a.status, a.acknowledged_by ? " by " + a.acknowledged_by : ""),
+      el("td", {},
+        a.status === "open" ? el("button", { onclick: () => alertAction(a, "acknowledge") }, "Acknowledge") : null,
+        el("button", { onclick: () => alertAction(a, "resolve") }, "Resolve")),
+    ));
+  $("alerts").replaceChildren(...rows);
+  $("alerts-table").hidden = rows.length === 0;
+}
+
+async function alertAction(alert, action) {
+  try {
+    await api("/api/alerts/" + encodeURIComponent(alert.alert_id) + "/" + action, { method: "POST" });
+  } catch (err) {
+    $("error").textContent = err.message;
+    $("error").hidden = false;
+    return;
+  }
+  refresh();
+}
+
+async function renderTimeline(session, student) {
+  const url = "/api/dashboard/sessions/" + encodeURIComponent(session)
+    + "/students/" + encodeURIComponent(student) + "/timeline";
+  const timeline = await fetchJson(url);
+  $("timeline-title").textContent = student + " in " + session;
+  $("timeline-note").textContent = timeline.truncated ? "Showing the most recent entries only." : "";
+
+  // evidence linked to a violation goes under it, the rest (webcam snapshots) stands on its own
+  const byViolation = new Map();
+  const items = [];
+  for (const e of timeline.evidence) {
+    if (e.violation_id) {
+      if (!byViolation.has(e.violation_id)) byViolation.set(e.violation_id, []);
+      byViolation.get(e.violation_id).push(e);
+    } else {
+      items.push({ at: e.uploaded_at, node: evidenceItem(e) });
+    }
+  }
+
+  for (const entry of timeline.entries) {
+    const violation = entry.level.startsWith("VIOLATION_");
+    const node = el("li", { class: violation ? "violation" : "" },
+      el("span", { class: "time" }, time(entry.at)),
+      el("span", { class: "level" }, entry.level),
+      entry.message);
+    for (const e of byViolation.get(entry.violation_id) || []) {
+      node.append(evidenceImage(e));
+    }
+    items.push({ at: entry.at, node });
+  }
+
+  items.sort((a, b) => a.at - b.at);
+  $("timeline").replaceChildren(...items.map((i) => i.node));
+  await renderIncidents(session, student);
+  show("timeline-view");
+}
+
+const INCIDENT_ACTIONS = [["confirmed", "Confirm"], ["false_positive", "False positive"], ["escalated", "Escalate"], ["pending", "Reopen"]];
+
+// the student's violations grouped for review, above the timeline
+async function renderIncidents(session, student) {
+  const incidents = await fetchJson("/api/sessions/" + encodeURIComponent(session)
+    + "/incidents?student_id=" + encodeURIComponent(student));
+  const rows = incidents.map((i) =>
+    el("tr", {},
+      el("td", {}, el("span", { class: "level" }, i.level), " " + i.signature),
+      el("td", {}, i.entries),
+      el("td", {}, ago(i.last_at)),
+      el("td", {}, el("span", { class: "review " + i.status }, i.status.replace("_", " ")),
+        i.reviewed_by ? " by " + i.reviewed_by : ""),
+      el("td", {},
+        el("ul", { class: "notes" }, ...i.notes.map((n) => el("li", {}, n.author + ": " + n.text)))),
+      el("td", {},
+        ...INCIDENT_ACTIONS.filter(([status]) => status !== i.status)
+          .map(([status, label]) => el("button", { onclick: () => reviewIncident(i, status) }, label)),
+        el("button", { onclick: () => noteIncident(i) }, "Add note")),
+    ));
+  $("incidents").replaceChildren(...rows);
+  $("incidents-table").hidden = rows.length === 0;
+}
+
+async function reviewIncident(incident, status) {
+  const note = prompt("Note for the review (optional)");
+  if (note === null) return;
+  await incidentRequest(incident, "status", "PUT", { status, note });
+}
+
+async function noteIncident(incident) {
+  const text = prompt("Note");
+  if (!text) return;
+  await incidentRequest(incident, "notes", "POST", { text });
+}
+
+async function incidentRequest(incident, path, method, body) {
+  try {
+    await api("/api/incidents/" + encodeURIComponent(incident.incident_id) + "/" + path, {
+      method,
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify(body),
+    });
+  } catch (err) {
+    $("error").textContent = err.message;
+    $("error").hidden = false;
+    return;
+  }
+  refresh();
+}
+
+function evidenceItem(e) {
+  return el("li", { class: "evidence" },
+    el("span", { class: "time" }, time(e.uploaded_at)),
+    el("span", { class: "level" }, e.kind.toUpperCase()),
+    e.size + " bytes",
+    evidenceImage(e));
+}
+
+function evidenceImage(e) {
+  if (!e.content_type.startsWith("image/")) {
+    return el("a", { onclick: () => openEvidence(e) }, " [" + e.kind + "]");
+  }
+  const img = el("img", { alt: e.kind });
+  evidenceUrl(e).then((url) => { img.src = url; }).catch(() => { img.alt = e.kind + " (unavailable)"; });
+  return el("a", { class: "evidence", onclick: () => openEvidence(e) }, img);
+}
+
+async function evidenceUrl(e) {
+  if (!images.has(e.evidence_id)) {
+    const blob = await (await api("/api/evidence/" + encodeURIComponent(e.evidence_id))).blob();
+    images.set(e.evidence_id, URL.createObjectURL(blob));
+  }
+  return images.get(e.evidence_id);
+}
+
+// links can't carry the token, the file is fetched and handed to the browser
+async function download(url, filename) {
+  try {
+    const blob = await (await api(url)).blob();
+    const link = el("a", { href: URL.createObjectURL(blob), download: filename });
+    link.click();
+    URL.revokeObjectURL(link.href);
+  } catch (err) {
+    $("error").textContent = err.message;
+    $("error").hidden = false;
+  }
+}
+
+async function openEvidence(e) {
+  window.open(await evidenceUrl(e), "_blank");
+}
+
+async function refresh() {
+  if (!token) {
+    signedOut();
+    return;
+  }
+  $("username").textContent = sessionStorage.getItem("username") || "";
+  $("account").hidden = false;
+  const current = route();
+  crumbs(current);
+  try {
+    if (current.student) await renderTimeline(current.session, current.student);
+    else if (current.session) await renderStudents(current.session);
+    else await renderSessions();
+    $("error").hidden = true;
+    $("updated").textContent = "updated " + new Date().toLocaleTimeString();
+  } catch (err) {
+    if (err instanceof Unauthorized) {
+      signedOut();
+      return;
+    }
+    $("error").textContent = err.message;
+    $("error").hidden = false;
+  }
+}
+
+$("login").addEventListener("submit", login);
+$("logout").onclick = logout;
+window.addEventListener("hashchange", refresh);
+setInterval(refresh, REFRESH_MS);
+refresh();
@@ -0,0 +1,669 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument};
+use mongodb::IndexModel;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use crate::{
+    auth::{self, AuthUser, INSTRUCTORS, STAFF},
+    db::{AppState, DbResult},
+    handlers::{collect, db_error, response},
+    models::{
+        Alert, AlertAction, AlertQuery, AlertRule, AlertStatus, LogEntry, NewAlertRule, Role, RuleCondition, Severity,
+    },
+    webhooks,
+};
+
+// alert rules. a rule is a declarative condition over exam_logs or schedule polls, checked on
+// ingestion and by a periodic heartbeat check. a rule that fires opens an alert for the student,
+// proctors acknowledge it and resolve it once handled. new alerts also go out to the webhooks
+
+// the alert list is for the live exam, older ones are in the reports
+const MAX_ALERTS: i64 = 500;
+// states in which the client is done and stops polling on purpose
+const FINISHED_STATES: &[&str] = &["Released", "SignedOut"];
+
+pub async fn init(db: &mongodb::Database) -> DbResult<()> {
+    let alerts = db.collection::<Document>("alerts");
+    // only alerts that aren't resolved have an open_key, so there is one per rule and student
+    let unique = IndexOptions::builder().unique(true).sparse(true).build();
+    alerts
+        .create_index(IndexModel::builder().keys(doc! { "open_key": 1 }).options(unique).build(), None)
+        .await?;
+    alerts
+        .create_index(IndexModel::builder().keys(doc! { "session_id": 1, "last_at": -1 }).build(), None)
+        .await?;
+
+    // a fresh install starts with the usual rules
+    let rules = db.collection::<AlertRule>("alert_rules");
+    if rules.count_documents(None, None).await? == 0 {
+        rules.insert_many(default_rules(), None).await?;
+        println!("[server] created the default alert rules");
+    }
+    Ok(())
+}
+
+// staff: the rules that apply to the sessions the account can see
+pub async fn list_rules(State(state): State<Arc<AppState>>, user: AuthUser) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+    let rules = state.db.collection::<Document>("alert_rules");
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let docs = match collect(rules.find(None, options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let rules: Vec<AlertRule> = docs
+        .into_iter()
+        .filter_map(|d| mongodb::bson::from_document::<AlertRule>(d).ok())
+        .filter(|r| r.session_ids.is_empty() || r.session_ids.iter().any(|s| user.can_access(s)))
+        .collect();
+    Json(rules).into_response()
+}
+
+// instructor: adds a rule for own sessions, rules for every session are for admins
+pub async fn create_rule(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<NewAlertRule>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(INSTRUCTORS) {
+        return e.into_response();
+    }
+    if let Err(e) = can_manage(&user, &req.session_ids) {
+        return e.into_response();
+    }
+    if let Err(message) = validate_rule(&req) {
+        return response(StatusCode::BAD_REQUEST, "error", format!("[server] [alerts] {}", message)).into_response();
+    }
+
+    let rule = AlertRule {
+        rule_id: ObjectId::new().to_hex(),
+        name: req.name.trim().to_string(),
+        severity: req.severity,
+        condition: req.condition,
+        session_ids: req.session_ids,
+        enabled: true,
+        created_by: user.username.clone(),
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    let rules = state.db.collection::<AlertRule>("alert_rules");
+    if let Err(e) = rules.insert_one(&rule, None).await {
+        return db_error(e).into_response();
+    }
+    tracing::info!("[server] [alerts] {} added rule '{}' ({})", user.username, rule.name, rule.rule_id);
+    (StatusCode::CREATED, Json(rule)).into_response()
+}
+
+// instructor: removes a rule, its alerts stay
+pub async fn delete_rule(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(rule_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(INSTRUCTORS) {
+        return e.into_response();
+    }
+    let rules = state.db.collection::<AlertRule>("alert_rules");
+    let rule = match rules.find_one(doc! { "rule_id": &rule_id }, None).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [alerts] no rule {}", rule_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+    if let Err(e) = can_manage(&user, &rule.session_ids) {
+        return e.into_response();
+    }
+    if let Err(e) = rules.delete_one(doc! { "rule_id": &rule_id }, None).await {
+        return db_error(e).into_response();
+    }
+    tracing::info!("[server] [alerts] {} removed rule '{}' ({})", user.username, rule.name, rule_id);
+    response(StatusCode::OK, "success", format!("[server] [alerts] removed rule {}", rule_id)).into_response()
+}
+
+// staff: alerts of the sessions the account can see, latest first
+pub async fn list_alerts(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<AlertQuery>,
+) -> axum::response::Response {
+    let mut filter = doc! {};
+    match &query.session_id {
+        Some(session_id) => {
+            if let Err(e) = user.require_session(STAFF, session_id) {
+                return e.into_response();
+            }
+            filter.insert("session_id", session_id);
+        }
+        None => {
+            if let Err(e) = user.require(STAFF) {
+                return e.into_response();
+            }
+            if user.role != Role::Admin {
+                filter.insert("session_id", doc! { "$in": &user.sessions });
+            }
+        }
+    }
+    if let Some(status) = query.status {
+        filter.insert("status", status_str(status));
+    }
+
+    let alerts = state.db.collection::<Document>("alerts");
+    let options = FindOptions::builder().sort(doc! { "last_at": -1 }).limit(MAX_ALERTS).build();
+    let docs = match collect(alerts.find(filter, options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let alerts: Vec<Alert> = docs.into_iter().filter_map(|d| mongodb::bson::from_document::<Alert>(d).ok()).collect();
+    Json(alerts).into_response()
+}
+
+// staff: someone is looking into it
+pub async fn acknowledge_alert(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(alert_id): Path<String>,
+    body: Option<Json<AlertAction>>,
+) -> axum::response::Response {
+    let now = chrono::Utc::now().timestamp();
+    let mut set = doc! { "status": status_str(AlertStatus::Acknowledged), "acknowledged_by": &user.username, "acknowledged_at": now };
+    if let Some(note) = body.and_then(|Json(b)| b.note) {
+        set.insert("note", note);
+    }
+    update_alert(&state, &user, &alert_id, &[AlertStatus::Open], doc! { "$set": set }).await
+}
+
+// staff: handled, the rule firing again opens a new alert
+pub async fn resolve_alert(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(alert_id): Path<String>,
+    body: Option<Json<AlertAction>>,
+) -> axum::response::Response {
+    let now = chrono::Utc::now().timestamp();
+    let mut set = doc! { "status": status_str(AlertStatus::Resolved), "resolved_by": &user.username, "resolved_at": now };
+    if let Some(note) = body.and_then(|Json(b)| b.note) {
+        set.insert("note", note);
+    }
+    let update = doc! { "$set": set, "$unset": { "open_key": "" } };
+    update_alert(&state, &user, &alert_id, &[AlertStatus::Open, AlertStatus::Acknowledged], update).await
+}
+
+// ingestion: checks the log rules against a stored batch
+pub async fn evaluate_logs(state: Arc<AppState>, logs: Vec<LogEntry>) {
+    let rules = match enabled_rules(&state).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::error!("[server] [alerts] failed to load rules: {:?}", e);
+            return;
+        }
+    };
+
+    for rule in &rules {
+        let matching = |levels: &[String], contains: &Option<String>| {
+            logs.iter()
+                .filter(|l| applies(rule, &l.session_id) && matches(levels, contains, &l.level, &l.message))
+                .collect::<Vec<&LogEntry>>()
+        };
+        match &rule.condition {
+            RuleCondition::Level { levels, contains } => {
+                for log in matching(levels, contains) {
+                    fire(&state, rule, &log.session_id, &log.student_id, &log.message, log.timestamp).await;
+                }
+            }
+            RuleCondition::Rate { levels, contains, count, window_secs } => {
+                let entries = matching(levels, contains);
+                let students: BTreeSet<(&str, &str)> =
+                    entries.iter().map(|l| (l.session_id.as_str(), l.student_id.as_str())).collect();
+                // the window is on the server clock, client timestamps can be anything
+                let since = chrono::Utc::now().timestamp() - window_secs;
+                for (session_id, student_id) in students {
+                    let latest = entries
+                        .iter()
+                        .filter(|l| l.session_id == session_id && l.student_id == student_id)
+                        .map(|l| l.timestamp)
+                        .max()
+                        .unwrap_or(0);
+                    let n = match count_recent(&state, session_id, student_id, levels, contains, since).await {
+                        Ok(n) => n,
+                        Err(e) => {
+                            tracing::error!("[server] [alerts] rule '{}' failed: {:?}", rule.name, e);
+                            continue;
+                        }
+                    };
+                    if n > *count {
+                        let message = format!("{} matching entries in the last {}s", n, window_secs);
+                        fire(&state, rule, session_id, student_id, &message, latest).await;
+                    }
+                }
+            }
+            RuleCondition::Offline { .. } => {}
+        }
+    }
+}
+
+// background: clients that stopped polling while their exam window is open
+pub async fn check_heartbeats(state: Arc<AppState>) {
+    let rules = match enabled_rules(&state).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::error!("[server] [alerts] failed to load rules: {:?}", e);
+            return;
+        }
+    };
+    let offline_rules: Vec<(&AlertRule, i64)> = rules
+        .iter()
+        .filter_map(|r| match r.condition {
+            RuleCondition::Offline { after_secs } => Some((r, after_secs)),
+            _ => None,
+        })
+        .collect();
+    if offline_rules.is_empty() {
+        return;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let sessions = state.db.collection::<Document>("sessions");
+    // extensions and the submit grace are added per student below
+    let filter = doc! { "starts_at": { "$lte": now }, "ends_at": { "$gte": now - 24 * 60 * 60 } };
+    let docs = match collect(sessions.find(filter, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => {
+            tracing::error!("[server] [alerts] failed to list running sessions: {:?}", e);
+            return;
+        }
+    };
+
+    let session_students = state.db.collection::<Document>("session_students");
+    for session in docs {
+        let Ok(session_id) = session.get_str("session_id") else { continue };
+        let Ok(ends_at) = session.get_i64("ends_at") else { continue };
+        let submit_grace = session.get_i64("submit_grace_secs").unwrap_or(0);
+        let students = match collect(session_students.find(doc! { "session_id": session_id }, None).await).await {
+            Ok(docs) => docs,
+            Err(e) => {
+                tracing::error!("[server] [alerts] failed to list students of {}: {:?}", session_id, e);
+                continue;
+            }
+        };
+
+        for d in students {
+            let Ok(student_id) = d.get_str("student_id") else { continue };
+            let Ok(last_seen) = d.get_datetime("last_seen").map(|t| t.timestamp_millis() / 1000) else { continue };
+            let closes_at = ends_at + submit_grace + d.get_i64("extra_secs").unwrap_or(0);
+            let silent = now - last_seen;
+            if now > closes_at {
+                continue;
+            }
+            let due: Vec<&(&AlertRule, i64)> =
+                offline_rules.iter().filter(|(r, after)| silent > *after && applies(r, session_id)).collect();
+            if due.is_empty() || finished(&state, session_id, student_id).await {
+                continue;
+            }
+            for (rule, after_secs) in due {
+                let resolved_at = match last_resolved(&state, &rule.rule_id, session_id, student_id).await {
+                    Ok(at) => at,
+                    Err(e) => {
+                        tracing::error!("[server] [alerts] rule '{}' failed: {:?}", rule.name, e);
+                        continue;
+                    }
+                };
+                if !offline_due(now, last_seen, *after_secs, resolved_at) {
+                    continue;
+                }
+                let message = format!("no contact from the client for {}s", silent);
+                fire(&state, rule, session_id, student_id, &message, now).await;
+            }
+        }
+    }
+}
+
+// Helpers functions
+
+fn default_rules() -> Vec<AlertRule> {
+    let now = chrono::Utc::now().timestamp();
+    let rule = |rule_id: &str, name: &str, severity, condition| AlertRule {
+        rule_id: rule_id.to_string(),
+        name: name.to_string(),
+        severity,
+        condition,
+        session_ids: Vec::new(),
+        enabled: true,
+        created_by: "system".to_string(),
+        created_at: now,
+    };
+    let levels = |levels: &[&str]| levels.iter().map(|l| l.to_string()).collect();
+    vec![
+        rule(
+            "critical-violation",
+            "Critical violation",
+            Severity::Critical,
+            RuleCondition::Level {
+                levels: levels(&["VIOLATION_TAMPER", "VERIFICATION_FAIL", "LOCKDOWN_ROLLBACK_FAILED"]),
+                contains: None,
+            },
+        ),
+        rule(
+            "banned-window-burst",
+            "More than 5 banned windows in 10 minutes",
+            Severity::Warning,
+            RuleCondition::Rate {
+                levels: levels(&["VIOLATION_APP"]),
+                contains: Some("BANNED WINDOW".to_string()),
+                count: 5,
+                window_secs: 10 * 60,
+            },
+        ),
+        rule("client-offline", "Client offline for 2 minutes", Severity::Warning, RuleCondition::Offline { after_secs: 120 }),
+    ]
+}
+
+fn validate_rule(req: &NewAlertRule) -> Result<(), String> {
+    if req.name.trim().is_empty() {
+        return Err("rule needs a name".to_string());
+    }
+    match &req.condition {
+        RuleCondition::Level { levels, .. } if levels.is_empty() => Err("rule needs at least one level".to_string()),
+        RuleCondition::Rate { levels, .. } if levels.is_empty() => Err("rule needs at least one level".to_string()),
+        RuleCondition::Rate { count, window_secs, .. } if *count < 0 || *window_secs <= 0 => {
+            Err("count can't be negative and the window must be positive".to_string())
+        }
+        RuleCondition::Offline { after_secs } if *after_secs <= 0 => Err("after_secs must be positive".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// rules for every session are for admins, others need all their sessions assigned
+fn can_manage(user: &AuthUser, session_ids: &[String]) -> Result<(), auth::AuthError> {
+    if user.role == Role::Admin {
+        return Ok(());
+    }
+    if session_ids.is_empty() {
+        return Err(auth::forbidden("only admins manage rules for every session".to_string()));
+    }
+    match session_ids.iter().find(|s| !user.can_access(s)) {
+        Some(s) => Err(auth::forbidden(format!("not assigned to session {}", s))),
+        None => Ok(()),
+    }
+}
+
+async fn update_alert(
+    state: &AppState,
+    user: &AuthUser,
+    alert_id: &str,
+    from: &[AlertStatus],
+    update: Document,
+) -> axum::response::Response {
+    let alerts = state.db.collection::<Document>("alerts");
+    let alert = match alerts.find_one(doc! { "alert_id": alert_id }, None).await {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [alerts] no alert {}", alert_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+    if let Err(e) = user.require_session(STAFF, alert.get_str("session_id").unwrap_or_default()) {
+        return e.into_response();
+    }
+
+    let from: Vec<&str> = from.iter().map(|s| status_str(*s)).collect();
+    let filter = doc! { "alert_id": alert_id, "status": { "$in": &from } };
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    match alerts.find_one_and_update(filter, update, options).await {
+        Ok(Some(d)) => match mongodb::bson::from_document::<Alert>(d) {
+            Ok(alert) => {
+                tracing::info!("[server] [alerts] {} set {} to {:?}", user.username, alert_id, alert.status);
+                Json(alert).into_response()
+            }
+            Err(e) => {
+                tracing::error!("[server] [alerts] corrupt alert {}: {}", alert_id, e);
+                response(StatusCode::INTERNAL_SERVER_ERROR, "error", "[server] [alerts] corrupt alert".to_string())
+                    .into_response()
+            }
+        },
+        Ok(None) => response(
+            StatusCode::CONFLICT,
+            "error",
+            format!("[server] [alerts] alert {} is already {}", alert_id, alert.get_str("status").unwrap_or("gone")),
+        )
+        .into_response(),
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+async fn enabled_rules(state: &AppState) -> mongodb::error::Result<Vec<AlertRule>> {
+    let rules = state.db.collection::<Document>("alert_rules");
+    let docs = collect(rules.find(doc! { "enabled": true }, None).await).await?;
+    Ok(docs.into_iter().filter_map(|d| mongodb::bson::from_document::<AlertRule>(d).ok()).collect())
+}
+
+fn applies(rule: &AlertRule, session_id: &str) -> bool {
+    rule.session_ids.is_empty() || rule.session_ids.iter().any(|s| s == session_id)
+}
+
+fn matches(levels: &[String], contains: &Option<String>, level: &str, message: &str) -> bool {
+    levels.iter().any(|l| l == level) && contains.as_ref().is_none_or(|text| message.contains(text.as_str()))
+}
+
+async fn count_recent(
+    state: &AppState,
+    session_id: &str,
+    student_id: &str,
+    levels: &[String],
+    contains: &Option<String>,
+    since: i64,
+) -> mongodb::error::Result<i64> {
+    let logs = state.db.collection::<Document>("exam_logs");
+    let filter = doc! {
+        "session_id": session_id,
+        "student_id": student_id,
+        "level": { "$in": levels },
+        "ingested_at": { "$gt": since },
+    };
+    let docs = collect(logs.find(filter, None).await).await?;
+    let n = docs
+        .iter()
+        .filter(|d| {
+            matches(levels, contains, d.get_str("level").unwrap_or_default(), d.get_str("message").unwrap_or_default())
+        })
+        .count();
+    Ok(n as i64)
+}
+
+// whether the latest state the client reported is one it stops polling in
+async fn finished(state: &AppState, session_id: &str, student_id: &str) -> bool {
+    let logs = state.db.collection::<Document>("exam_logs");
+    let filter = doc! { "session_id": session_id, "student_id": student_id, "level": "EXAM_TRANSITION" };
+    let options = mongodb::options::FindOneOptions::builder().sort(doc! { "original_ts": -1 }).build();
+    let Ok(Some(d)) = logs.find_one(filter, options).await else { return false };
+    d.get_str("message")
+        .ok()
+        .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+        .and_then(|t| t.get("to").and_then(|s| s.as_str()).map(|to| FINISHED_STATES.contains(&to)))
+        .unwrap_or(false)
+}
+
+// a proctor resolving an offline alert has seen the silence so far, only a new one counts
+fn offline_due(now: i64, last_seen: i64, after_secs: i64, resolved_at: Option<i64>) -> bool {
+    now - last_seen > after_secs && resolved_at.is_none_or(|resolved_at| last_seen > resolved_at)
+}
+
+async fn last_resolved(
+    state: &AppState,
+    rule_id: &str,
+    session_id: &str,
+    student_id: &str,
+) -> mongodb::error::Result<Option<i64>> {
+    let alerts = state.db.collection::<Document>("alerts");
+    let filter = doc! {
+        "rule_id": rule_id,
+        "session_id": session_id,
+        "student_id": student_id,
+        "status": status_str(AlertStatus::Resolved),
+    };
+    let options = mongodb::options::FindOneOptions::builder().sort(doc! { "resolved_at": -1 }).build();
+    let alert = alerts.find_one(filter, options).await?;
+    Ok(alert.and_then(|d| d.get_i64("resolved_at").ok()))
+}
+
+// opens an alert for the student, or updates the one that is still open
+async fn fire(state: &AppState, rule: &AlertRule, session_id: &str, student_id: &str, message: &str, at: i64) {
+    let alerts = state.db.collection::<Document>("alerts");
+    let open_key = format!("{}|{}|{}", rule.rule_id, session_id, student_id);
+    let alert_id = ObjectId::new().to_hex();
+    let update = doc! {
+        "$set": { "message": message, "last_at": at },
+        "$inc": { "count": 1_i64 },
+        "$setOnInsert": {
+            "alert_id": &alert_id,
+            "rule_id": &rule.rule_id,
+            "rule_name": &rule.name,
+            "severity": severity_str(rule.severity),
+            "session_id": session_id,
+            "student_id": student_id,
+            "status": status_str(AlertStatus::Open),
+            "first_at": at,
+        },
+    };
+    let options = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::Before).build();
+
+    // two batches of one student racing both try to insert, the loser updates
+    let mut result = alerts.find_one_and_update(doc! { "open_key": &open_key }, update.clone(), options.clone()).await;
+    if matches!(&result, Err(e) if auth::is_duplicate(e)) {
+        result = alerts.find_one_and_update(doc! { "open_key": &open_key }, update, options).await;
+    }
+    match result {
+        Ok(None) => {
+            tracing::warn!(
+                "[server] [alerts] {:?} '{}' for {} in {}: {}",
+                rule.severity, rule.name, student_id, session_id, message
+            );
+            let alert = Alert {
+                alert_id,
+                rule_id: rule.rule_id.clone(),
+                rule_name: rule.name.clone(),
+                severity: rule.severity,
+                session_id: session_id.to_string(),
+                student_id: student_id.to_string(),
+                status: AlertStatus::Open,
+                message: message.to_string(),
+                first_at: at,
+                last_at: at,
+                count: 1,
+                acknowledged_by: None,
+                acknowledged_at: None,
+                resolved_by: None,
+                resolved_at: None,
+                note: None,
+            };
+            webhooks::enqueue(state, &alert).await;
+        }
+        Ok(Some(_)) => {}
+        Err(e) => tracing::error!("[server] [alerts] failed to record alert of '{}': {:?}", rule.name, e),
+    }
+}
+
+fn status_str(status: AlertStatus) -> &'static str {
+    match status {
+        AlertStatus::Open => "open",
+        AlertStatus::Acknowledged => "acknowledged",
+        AlertStatus::Resolved => "resolved",
+    }
+}
+
+fn severity_str(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn new_rule(condition: RuleCondition) -> NewAlertRule {
+        NewAlertRule { name: "rule".to_string(), severity: Severity::Warning, condition, session_ids: Vec::new() }
+    }
+
+    #[test]
+    fn matches_level_and_text() {
+        let levels = strings(&["VIOLATION_APP", "VIOLATION_TAMPER"]);
+        let banned = Some("BANNED WINDOW".to_string());
+
+        assert!(matches(&levels, &None, "VIOLATION_TAMPER", "anything"));
+        assert!(!matches(&levels, &None, "INFO", "anything"));
+        assert!(matches(&levels, &banned, "VIOLATION_APP", "BANNED WINDOW: discord"));
+        assert!(!matches(&levels, &banned, "VIOLATION_APP", "banned window: discord"));
+        assert!(!matches(&levels, &banned, "INFO", "BANNED WINDOW: discord"));
+        assert!(!matches(&[], &None, "VIOLATION_APP", ""));
+    }
+
+    #[test]
+    fn rules_apply_to_their_sessions() {
+        let mut rule = default_rules().remove(0);
+        assert!(applies(&rule, "any-session"));
+
+        rule.session_ids = strings(&["s1", "s2"]);
+        assert!(applies(&rule, "s2"));
+        assert!(!applies(&rule, "s3"));
+    }
+
+    #[test]
+    fn rule_validation() {
+        assert!(default_rules()
+            .into_iter()
+            .all(|r| validate_rule(&new_rule(r.condition)).is_ok()));
+
+        let mut unnamed = new_rule(RuleCondition::Offline { after_secs: 60 });
+        unnamed.name = "  ".to_string();
+        assert_eq!(validate_rule(&unnamed), Err("rule needs a name".to_string()));
+
+        let invalid = [
+            RuleCondition::Level { levels: Vec::new(), contains: None },
+            RuleCondition::Rate { levels: Vec::new(), contains: None, count: 1, window_secs: 60 },
+            RuleCondition::Rate { levels: strings(&["INFO"]), contains: None, count: -1, window_secs: 60 },
+            RuleCondition::Rate { levels: strings(&["INFO"]), contains: None, count: 1, window_secs: 0 },
+            RuleCondition::Offline { after_secs: 0 },
+        ];
+        for condition in invalid {
+            assert!(validate_rule(&new_rule(condition.clone())).is_err(), "{:?}", condition);
+        }
+        let zero_count = RuleCondition::Rate { levels: strings(&["INFO"]), contains: None, count: 0, window_secs: 60 };
+        assert!(validate_rule(&new_rule(zero_count)).is_ok());
+    }
+
+    #[test]
+    fn offline_after_silence() {
+        assert!(!offline_due(1_000, 900, 120, None));
+        assert!(!offline_due(1_000, 880, 120, None));
+        assert!(offline_due(1_000, 879, 120, None));
+    }
+
+    #[test]
+    fn resolve_while_still_offline() {
+        // last seen at 500, the alert resolved at 700 while the client is still silent
+        let (last_seen, resolved_at) = (500, Some(700));
+        for now in [700, 730, 10_000] {
+            assert!(!offline_due(now, last_seen, 120, resolved_at), "fired again at {}", now);
+        }
+
+        // the client came back after the resolve and went silent again
+        assert!(!offline_due(800, 750, 120, resolved_at));
+        assert!(offline_due(900, 750, 120, resolved_at));
+    }
+}
@@ -0,0 +1,578 @@
+use argon2::{
+    password_hash::{rand_core::{OsRng, RngCore}, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{
+    db::{AppState, DbResult},
+    handlers::{db_error, response},
+    models::{
+        AssignSessions, ChangePassword, CreateUser, GenericResponse, LoginRequest, LoginResponse, Role, UserInfo,
+        UserRecord,
+    },
+};
+
+// accounts live in `users`, bearer tokens in `auth_tokens` (only their SHA-256 is stored).
+// a token is checked against the user on every request, so disabling an account or changing
+// its sessions applies right away
+
+pub type AuthError = (StatusCode, Json<GenericResponse>);
+
+// besides admins, who always pass
+pub const ADMINS: &[Role] = &[];
+pub const STAFF: &[Role] = &[Role::Instructor, Role::Proctor];
+pub const INSTRUCTORS: &[Role] = &[Role::Instructor];
+pub const CLIENTS: &[Role] = &[Role::StudentClient];
+
+const MIN_PASSWORD_LEN: usize = 10;
+// characters of a join code, without the dash
+pub const JOIN_CODE_LEN: usize = 8;
+// argon2 hash of a password nobody has, unknown accounts are checked against it so a login
+// takes as long whether the username exists or not
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$MUVEk2CKtuqiWuJADjjjAw$fkMEKf7VkRrr6EuHsksf3Z+w1hZ61FpZRhFZ0Gavj9s";
+const STAFF_TOKEN_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+// lab machines get their token before the exam day
+const CLIENT_TOKEN_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// the signed-in account of a request
+pub struct AuthUser {
+    // the student id for tokens from a join code
+    pub username: String,
+    pub role: Role,
+    pub sessions: Vec<String>,
+    // set for tokens from a join code, they only act for that student in its session
+    pub student_id: Option<String>,
+    token_hash: String,
+}
+
+impl AuthUser {
+    pub fn require(&self, roles: &[Role]) -> Result<(), AuthError> {
+        if self.role == Role::Admin || roles.contains(&self.role) {
+            Ok(())
+        } else {
+            Err(forbidden(format!("{:?} accounts can't do this", self.role)))
+        }
+    }
+
+    pub fn can_access(&self, session_id: &str) -> bool {
+        self.role == Role::Admin || self.sessions.iter().any(|s| s == session_id)
+    }
+
+    // role check plus the session assignment
+    pub fn require_session(&self, roles: &[Role], session_id: &str) -> Result<(), AuthError> {
+        self.require(roles)?;
+        if self.can_access(session_id) {
+            Ok(())
+        } else {
+            Err(forbidden(format!("not assigned to session {}", session_id)))
+        }
+    }
+
+    // whether a client may send logs / evidence / schedule polls for the student. join tokens act
+    // for their own student only, accounts for any student of their sessions
+    pub fn can_report(&self, session_id: &str, student_id: &str) -> bool {
+        match &self.student_id {
+            Some(own) => own == student_id && self.can_access(session_id),
+            None => self.can_access(session_id),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| unauthorized("missing bearer token"))?;
+        let token_hash = hash_token(token.trim());
+
+        let tokens = state.db.collection::<Document>("auth_tokens");
+        let filter = doc! { "token_hash": &token_hash, "expires_at": { "$gt": DateTime::now() } };
+        let token = match tokens.find_one(filter, None).await {
+            Ok(Some(d)) => d,
+            Ok(None) => return Err(unauthorized("invalid or expired token")),
+            Err(e) => return Err(db_error(e)),
+        };
+
+        // joined students have no account
+        if let (Ok(student_id), Ok(session_id)) = (token.get_str("student_id"), token.get_str("session_id")) {
+            return Ok(Self {
+                username: student_id.to_string(),
+                role: Role::StudentClient,
+                sessions: vec![session_id.to_string()],
+                student_id: Some(student_id.to_string()),
+                token_hash,
+            });
+        }
+
+        let username = token.get_str("username").unwrap_or_default().to_string();
+        let users = state.db.collection::<UserRecord>("users");
+        match users.find_one(doc! { "username": &username, "disabled": { "$ne": true } }, None).await {
+            Ok(Some(user)) => Ok(Self { username, role: user.role, sessions: user.sessions, student_id: None, token_hash }),
+            Ok(None) => Err(unauthorized("account disabled")),
+            Err(e) => Err(db_error(e)),
+        }
+    }
+}
+
+// indexes, and the first admin from ADMIN_USERNAME / ADMIN_PASSWORD while there is no account
+pub async fn init(db: &mongodb::Database) -> DbResult<()> {
+    let users = db.collection::<UserRecord>("users");
+    let unique = IndexOptions::builder().unique(true).build();
+    users
+        .create_index(IndexModel::builder().keys(doc! { "username": 1 }).options(unique).build(), None)
+        .await?;
+
+    // mongodb drops expired tokens by itself
+    let tokens = db.collection::<Document>("auth_tokens");
+    let ttl = IndexOptions::builder().expire_after(Duration::ZERO).build();
+    tokens
+        .create_index(IndexModel::builder().keys(doc! { "expires_at": 1 }).options(ttl).build(), None)
+        .await?;
+    tokens.create_index(IndexModel::builder().keys(doc! { "token_hash": 1 }).build(), None).await?;
+
+    if users.count_documents(None, None).await? > 0 {
+        return Ok(());
+    }
+    match (env::var("ADMIN_USERNAME"), env::var("ADMIN_PASSWORD")) {
+        (Ok(username), Ok(password)) if password.len() >= MIN_PASSWORD_LEN => {
+            let password_hash = hash_password(password).await?;
+            users
+                .insert_one(
+                    UserRecord {
+                        username: username.clone(),
+                        password_hash,
+                        role: Role::Admin,
+                        sessions: Vec::new(),
+                        disabled: false,
+                        created_at: chrono::Utc::now().timestamp(),
+                    },
+                    None,
+                )
+                .await?;
+            println!("[server] [auth] created admin account '{}'", username);
+        }
+        _ => println!(
+            "[server] [auth] no accounts yet, set ADMIN_USERNAME and ADMIN_PASSWORD (at least {} characters) to create the first admin",
+            MIN_PASSWORD_LEN
+        ),
+    }
+    Ok(())
+}
+
+pub async fn login(State(state): State<Arc<AppState>>, Json(req): Json<LoginRequest>) -> axum::response::Response {
+    let users = state.db.collection::<UserRecord>("users");
+    let user = match users.find_one(doc! { "username": &req.username, "disabled": { "$ne": true } }, None).await {
+        Ok(user) => user,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    // same answer, and the same time, for unknown accounts and wrong passwords
+    let Some(user) = user else {
+        verify_password(req.password, DUMMY_PASSWORD_HASH.to_string()).await;
+        return unauthorized("wrong username or password").into_response();
+    };
+    if !verify_password(req.password, user.password_hash.clone()).await {
+        tracing::info!("[server] [auth] failed login for {}", user.username);
+        return unauthorized("wrong username or password").into_response();
+    }
+
+    let ttl = if user.role == Role::StudentClient { CLIENT_TOKEN_TTL } else { STAFF_TOKEN_TTL };
+    let token = new_token();
+    let expires_at = chrono::Utc::now().timestamp() + ttl.as_secs() as i64;
+
+    let tokens = state.db.collection::<Document>("auth_tokens");
+    let record = doc! {
+        "token_hash": hash_token(&token),
+        "username": &user.username,
+        "created_at": DateTime::now(),
+        "expires_at": DateTime::from_millis(expires_at * 1000),
+    };
+    if let Err(e) = tokens.insert_one(record, None).await {
+        return db_error(e).into_response();
+    }
+
+    tracing::info!("[server] [auth] {} signed in", user.username);
+    Json(LoginResponse { token, role: user.role, expires_at }).into_response()
+}
+
+// a token for one student in one session, handed out for a join code
+pub async fn issue_student_token(
+    db: &mongodb::Database,
+    student_id: &str,
+    session_id: &str,
+    expires_at: i64,
+) -> mongodb::error::Result<String> {
+    let token = new_token();
+    let record = doc! {
+        "token_hash": hash_token(&token),
+        "student_id": student_id,
+        "session_id": session_id,
+        "created_at": DateTime::now(),
+        "expires_at": DateTime::from_millis(expires_at * 1000),
+    };
+    db.collection::<Document>("auth_tokens").insert_one(record, None).await?;
+    Ok(token)
+}
+
+pub async fn logout(State(state): State<Arc<AppState>>, user: AuthUser) -> impl IntoResponse {
+    let tokens = state.db.collection::<Document>("auth_tokens");
+    match tokens.delete_one(doc! { "token_hash": &user.token_hash }, None).await {
+        Ok(_) => response(StatusCode::OK, "success", format!("[server] [auth] {} signed out", user.username)),
+        Err(e) => db_error(e),
+    }
+}
+
+pub async fn me(user: AuthUser) -> impl IntoResponse {
+    Json(UserInfo { username: user.username, role: user.role, sessions: user.sessions, disabled: false })
+}
+
+// admin: creates an account
+pub async fn create_user(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<CreateUser>,
+) -> impl IntoResponse {
+    if let Err(e) = user.require(ADMINS) {
+        return e;
+    }
+    if req.username.trim().is_empty() || req.username.len() > 64 {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] [auth] invalid username".to_string());
+    }
+    if req.password.len() < MIN_PASSWORD_LEN {
+        return response(
+            StatusCode::BAD_REQUEST,
+            "error",
+            format!("[server] [auth] passwords need at least {} characters", MIN_PASSWORD_LEN),
+        );
+    }
+
+    let password_hash = match hash_password(req.password).await {
+        Ok(h) => h,
+        Err(e) => return response(StatusCode::INTERNAL_SERVER_ERROR, "error", format!("[server] [auth] {}", e)),
+    };
+    let record = UserRecord {
+        username: req.username.trim().to_string(),
+        password_hash,
+        role: req.role,
+        sessions: req.sessions,
+        disabled: false,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+
+    let users = state.db.collection::<UserRecord>("users");
+    match users.insert_one(&record, None).await {
+        Ok(_) => {
+            tracing::info!("[server] [auth] {} created {:?} account {}", user.username, record.role, record.username);
+            response(StatusCode::CREATED, "success", format!("[server] [auth] created {}", record.username))
+        }
+        Err(e) if is_duplicate(&e) => {
+            response(StatusCode::CONFLICT, "error", format!("[server] [auth] {} already exists", record.username))
+        }
+        Err(e) => db_error(e),
+    }
+}
+
+// admin: every account
+pub async fn list_users(State(state): State<Arc<AppState>>, user: AuthUser) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+
+    let users = state.db.collection::<UserRecord>("users");
+    let mut cursor = match users.find(None, None).await {
+        Ok(c) => c,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let mut list = Vec::new();
+    loop {
+        match cursor.advance().await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => return db_error(e).into_response(),
+        }
+        if let Ok(u) = cursor.deserialize_current() {
+            list.push(UserInfo::from(u));
+        }
+    }
+    list.sort_by(|a, b| a.username.cmp(&b.username));
+    Json(list).into_response()
+}
+
+// admin, or an instructor handing their own sessions to a proctor
+pub async fn assign_sessions(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(username): Path<String>,
+    Json(req): Json<AssignSessions>,
+) -> impl IntoResponse {
+    if let Err(e) = user.require(INSTRUCTORS) {
+        return e;
+    }
+
+    let users = state.db.collection::<UserRecord>("users");
+    let target = match users.find_one(doc! { "username": &username }, None).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return response(StatusCode::NOT_FOUND, "error", format!("[server] [auth] no account {}", username)),
+        Err(e) => return db_error(e),
+    };
+
+    if user.role != Role::Admin {
+        if target.role != Role::Proctor {
+            return forbidden("instructors can only assign proctors".to_string());
+        }
+        // an instructor can't take away or hand out sessions that aren't theirs
+        let changed = target
+            .sessions
+            .iter()
+            .filter(|s| !req.sessions.contains(s))
+            .chain(req.sessions.iter().filter(|s| !target.sessions.contains(s)));
+        if let Some(session_id) = changed.into_iter().find(|s| !user.can_access(s)) {
+            return forbidden(format!("not assigned to session {}", session_id));
+        }
+    }
+
+    match users.update_one(doc! { "username": &username }, doc! { "$set": { "sessions": &req.sessions } }, None).await {
+        Ok(_) => {
+            tracing::info!("[server] [auth] {} assigned {} to {:?}", user.username, username, req.sessions);
+            response(StatusCode::OK, "success", format!("[server] [auth] {} has {} sessions", username, req.sessions.len()))
+        }
+        Err(e) => db_error(e),
+    }
+}
+
+// admin: disables an account and signs it out everywhere
+pub async fn disable_user(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = user.require(ADMINS) {
+        return e;
+    }
+    if username == user.username {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] [auth] can't disable your own account".to_string());
+    }
+
+    let users = state.db.collection::<UserRecord>("users");
+    match users.update_one(doc! { "username": &username }, doc! { "$set": { "disabled": true } }, None).await {
+        Ok(r) if r.matched_count == 0 => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [auth] no account {}", username));
+        }
+        Ok(_) => {}
+        Err(e) => return db_error(e),
+    }
+
+    let tokens = state.db.collection::<Document>("auth_tokens");
+    match tokens.delete_many(doc! { "username": &username }, None).await {
+        Ok(_) => {
+            tracing::info!("[server] [auth] {} disabled {}", user.username, username);
+            response(StatusCode::OK, "success", format!("[server] [auth] disabled {}", username))
+        }
+        Err(e) => db_error(e),
+    }
+}
+
+// the account itself or an admin. other tokens of the account stop working
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(username): Path<String>,
+    Json(req): Json<ChangePassword>,
+) -> impl IntoResponse {
+    if username != user.username
+        && let Err(e) = user.require(ADMINS)
+    {
+        return e;
+    }
+    if req.password.len() < MIN_PASSWORD_LEN {
+        return response(
+            StatusCode::BAD_REQUEST,
+            "error",
+            format!("[server] [auth] passwords need at least {} characters", MIN_PASSWORD_LEN),
+        );
+    }
+
+    let password_hash = match hash_password(req.password).await {
+        Ok(h) => h,
+        Err(e) => return response(StatusCode::INTERNAL_SERVER_ERROR, "error", format!("[server] [auth] {}", e)),
+    };
+    let users = state.db.collection::<UserRecord>("users");
+    let update = doc! { "$set": { "password_hash": password_hash } };
+    match users.update_one(doc! { "username": &username }, update, None).await {
+        Ok(r) if r.matched_count == 0 => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [auth] no account {}", username));
+        }
+        Ok(_) => {}
+        Err(e) => return db_error(e),
+    }
+
+    let tokens = state.db.collection::<Document>("auth_tokens");
+    let others = doc! { "username": &username, "token_hash": { "$ne": &user.token_hash } };
+    match tokens.delete_many(others, None).await {
+        Ok(_) => response(StatusCode::OK, "success", format!("[server] [auth] password changed for {}", username)),
+        Err(e) => db_error(e),
+    }
+}
+
+// Helpers functions
+
+pub fn unauthorized(message: &str) -> AuthError {
+    response(StatusCode::UNAUTHORIZED, "error", format!("[server] [auth] {}", message))
+}
+
+pub fn forbidden(message: String) -> AuthError {
+    response(StatusCode::FORBIDDEN, "error", format!("[server] [auth] {}", message))
+}
+
+// argon2 is slow on purpose, kept off the async workers
+async fn hash_password(password: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .unwrap_or(false)
+}
+
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// "K7QM-2XPD", no 0/O or 1/I to mix up
+pub fn new_join_code() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut bytes = [0u8; JOIN_CODE_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    let chars: String = bytes.iter().map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char).collect();
+    let (first, second) = chars.split_at(JOIN_CODE_LEN / 2);
+    format!("{}-{}", first, second)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn is_duplicate(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(w)) if w.code == 11000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(role: Role, sessions: &[&str], student_id: Option<&str>) -> AuthUser {
+        AuthUser {
+            username: "someone".to_string(),
+            role,
+            sessions: sessions.iter().map(|s| s.to_string()).collect(),
+            student_id: student_id.map(str::to_string),
+            token_hash: String::new(),
+        }
+    }
+
+    fn status(result: Result<(), AuthError>) -> Option<StatusCode> {
+        result.err().map(|(code, _)| code)
+    }
+
+    #[test]
+    fn admins_pass_every_check() {
+        let admin = user(Role::Admin, &[], None);
+        assert_eq!(status(admin.require(ADMINS)), None);
+        assert_eq!(status(admin.require(CLIENTS)), None);
+        assert_eq!(status(admin.require_session(INSTRUCTORS, "any-session")), None);
+        assert!(admin.can_report("any-session", "any-student"));
+
+        let instructor = user(Role::Instructor, &["s1"], None);
+        assert_eq!(status(instructor.require(ADMINS)), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn staff_only_in_assigned_sessions() {
+        let proctor = user(Role::Proctor, &["s1"], None);
+        assert_eq!(status(proctor.require_session(STAFF, "s1")), None);
+        assert_eq!(status(proctor.require_session(STAFF, "s2")), Some(StatusCode::FORBIDDEN));
+        // assigned, but the wrong role
+        assert_eq!(status(proctor.require_session(INSTRUCTORS, "s1")), Some(StatusCode::FORBIDDEN));
+        assert!(proctor.can_access("s1"));
+        assert!(!proctor.can_access("s2"));
+    }
+
+    #[test]
+    fn join_tokens_act_for_their_own_student() {
+        let joined = user(Role::StudentClient, &["s1"], Some("st-1"));
+        assert!(joined.can_report("s1", "st-1"));
+        assert!(!joined.can_report("s1", "st-2"));
+        assert!(!joined.can_report("s2", "st-1"));
+        assert_eq!(status(joined.require(CLIENTS)), None);
+        assert_eq!(status(joined.require(STAFF)), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn client_accounts_stay_in_their_sessions() {
+        let lab = user(Role::StudentClient, &["lab-1", "lab-2"], None);
+        assert!(lab.can_report("lab-1", "st-1"));
+        assert!(lab.can_report("lab-2", "st-9"));
+        assert!(!lab.can_report("lab-3", "st-1"));
+        assert_eq!(status(lab.require_session(CLIENTS, "lab-3")), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status(lab.require_session(STAFF, "lab-1")), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[tokio::test]
+    async fn passwords_and_the_dummy_hash() {
+        let hash = hash_password("correct horse".to_string()).await.unwrap();
+        assert!(verify_password("correct horse".to_string(), hash.clone()).await);
+        assert!(!verify_password("wrong horse".to_string(), hash).await);
+
+        // parses with the same parameters as real hashes, so it costs the same to check
+        let dummy = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+        let real = hash_password("x".repeat(MIN_PASSWORD_LEN)).await.unwrap();
+        assert_eq!(dummy.params, PasswordHash::new(&real).unwrap().params);
+        assert!(!verify_password(String::new(), DUMMY_PASSWORD_HASH.to_string()).await);
+    }
+
+    #[test]
+    fn join_codes_use_the_unambiguous_alphabet() {
+        let code = new_join_code();
+        assert_eq!(code.len(), JOIN_CODE_LEN + 1);
+        assert_eq!(code.as_bytes()[JOIN_CODE_LEN / 2], b'-');
+        assert!(!code.contains(['0', 'O', '1', 'I']));
+    }
+}
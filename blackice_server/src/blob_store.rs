@@ -0,0 +1,162 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// binary evidence lives outside mongodb, the documents only keep the key
+// keys are '/' separated and built by the server, never taken from a request as is
+pub trait BlobStore: Send + Sync {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    // removes every key under the prefix, missing prefixes are fine
+    fn delete_prefix(&self, prefix: &str) -> io::Result<()>;
+}
+
+// one file per key under a root directory
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let mut path = self.root.clone();
+        for part in key.split('/') {
+            if !is_safe_segment(part) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid blob key '{}'", key)));
+            }
+            path.push(part);
+        }
+        Ok(path)
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write then rename, a crash never leaves a half written blob under the real key
+        let tmp = path.with_extension("part");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?)
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> io::Result<()> {
+        match fs::remove_dir_all(self.path(prefix)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        }
+    }
+}
+
+// EVIDENCE_MAX_BYTES (one blob) and EVIDENCE_SESSION_QUOTA_BYTES (all blobs of a session)
+#[derive(Debug, Clone, Copy)]
+pub struct EvidenceLimits {
+    pub max_blob_bytes: i64,
+    pub session_quota_bytes: i64,
+}
+
+impl EvidenceLimits {
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: i64| {
+            env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v| *v > 0).unwrap_or(default)
+        };
+        Self {
+            max_blob_bytes: var("EVIDENCE_MAX_BYTES", 25 * 1024 * 1024),
+            session_quota_bytes: var("EVIDENCE_SESSION_QUOTA_BYTES", 2 * 1024 * 1024 * 1024),
+        }
+    }
+}
+
+// BLOB_DIR, defaults to ./evidence_store next to the server
+pub fn from_env() -> io::Result<Arc<dyn BlobStore>> {
+    let root = env::var("BLOB_DIR").unwrap_or_else(|_| "evidence_store".to_string());
+    Ok(Arc::new(LocalBlobStore::new(root)?))
+}
+
+// ids and hashes only, nothing that can climb out of the root
+pub fn is_safe_segment(part: &str) -> bool {
+    !part.is_empty()
+        && part.len() <= 128
+        && part != "."
+        && part != ".."
+        && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh root under the system temp dir, removed on drop
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new() -> Self {
+            Self(env::temp_dir().join(format!("blackice-blobs-{}", mongodb::bson::oid::ObjectId::new().to_hex())))
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn put_get_delete_prefix() {
+        let root = TempRoot::new();
+        let store = LocalBlobStore::new(&root.0).unwrap();
+
+        store.put("uploads/u1/0", b"first").unwrap();
+        store.put("uploads/u1/1", b"second").unwrap();
+        store.put("uploads/u2/0", b"other").unwrap();
+        assert_eq!(store.get("uploads/u1/1").unwrap(), b"second");
+        // put replaces, and leaves no temporary file behind
+        store.put("uploads/u1/1", b"again").unwrap();
+        assert_eq!(store.get("uploads/u1/1").unwrap(), b"again");
+        assert!(!root.0.join("uploads/u1/1.part").exists());
+
+        store.delete_prefix("uploads/u1").unwrap();
+        assert_eq!(store.get("uploads/u1/0").unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.get("uploads/u2/0").unwrap(), b"other");
+        // already gone is fine
+        store.delete_prefix("uploads/u1").unwrap();
+    }
+
+    #[test]
+    fn keys_stay_under_the_root() {
+        let root = TempRoot::new();
+        let store = LocalBlobStore::new(&root.0).unwrap();
+
+        let keys = ["..", "../outside", "s1/../../outside", "s1/./x", "", "/abs", "s1//x", "s1/", "s1\\..\\x", "s1/a b"];
+        for key in keys {
+            let err = store.put(key, b"x").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", key);
+            assert_eq!(store.get(key).unwrap_err().kind(), io::ErrorKind::InvalidInput, "{:?}", key);
+            assert_eq!(store.delete_prefix(key).unwrap_err().kind(), io::ErrorKind::InvalidInput, "{:?}", key);
+        }
+        assert!(!root.0.parent().unwrap().join("outside").exists());
+    }
+
+    #[test]
+    fn safe_segments() {
+        for part in ["s1", "CS101-lab_2", "abc.png", &"a".repeat(128)] {
+            assert!(is_safe_segment(part), "{}", part);
+        }
+        for part in ["", ".", "..", "a/b", "a\\b", "a:b", "é", &"a".repeat(129)] {
+            assert!(!is_safe_segment(part), "{}", part);
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{Html, IntoResponse},
+    Json,
+};
+use mongodb::bson::{doc, DateTime, Document};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::{
+    auth::{AuthUser, STAFF},
+    db::AppState,
+    handlers::{collect, db_error},
+    models::{EvidenceRecord, SessionOverview, StudentOverview, StudentTimeline, TimelineEntry, TimelineQuery},
+};
+
+// the proctor dashboard, static files compiled into the binary and a few read-only queries
+// over exam_logs / sessions / session_students / evidence
+
+const INDEX_HTML: &str = include_str!("../assets/dashboard/index.html");
+const APP_JS: &str = include_str!("../assets/dashboard/app.js");
+const STYLE_CSS: &str = include_str!("../assets/dashboard/style.css");
+
+// clients poll the schedule every 30s and flush logs every 10s
+const ONLINE_SECS: i64 = 90;
+// sessions with activity (or an exam window) in this range are listed
+const ACTIVE_WINDOW_SECS: i64 = 24 * 60 * 60;
+const LATEST_VIOLATIONS: usize = 5;
+const MAX_TIMELINE: i64 = 5000;
+
+// risk of one distinct entry (same level, same message up to numbers), the score is capped at 100.
+// the monitors re-report a violation every scan, repeats don't add to it
+const RISK_WEIGHTS: &[(&str, i64)] = &[
+    ("VIOLATION_TAMPER", 30),
+    ("VERIFICATION_FAIL", 25),
+    ("EVENT_UNCLEAN_EXIT", 15),
+    ("VIOLATION_NET", 10),
+    ("LOCKDOWN_ROLLBACK_FAILED", 10),
+    ("VIOLATION_APP", 8),
+    ("VIOLATION_ENV", 5),
+    ("VIOLATION_OTH", 2),
+];
+const MAX_RISK: i64 = 100;
+
+pub async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+pub async fn app_js() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/javascript")], APP_JS)
+}
+
+pub async fn style_css() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/css")], STYLE_CSS)
+}
+
+// sessions with recent activity or a current exam window, most recently active first.
+// instructors and proctors only get the sessions they are assigned to
+pub async fn list_sessions(State(state): State<Arc<AppState>>, user: AuthUser) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+    let now = chrono::Utc::now().timestamp();
+    let since = now - ACTIVE_WINDOW_SECS;
+
+    let mut overview: HashMap<String, SessionOverview> = HashMap::new();
+    let mut heartbeats: HashMap<(String, String), i64> = HashMap::new();
+
+    // scheduled sessions that didn't end before the window
+    let sessions = state.db.collection::<Document>("sessions");
+    let scheduled = match collect(sessions.find(doc! { "ends_at": { "$gte": since } }, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in scheduled {
+        let Ok(session_id) = d.get_str("session_id") else { continue };
+        let entry = session_entry(&mut overview, session_id);
+        entry.starts_at = d.get_i64("starts_at").ok();
+        entry.ends_at = d.get_i64("ends_at").ok();
+    }
+
+    // students that logged something
+    let pipeline = vec![
+        doc! { "$match": { "timestamp_iso": { "$gte": DateTime::from_millis(since * 1000) } } },
+        doc! { "$group": {
+            "_id": { "session_id": "$session_id", "student_id": "$student_id" },
+            "last": { "$max": "$original_ts" },
+        } },
+    ];
+    let logs = state.db.collection::<Document>("exam_logs");
+    let active = match collect(logs.aggregate(pipeline, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in active {
+        let Ok(id) = d.get_document("_id") else { continue };
+        let (Ok(session_id), Ok(student_id)) = (id.get_str("session_id"), id.get_str("student_id")) else { continue };
+        let last = d.get_i64("last").unwrap_or(0);
+        heartbeat(&mut heartbeats, session_id, student_id, last);
+    }
+
+    // students that only polled their schedule
+    let students = state.db.collection::<Document>("session_students");
+    let seen = match collect(students.find(doc! { "last_seen": { "$gte": DateTime::from_millis(since * 1000) } }, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in seen {
+        let (Ok(session_id), Ok(student_id)) = (d.get_str("session_id"), d.get_str("student_id")) else { continue };
+        let last = d.get_datetime("last_seen").map(|t| t.timestamp_millis() / 1000).unwrap_or(0);
+        heartbeat(&mut heartbeats, session_id, student_id, last);
+    }
+
+    // students with anything that counts towards the risk score
+    let pipeline = vec![
+        doc! { "$match": {
+            "timestamp_iso": { "$gte": DateTime::from_millis(since * 1000) },
+            "level": { "$in": risk_levels() },
+        } },
+        doc! { "$group": { "_id": { "session_id": "$session_id", "student_id": "$student_id" } } },
+    ];
+    let flagged = match collect(logs.aggregate(pipeline, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let flagged: HashSet<(String, String)> = flagged
+        .iter()
+        .filter_map(|d| {
+            let id = d.get_document("_id").ok()?;
+            Some((id.get_str("session_id").ok()?.to_string(), id.get_str("student_id").ok()?.to_string()))
+        })
+        .collect();
+
+    for ((session_id, student_id), last) in &heartbeats {
+        let entry = session_entry(&mut overview, session_id);
+        entry.students += 1;
+        if now - last <= ONLINE_SECS {
+            entry.online += 1;
+        }
+        if flagged.contains(&(session_id.clone(), student_id.clone())) {
+            entry.flagged += 1;
+        }
+        entry.last_activity = entry.last_activity.max(Some(*last));
+    }
+
+    let mut list: Vec<SessionOverview> = overview.into_values().filter(|s| user.can_access(&s.session_id)).collect();
+    list.sort_by_key(|s| std::cmp::Reverse(s.last_activity.or(s.starts_at)));
+    Json(list).into_response()
+}
+
+// every student of a session with state, heartbeat and risk, riskiest first
+pub async fn list_students(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e.into_response();
+    }
+    let now = chrono::Utc::now().timestamp();
+    let mut students: HashMap<String, StudentOverview> = HashMap::new();
+
+    let activity = match session_activity(&state.db, &session_id).await {
+        Ok(activity) => activity,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for (student_id, a) in activity {
+        let entry = student_entry(&mut students, &student_id);
+        entry.last_heartbeat = a.last_log;
+        entry.state = a.state.clone();
+        entry.risk_score = a.risk_score();
+        entry.violations = a.violations();
+        entry.latest_violations = a.violations.into_iter().take(LATEST_VIOLATIONS).collect();
+    }
+
+    let session_students = state.db.collection::<Document>("session_students");
+    let seen = match collect(session_students.find(doc! { "session_id": &session_id }, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in seen {
+        let Ok(student_id) = d.get_str("student_id") else { continue };
+        let entry = student_entry(&mut students, student_id);
+        let last_seen = d.get_datetime("last_seen").ok().map(|t| t.timestamp_millis() / 1000);
+        entry.last_heartbeat = entry.last_heartbeat.max(last_seen);
+        entry.extra_secs = d.get_i64("extra_secs").unwrap_or(0);
+    }
+
+    // roster students show up before their client ever connects
+    let roster = state.db.collection::<Document>("roster");
+    let listed = match collect(roster.find(doc! { "session_id": &session_id }, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in listed {
+        let Ok(student_id) = d.get_str("student_id") else { continue };
+        student_entry(&mut students, student_id).name = d.get_str("name").ok().map(str::to_string);
+    }
+
+    let mut list: Vec<StudentOverview> = students
+        .into_values()
+        .map(|mut s| {
+            s.online = s.last_heartbeat.is_some_and(|t| now - t <= ONLINE_SECS);
+            s
+        })
+        .collect();
+    list.sort_by(|a, b| b.risk_score.cmp(&a.risk_score).then_with(|| a.student_id.cmp(&b.student_id)));
+    Json(list).into_response()
+}
+
+// everything one student's client reported, with the uploaded evidence
+pub async fn student_timeline(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((session_id, student_id)): Path<(String, String)>,
+    Query(query): Query<TimelineQuery>,
+) -> axum::response::Response {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e.into_response();
+    }
+    let limit = query.limit.clamp(1, MAX_TIMELINE);
+    let logs = state.db.collection::<Document>("exam_logs");
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "original_ts": -1 })
+        .limit(limit + 1)
+        .build();
+
+    let filter = doc! { "session_id": &session_id, "student_id": &student_id };
+    let docs = match collect(logs.find(filter.clone(), options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let truncated = docs.len() as i64 > limit;
+    let mut entries: Vec<TimelineEntry> = docs
+        .iter()
+        .take(limit as usize)
+        .map(|d| TimelineEntry {
+            at: d.get_i64("original_ts").unwrap_or(0),
+            level: d.get_str("level").unwrap_or_default().to_string(),
+            message: d.get_str("message").unwrap_or_default().to_string(),
+            violation_id: d.get_str("violation_id").ok().map(str::to_string),
+        })
+        .collect();
+    entries.reverse();
+
+    let evidence = state.db.collection::<Document>("evidence");
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "uploaded_at": 1 }).build();
+    let evidence: Vec<EvidenceRecord> = match collect(evidence.find(filter, options).await).await {
+        Ok(docs) => docs.into_iter().filter_map(|d| mongodb::bson::from_document(d).ok()).collect(),
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    Json(StudentTimeline { session_id, student_id, entries, evidence, truncated }).into_response()
+}
+
+// what the exam_logs say about one student of a session, shared with the reports
+pub(crate) struct StudentActivity {
+    pub first_log: Option<i64>,
+    pub last_log: Option<i64>,
+    // the `to` of the latest EXAM_TRANSITION
+    pub state: Option<String>,
+    // distinct (level, message up to numbers) entries that count towards the risk score
+    pub risks: HashSet<(String, String)>,
+    // distinct violations, newest first
+    pub violations: Vec<TimelineEntry>,
+}
+
+impl StudentActivity {
+    pub fn risk_score(&self) -> i64 {
+        self.risks.iter().map(|(level, _)| weight(level)).sum::<i64>().min(MAX_RISK)
+    }
+
+    pub fn violations(&self) -> i64 {
+        self.count(|level| level.starts_with("VIOLATION_"))
+    }
+
+    // distinct risk entries whose level matches
+    pub fn count(&self, matches: impl Fn(&str) -> bool) -> i64 {
+        self.risks.iter().filter(|(level, _)| matches(level)).count() as i64
+    }
+
+    // repeats that only differ in numbers are one violation, shown as the latest of them
+    fn add_risk(&mut self, entry: TimelineEntry) {
+        let key = (entry.level.clone(), signature(&entry.message));
+        if !entry.level.starts_with("VIOLATION_") {
+            self.risks.insert(key);
+            return;
+        }
+        let seen = self.violations.iter_mut().find(|e| e.level == key.0 && signature(&e.message) == key.1);
+        match seen {
+            Some(seen) if entry.at > seen.at => *seen = entry,
+            Some(_) => {}
+            None => {
+                self.risks.insert(key);
+                self.violations.push(entry);
+            }
+        }
+    }
+}
+
+pub(crate) async fn session_activity(
+    db: &mongodb::Database,
+    session_id: &str,
+) -> mongodb::error::Result<HashMap<String, StudentActivity>> {
+    let logs = db.collection::<Document>("exam_logs");
+    let mut students: HashMap<String, StudentActivity> = HashMap::new();
+
+    let pipeline = vec![
+        doc! { "$match": { "session_id": session_id } },
+        doc! { "$group": {
+            "_id": "$student_id",
+            "first": { "$min": "$original_ts" },
+            "last": { "$max": "$original_ts" },
+        } },
+    ];
+    for d in collect(logs.aggregate(pipeline, None).await).await? {
+        let Ok(student_id) = d.get_str("_id") else { continue };
+        let a = activity_entry(&mut students, student_id);
+        a.first_log = d.get_i64("first").ok();
+        a.last_log = d.get_i64("last").ok();
+    }
+
+    // the state machine reports every transition, the latest one is where the client is
+    let pipeline = vec![
+        doc! { "$match": { "session_id": session_id, "level": "EXAM_TRANSITION" } },
+        doc! { "$sort": { "original_ts": -1 } },
+        doc! { "$group": { "_id": "$student_id", "message": { "$first": "$message" } } },
+    ];
+    for d in collect(logs.aggregate(pipeline, None).await).await? {
+        let Ok(student_id) = d.get_str("_id") else { continue };
+        let to = d
+            .get_str("message")
+            .ok()
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .and_then(|t| t.get("to").and_then(|s| s.as_str()).map(str::to_string));
+        activity_entry(&mut students, student_id).state = to;
+    }
+
+    // distinct risk entries per student, with when they were last reported
+    let pipeline = vec![
+        doc! { "$match": { "session_id": session_id, "level": { "$in": risk_levels() } } },
+        doc! { "$sort": { "original_ts": -1 } },
+        doc! { "$group": {
+            "_id": { "student_id": "$student_id", "level": "$level", "message": "$message" },
+            "last": { "$first": "$original_ts" },
+            "violation_id": { "$first": "$violation_id" },
+        } },
+    ];
+    for d in collect(logs.aggregate(pipeline, None).await).await? {
+        let Ok(id) = d.get_document("_id") else { continue };
+        let (Ok(student_id), Ok(level), Ok(message)) = (id.get_str("student_id"), id.get_str("level"), id.get_str("message"))
+        else {
+            continue;
+        };
+
+        activity_entry(&mut students, student_id).add_risk(TimelineEntry {
+            at: d.get_i64("last").unwrap_or(0),
+            level: level.to_string(),
+            message: message.to_string(),
+            violation_id: d.get_str("violation_id").ok().map(str::to_string),
+        });
+    }
+
+    for a in students.values_mut() {
+        a.violations.sort_by_key(|e| std::cmp::Reverse(e.at));
+    }
+    Ok(students)
+}
+
+// Helpers functions
+
+fn session_entry<'a>(overview: &'a mut HashMap<String, SessionOverview>, session_id: &str) -> &'a mut SessionOverview {
+    overview.entry(session_id.to_string()).or_insert_with(|| SessionOverview {
+        session_id: session_id.to_string(),
+        starts_at: None,
+        ends_at: None,
+        students: 0,
+        online: 0,
+        flagged: 0,
+        last_activity: None,
+    })
+}
+
+fn student_entry<'a>(students: &'a mut HashMap<String, StudentOverview>, student_id: &str) -> &'a mut StudentOverview {
+    students.entry(student_id.to_string()).or_insert_with(|| StudentOverview {
+        student_id: student_id.to_string(),
+        name: None,
+        state: None,
+        last_heartbeat: None,
+        online: false,
+        risk_score: 0,
+        violations: 0,
+        latest_violations: Vec::new(),
+        extra_secs: 0,
+    })
+}
+
+fn activity_entry<'a>(students: &'a mut HashMap<String, StudentActivity>, student_id: &str) -> &'a mut StudentActivity {
+    students.entry(student_id.to_string()).or_insert_with(|| StudentActivity {
+        first_log: None,
+        last_log: None,
+        state: None,
+        risks: HashSet::new(),
+        violations: Vec::new(),
+    })
+}
+
+fn heartbeat(heartbeats: &mut HashMap<(String, String), i64>, session_id: &str, student_id: &str, at: i64) {
+    let last = heartbeats.entry((session_id.to_string(), student_id.to_string())).or_insert(at);
+    *last = (*last).max(at);
+}
+
+pub(crate) fn risk_levels() -> Vec<&'static str> {
+    RISK_WEIGHTS.iter().map(|(level, _)| *level).collect()
+}
+
+fn weight(level: &str) -> i64 {
+    RISK_WEIGHTS.iter().find(|(l, _)| *l == level).map(|(_, w)| *w).unwrap_or(0)
+}
+
+// "BANNED WINDOW: 'discord' (PID: 4312)" and "... (PID: 9921)" are the same violation
+pub(crate) fn signature(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut in_number = false;
+    for c in message.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                out.push('#');
+            }
+            in_number = true;
+        } else {
+            in_number = false;
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: &str, message: &str, at: i64, violation_id: &str) -> TimelineEntry {
+        TimelineEntry {
+            at,
+            level: level.to_string(),
+            message: message.to_string(),
+            violation_id: Some(violation_id.to_string()),
+        }
+    }
+
+    #[test]
+    fn signature_masks_numbers() {
+        assert_eq!(signature("BANNED WINDOW: 'discord' (PID: 4312)"), "BANNED WINDOW: 'discord' (PID: #)");
+        assert_eq!(signature("3 displays, 1920x1080"), "# displays, #x#");
+        assert_eq!(signature("no numbers"), "no numbers");
+    }
+
+    #[test]
+    fn repeats_keep_the_latest_variant() {
+        let mut students = HashMap::new();
+        let a = activity_entry(&mut students, "st-1");
+        // the aggregation returns the groups in no particular order
+        a.add_risk(entry("VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 10)", 200, "v-2"));
+        a.add_risk(entry("VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 20)", 300, "v-3"));
+        a.add_risk(entry("VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 30)", 100, "v-1"));
+        a.add_risk(entry("VIOLATION_NET", "DNS BLOCKED: example.com", 150, "v-4"));
+        a.add_risk(entry("EVENT_UNCLEAN_EXIT", "previous session crashed after 12s", 50, "v-5"));
+        a.add_risk(entry("EVENT_UNCLEAN_EXIT", "previous session crashed after 40s", 60, "v-6"));
+
+        assert_eq!(a.violations.len(), 2);
+        let app = &a.violations[0];
+        assert_eq!((app.at, app.violation_id.as_deref()), (300, Some("v-3")));
+        assert_eq!(app.message, "BANNED WINDOW: 'discord' (PID: 20)");
+        assert_eq!(a.violations(), 2);
+        assert_eq!(a.count(|level| level == "EVENT_UNCLEAN_EXIT"), 1);
+        assert_eq!(a.risks.len(), 3);
+    }
+}
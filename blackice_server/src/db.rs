@@ -1,12 +1,19 @@
 use mongodb::{Client, options::{ClientOptions, ResolverConfig}};
 use std::env;
 use std::error::Error;
+use std::sync::Arc;
+
+use crate::{alerts, auth, incidents, roster, webhooks};
+use crate::blob_store::{self, BlobStore, EvidenceLimits};
 
 pub type DbResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: mongodb::Database,
+    // screenshots, reports and log exports, referenced from the `evidence` collection
+    pub blobs: Arc<dyn BlobStore>,
+    pub evidence_limits: EvidenceLimits,
 }
 
 pub async fn init_db() -> DbResult<AppState> {
@@ -18,7 +25,6 @@ pub async fn init_db() -> DbResult<AppState> {
 
     use mongodb::options::CreateCollectionOptions;
     use mongodb::options::TimeseriesOptions;
-    use mongodb::bson::doc;
 
     let ts_options = TimeseriesOptions::builder()
     .time_field("timestamp_iso".to_string()) // VVIP must match the field in doc! above
@@ -34,5 +40,14 @@ pub async fn init_db() -> DbResult<AppState> {
     let _ = db.create_collection("exam_logs", create_opts).await;
 
     println!("[server] connected to mongodb");
-    Ok(AppState { db })
+
+    auth::init(&db).await?;
+    roster::init(&db).await?;
+    alerts::init(&db).await?;
+    webhooks::init(&db).await?;
+    incidents::init(&db).await?;
+
+    let blobs = blob_store::from_env()?;
+    let evidence_limits = EvidenceLimits::from_env();
+    Ok(AppState { db, blobs, evidence_limits })
 }
\ No newline at end of file
@@ -1,12 +1,36 @@
 use axum::{
-    extract::State,
+    body::Bytes,
+    extract::{Path, Query, State},
     Json,
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
 };
-use mongodb::bson::{doc, DateTime};
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::sync::Arc;
-use crate::{models::{LogEntry, GenericResponse}, db::AppState};
+use std::time::Duration;
+use crate::{
+    alerts,
+    incidents,
+    auth::{self, AuthUser, CLIENTS, INSTRUCTORS, STAFF},
+    blob_store::{is_safe_segment, BlobStore, EvidenceLimits},
+    models::{
+        LogEntry, GenericResponse, SessionSchedule, StudentSchedule, ScheduleQuery, ExtendRequest,
+        VerifyLogRequest, VerifyLogResponse, StartUpload, UploadStarted, UploadCompleted, EvidenceRecord,
+    },
+    db::AppState,
+};
+
+// what can be uploaded as evidence
+const EVIDENCE_KINDS: &[&str] = &["screenshot", "webcam", "readiness_report", "log_export"];
+// stays below axum's default 2 MB body limit
+const CHUNK_SIZE: i64 = 1024 * 1024;
+// no schedule poll for this long counts as offline
+pub const OFFLINE_GAP_SECS: i64 = 2 * 60;
+// uploads never completed are dropped with their chunks after this
+pub const UPLOAD_EXPIRY: Duration = Duration::from_secs(60 * 60);
 
 pub async fn health_checker() -> impl IntoResponse {
     const MESSAGE: &str = "BlackIce Server is running";
@@ -19,8 +43,28 @@ pub async fn health_checker() -> impl IntoResponse {
 
 pub async fn ingest_logs(
     State(state): State<Arc<AppState>>,
-    Json(logs): Json<Vec<LogEntry>>,
+    user: AuthUser,
+    Json(mut logs): Json<Vec<LogEntry>>,
 ) -> impl IntoResponse {
+    if let Err(e) = user.require(CLIENTS) {
+        return e;
+    }
+
+    // a joined client reports before sign-in as "unknown", those entries are the same student's
+    if let (Some(student_id), Some(session_id)) = (&user.student_id, user.sessions.first()) {
+        for log in logs.iter_mut().filter(|l| l.student_id == "unknown" && l.session_id == "unknown") {
+            log.student_id = student_id.clone();
+            log.session_id = session_id.clone();
+        }
+    } else if let [session_id] = user.sessions.as_slice() {
+        // a lab machine account of a single session, the student is still unknown
+        for log in logs.iter_mut().filter(|l| l.session_id == "unknown") {
+            log.session_id = session_id.clone();
+        }
+    }
+    if let Some(log) = logs.iter().find(|l| !user.can_report(&l.session_id, &l.student_id)) {
+        return auth::forbidden(format!("can't report for {} in {}", log.student_id, log.session_id));
+    }
     if logs.is_empty() {
         return (StatusCode::OK, Json(GenericResponse {
             status: "success".to_string(),
@@ -31,8 +75,18 @@ pub async fn ingest_logs(
     let collection = state.db.collection::<mongodb::bson::Document>("exam_logs");
     let log_count = logs.len();
 
+    // the pre-exam readiness report is also kept as the session's baseline
+    for log in logs.iter().filter(|l| l.level == "READINESS_BASELINE") {
+        store_baseline(&state, log).await;
+    }
+
+    // alert rules and incidents run on the stored batch, without holding up the client
+    let alert_logs = logs.clone();
+
     // mapping the incoming request structs to bson docs as this
     // dynamically adds 'timestamp_iso' field to our time-series database 
+    // server clock, the rate rules count by it instead of the client's timestamp
+    let ingested_at = chrono::Utc::now().timestamp();
     let docs: Vec<mongodb::bson::Document> = logs.into_iter().map(|log| {
         // convert UNIX timestamp (seconds) to bson DateTime (milliseconds)
         let bson_datetime = DateTime::from_millis(log.timestamp * 1000);
@@ -43,7 +97,11 @@ pub async fn ingest_logs(
             "level": log.level,
             "message": log.message,
             "timestamp_iso": bson_datetime,
-            "original_ts": log.timestamp    // here we are keeping the original for reference
+            "original_ts": log.timestamp,   // here we are keeping the original for reference
+            "ingested_at": ingested_at,
+            "evidence_seq": log.evidence_seq,
+            "evidence_hash": log.evidence_hash,
+            "violation_id": log.violation_id,
         }
     }).collect();
 
@@ -51,6 +109,8 @@ pub async fn ingest_logs(
     match collection.insert_many(docs, None).await {
         Ok(_) => {
             tracing::info!("[server] ingested {} logs", log_count);
+            tokio::spawn(incidents::record_logs(state.clone(), alert_logs.clone()));
+            tokio::spawn(alerts::evaluate_logs(state.clone(), alert_logs));
             (StatusCode::CREATED, Json(GenericResponse {
                 status: "success".to_string(),
                 message: format!("[server] 'ingested {} logs", log_count),
@@ -65,3 +125,772 @@ pub async fn ingest_logs(
         }
     }
 }
+
+// one baseline per (student, session), a re-run pre-check replaces it
+async fn store_baseline(state: &AppState, log: &LogEntry) {
+    let report = match serde_json::from_str::<serde_json::Value>(&log.message)
+        .map_err(|e| e.to_string())
+        .and_then(|v| mongodb::bson::to_bson(&v).map_err(|e| e.to_string()))
+    {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("[server] invalid readiness report from {}: {}", log.student_id, e);
+            return;
+        }
+    };
+
+    let baselines = state.db.collection::<mongodb::bson::Document>("session_baselines");
+    let filter = doc! { "student_id": &log.student_id, "session_id": &log.session_id };
+    let update = doc! {
+        "$set": {
+            "report": report,
+            "checked_at": DateTime::from_millis(log.timestamp * 1000),
+        }
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    if let Err(e) = baselines.update_one(filter, update, options).await {
+        tracing::error!("[server] failed to store baseline: {:?}", e);
+    }
+}
+
+// proctor: creates or replaces the exam window of a session
+pub async fn put_schedule(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+    Json(schedule): Json<SessionSchedule>,
+) -> impl IntoResponse {
+    if let Err(e) = user.require_session(INSTRUCTORS, &session_id) {
+        return e;
+    }
+    if schedule.ends_at <= schedule.starts_at
+        || schedule.join_grace_secs < 0
+        || schedule.submit_grace_secs < 0
+        || schedule.warn_before_end_secs < 0
+        || schedule.webcam.as_ref().is_some_and(|w| w.interval_secs < 10 || w.upload_every < 1)
+    {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] invalid schedule".to_string());
+    }
+
+    let webcam = match mongodb::bson::to_bson(&schedule.webcam) {
+        Ok(w) => w,
+        Err(e) => return response(StatusCode::BAD_REQUEST, "error", format!("[server] invalid webcam policy: {}", e)),
+    };
+
+    let sessions = state.db.collection::<mongodb::bson::Document>("sessions");
+    let update = doc! {
+        "$set": {
+            "starts_at": schedule.starts_at,
+            "ends_at": schedule.ends_at,
+            "join_grace_secs": schedule.join_grace_secs,
+            "submit_grace_secs": schedule.submit_grace_secs,
+            "warn_before_end_secs": schedule.warn_before_end_secs,
+            "webcam": webcam,
+        }
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    match sessions.update_one(doc! { "session_id": &session_id }, update, options).await {
+        Ok(_) => {
+            tracing::info!("[server] schedule set for session {}", session_id);
+            response(StatusCode::OK, "success", format!("[server] schedule set for {}", session_id))
+        }
+        Err(e) => db_error(e),
+    }
+}
+
+// client: the schedule for one student, polling it also counts as the student's heartbeat
+pub async fn get_schedule(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<ScheduleQuery>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(CLIENTS) {
+        return e.into_response();
+    }
+    if !user.can_report(&session_id, &query.student_id) {
+        return auth::forbidden(format!("can't poll for {} in {}", query.student_id, session_id)).into_response();
+    }
+    let sessions = state.db.collection::<mongodb::bson::Document>("sessions");
+    let schedule = match sessions.find_one(doc! { "session_id": &session_id }, None).await {
+        // sessions created by a roster import have no window until the instructor sets one
+        Ok(Some(d)) if d.contains_key("starts_at") => match mongodb::bson::from_document::<SessionSchedule>(d) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::error!("[server] corrupt schedule for {}: {}", session_id, e);
+                return response(StatusCode::INTERNAL_SERVER_ERROR, "error", "[server] corrupt schedule".to_string())
+                    .into_response();
+            }
+        },
+        Ok(_) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] no schedule for {}", session_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    // one document per (session, student): time extension, first / last contact and how long
+    // the client was out of contact
+    let now = DateTime::now();
+    let students = state.db.collection::<mongodb::bson::Document>("session_students");
+    let filter = doc! { "session_id": &session_id, "student_id": &query.student_id };
+    let update = doc! { "$set": { "last_seen": now }, "$setOnInsert": { "first_seen": now } };
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::Before)
+        .build();
+    let before = match students.find_one_and_update(filter.clone(), update, options).await {
+        Ok(d) => d,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    // polls come every 30s, a longer gap is time offline
+    if let Some(last) = before.as_ref().and_then(|d| d.get_datetime("last_seen").ok()) {
+        let gap = (now.timestamp_millis() - last.timestamp_millis()) / 1000;
+        if gap > OFFLINE_GAP_SECS {
+            let update = doc! {
+                "$inc": { "offline_secs": gap },
+                "$push": { "offline_periods": { "from": *last, "to": now } },
+            };
+            if let Err(e) = students.update_one(filter, update, None).await {
+                return db_error(e).into_response();
+            }
+        }
+    }
+    let extra_secs = before.and_then(|d| d.get_i64("extra_secs").ok()).unwrap_or(0);
+
+    Json(StudentSchedule {
+        session_id,
+        student_id: query.student_id,
+        schedule,
+        extra_secs,
+        server_time: chrono::Utc::now().timestamp(),
+    })
+    .into_response()
+}
+
+// proctor: gives a single student more time, applied on the client's next poll
+pub async fn extend_time(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+    Json(req): Json<ExtendRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e;
+    }
+    if req.extra_secs <= 0 {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] extra_secs must be positive".to_string());
+    }
+
+    let sessions = state.db.collection::<mongodb::bson::Document>("sessions");
+    match sessions.find_one(doc! { "session_id": &session_id }, None).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] no schedule for {}", session_id));
+        }
+        Err(e) => return db_error(e),
+    }
+
+    let students = state.db.collection::<mongodb::bson::Document>("session_students");
+    let filter = doc! { "session_id": &session_id, "student_id": &req.student_id };
+    let update = doc! { "$inc": { "extra_secs": req.extra_secs } };
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    match students.update_one(filter, update, options).await {
+        Ok(_) => {
+            tracing::info!("[server] extended {} in {} by {}s", req.student_id, session_id, req.extra_secs);
+            response(
+                StatusCode::OK,
+                "success",
+                format!("[server] extended {} by {}s", req.student_id, req.extra_secs),
+            )
+        }
+        Err(e) => db_error(e),
+    }
+}
+
+// compares an exported client evidence log with what was ingested for the student
+pub async fn verify_log(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+    Json(req): Json<VerifyLogRequest>,
+) -> axum::response::Response {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e.into_response();
+    }
+    let collection = state.db.collection::<mongodb::bson::Document>("exam_logs");
+    let filter = doc! {
+        "session_id": &session_id,
+        "student_id": &req.student_id,
+        "evidence_seq": { "$ne": null },
+    };
+
+    let mut cursor = match collection.find(filter, None).await {
+        Ok(c) => c,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    // a batch re-sent after a timeout can be stored twice, same seq same hash
+    let mut received: HashMap<i64, String> = HashMap::new();
+    loop {
+        match cursor.advance().await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => return db_error(e).into_response(),
+        }
+        let Ok(d) = cursor.deserialize_current() else { continue };
+        if let (Ok(seq), Ok(hash)) = (d.get_i64("evidence_seq"), d.get_str("evidence_hash")) {
+            received.insert(seq, hash.to_string());
+        }
+    }
+
+    let mut result = VerifyLogResponse {
+        consistent: false,
+        matched: 0,
+        missing_on_server: Vec::new(),
+        mismatched: Vec::new(),
+        unknown_to_client: Vec::new(),
+    };
+    for entry in &req.entries {
+        match received.remove(&entry.seq) {
+            Some(hash) if hash == entry.hash => result.matched += 1,
+            Some(_) => result.mismatched.push(entry.seq),
+            None => result.missing_on_server.push(entry.seq),
+        }
+    }
+    result.unknown_to_client = received.into_keys().collect();
+    result.unknown_to_client.sort_unstable();
+    result.consistent = result.missing_on_server.is_empty()
+        && result.mismatched.is_empty()
+        && result.unknown_to_client.is_empty();
+
+    tracing::info!(
+        "[server] verified evidence log of {} in {}: {} matched",
+        req.student_id, session_id, result.matched
+    );
+    Json(result).into_response()
+}
+
+// client: announces a blob, answers with where to send the chunks
+pub async fn start_upload(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<StartUpload>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(CLIENTS) {
+        return e.into_response();
+    }
+    if !user.can_report(&req.session_id, &req.student_id) {
+        return auth::forbidden(format!("can't upload for {} in {}", req.student_id, req.session_id)).into_response();
+    }
+    let limits = state.evidence_limits;
+    if let Some((code, message)) = invalid_upload(&req, limits) {
+        return response(code, "error", format!("[server] [evidence] {}", message)).into_response();
+    }
+
+    // stored blobs plus the ones still uploading count against the quota
+    let used = match session_usage(&state, &req.session_id).await {
+        Ok(used) => used,
+        Err(e) => return db_error(e).into_response(),
+    };
+    if !fits_quota(used, req.size, limits) {
+        tracing::warn!("[server] [evidence] quota of session {} exhausted ({} bytes used)", req.session_id, used);
+        return response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "error",
+            format!("[server] [evidence] session quota exhausted ({} of {} bytes used)", used, limits.session_quota_bytes),
+        )
+        .into_response();
+    }
+
+    let metadata = match mongodb::bson::to_bson(&req.metadata) {
+        Ok(m) => m,
+        Err(e) => {
+            return response(StatusCode::BAD_REQUEST, "error", format!("[server] [evidence] invalid metadata: {}", e))
+                .into_response();
+        }
+    };
+
+    let upload_id = ObjectId::new().to_hex();
+    let chunks = chunk_count(req.size);
+    let upload = doc! {
+        "upload_id": &upload_id,
+        "session_id": &req.session_id,
+        "student_id": &req.student_id,
+        "violation_id": &req.violation_id,
+        "kind": &req.kind,
+        "content_type": &req.content_type,
+        "size": req.size,
+        "sha256": req.sha256.to_ascii_lowercase(),
+        "metadata": metadata,
+        "chunks": chunks,
+        "received": [],
+        "created_at": DateTime::now(),
+    };
+
+    let uploads = state.db.collection::<mongodb::bson::Document>("evidence_uploads");
+    if let Err(e) = uploads.insert_one(upload, None).await {
+        return db_error(e).into_response();
+    }
+
+    (StatusCode::CREATED, Json(UploadStarted { upload_id, chunk_size: CHUNK_SIZE, chunks })).into_response()
+}
+
+// client: one chunk of an upload, chunks can come in any order and be re-sent
+pub async fn put_chunk(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((upload_id, index)): Path<(String, i64)>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Err(e) = user.require(CLIENTS) {
+        return e;
+    }
+    let uploads = state.db.collection::<mongodb::bson::Document>("evidence_uploads");
+    let upload = match uploads.find_one(doc! { "upload_id": &upload_id }, None).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return response(StatusCode::NOT_FOUND, "error", format!("[server] [evidence] no upload {}", upload_id)),
+        Err(e) => return db_error(e),
+    };
+    if !owns_upload(&user, &upload) {
+        return auth::forbidden(format!("upload {} belongs to another student", upload_id));
+    }
+
+    let chunks = upload.get_i64("chunks").unwrap_or(0);
+    let Some(expected) = chunk_len(upload.get_i64("size").unwrap_or(0), index) else {
+        return response(StatusCode::BAD_REQUEST, "error", format!("[server] [evidence] chunk {} out of range", index));
+    };
+    if body.len() as i64 != expected {
+        return response(
+            StatusCode::BAD_REQUEST,
+            "error",
+            format!("[server] [evidence] chunk {} has {} bytes, expected {}", index, body.len(), expected),
+        );
+    }
+
+    let blobs = state.blobs.clone();
+    let key = format!("uploads/{}/{}", upload_id, index);
+    if let Err(e) = blocking(move || blobs.put(&key, &body)).await {
+        return storage_error(e);
+    }
+
+    let update = doc! { "$addToSet": { "received": index } };
+    match uploads.update_one(doc! { "upload_id": &upload_id }, update, None).await {
+        Ok(_) => response(StatusCode::OK, "success", format!("[server] [evidence] chunk {} of {} stored", index + 1, chunks)),
+        Err(e) => db_error(e),
+    }
+}
+
+// client: all chunks are there, the blob is assembled, checked against its hash and stored
+pub async fn complete_upload(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(upload_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(CLIENTS) {
+        return e.into_response();
+    }
+    let uploads = state.db.collection::<mongodb::bson::Document>("evidence_uploads");
+    let upload = match uploads.find_one(doc! { "upload_id": &upload_id }, None).await {
+        Ok(Some(u)) => u,
+        Ok(None) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [evidence] no upload {}", upload_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+    if !owns_upload(&user, &upload) {
+        return auth::forbidden(format!("upload {} belongs to another student", upload_id)).into_response();
+    }
+
+    let chunks = upload.get_i64("chunks").unwrap_or(0);
+    let received = upload.get_array("received").map(|r| r.len() as i64).unwrap_or(0);
+    if received != chunks {
+        return response(
+            StatusCode::CONFLICT,
+            "error",
+            format!("[server] [evidence] {} of {} chunks received", received, chunks),
+        )
+        .into_response();
+    }
+
+    let session_id = upload.get_str("session_id").unwrap_or_default().to_string();
+    let expected_hash = upload.get_str("sha256").unwrap_or_default().to_string();
+    let size = upload.get_i64("size").unwrap_or(0);
+
+    let blobs = state.blobs.clone();
+    let (id, session, expected) = (upload_id.clone(), session_id.clone(), expected_hash.clone());
+    let assembled = blocking(move || assemble_blob(blobs.as_ref(), &id, &session, chunks, size, &expected)).await;
+
+    let (hash, blob_key) = match assembled {
+        Ok(result) => result,
+        Err(e) => return storage_error(e).into_response(),
+    };
+    if let Err(e) = uploads.delete_one(doc! { "upload_id": &upload_id }, None).await {
+        return db_error(e).into_response();
+    }
+    if hash != expected_hash {
+        tracing::warn!("[server] [evidence] upload {} does not match its hash, dropped", upload_id);
+        return response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "error",
+            "[server] [evidence] content does not match the announced sha256".to_string(),
+        )
+        .into_response();
+    }
+
+    let record = EvidenceRecord {
+        evidence_id: ObjectId::new().to_hex(),
+        session_id,
+        student_id: upload.get_str("student_id").unwrap_or_default().to_string(),
+        violation_id: upload.get_str("violation_id").ok().map(str::to_string),
+        kind: upload.get_str("kind").unwrap_or_default().to_string(),
+        content_type: upload.get_str("content_type").unwrap_or_default().to_string(),
+        size,
+        sha256: hash,
+        blob_key,
+        metadata: upload
+            .get("metadata")
+            .and_then(|m| mongodb::bson::from_bson(m.clone()).ok())
+            .unwrap_or(serde_json::Value::Null),
+        uploaded_at: chrono::Utc::now().timestamp(),
+    };
+
+    let evidence = state.db.collection::<EvidenceRecord>("evidence");
+    if let Err(e) = evidence.insert_one(&record, None).await {
+        return db_error(e).into_response();
+    }
+
+    tracing::info!(
+        "[server] [evidence] stored {} ({} bytes) for {} in {}",
+        record.kind, record.size, record.student_id, record.session_id
+    );
+    (
+        StatusCode::CREATED,
+        Json(UploadCompleted { evidence_id: record.evidence_id, sha256: record.sha256, size: record.size }),
+    )
+        .into_response()
+}
+
+// proctor: the blob itself
+pub async fn get_evidence(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(evidence_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+    let evidence = state.db.collection::<EvidenceRecord>("evidence");
+    let record = match evidence.find_one(doc! { "evidence_id": &evidence_id }, None).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [evidence] no evidence {}", evidence_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+    if let Err(e) = user.require_session(STAFF, &record.session_id) {
+        return e.into_response();
+    }
+
+    let blobs = state.blobs.clone();
+    let key = record.blob_key.clone();
+    match blocking(move || blobs.get(&key)).await {
+        Ok(data) => ([(header::CONTENT_TYPE, record.content_type)], data).into_response(),
+        Err(e) => storage_error(e).into_response(),
+    }
+}
+
+// proctor: evidence linked to a violation (violation_id of the VIOLATION_* log entry)
+pub async fn violation_evidence(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(violation_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+    let evidence = state.db.collection::<EvidenceRecord>("evidence");
+    let mut cursor = match evidence.find(doc! { "violation_id": &violation_id }, None).await {
+        Ok(c) => c,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let mut records = Vec::new();
+    loop {
+        match cursor.advance().await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => return db_error(e).into_response(),
+        }
+        match cursor.deserialize_current() {
+            Ok(r) if user.can_access(&r.session_id) => records.push(r),
+            Ok(_) => {}
+            Err(e) => tracing::error!("[server] [evidence] corrupt record: {}", e),
+        }
+    }
+    Json(records).into_response()
+}
+
+// background: drops uploads that were never completed, with their chunks
+pub async fn expire_uploads(state: Arc<AppState>) {
+    let uploads = state.db.collection::<mongodb::bson::Document>("evidence_uploads");
+    let cutoff = DateTime::from_millis(DateTime::now().timestamp_millis() - UPLOAD_EXPIRY.as_millis() as i64);
+    let filter = doc! { "created_at": { "$lt": cutoff } };
+
+    let mut cursor = match uploads.find(filter.clone(), None).await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!("[server] [evidence] failed to list stale uploads: {:?}", e);
+            return;
+        }
+    };
+
+    let mut expired = Vec::new();
+    while let Ok(true) = cursor.advance().await {
+        if let Ok(id) = cursor.current().get_str("upload_id") {
+            expired.push(id.to_string());
+        }
+    }
+    if expired.is_empty() {
+        return;
+    }
+
+    for id in &expired {
+        let blobs = state.blobs.clone();
+        let prefix = format!("uploads/{}", id);
+        if let Err(e) = blocking(move || blobs.delete_prefix(&prefix)).await {
+            tracing::error!("[server] [evidence] failed to remove chunks of {}: {}", id, e);
+        }
+    }
+    let ids: Vec<&String> = expired.iter().collect();
+    match uploads.delete_many(doc! { "upload_id": { "$in": ids } }, None).await {
+        Ok(r) => tracing::info!("[server] [evidence] expired {} unfinished uploads", r.deleted_count),
+        Err(e) => tracing::error!("[server] [evidence] failed to expire uploads: {:?}", e),
+    }
+}
+
+// Helpers functions
+
+// what start_upload refuses before looking at the quota
+fn invalid_upload(req: &StartUpload, limits: EvidenceLimits) -> Option<(StatusCode, String)> {
+    let bad = |message: String| Some((StatusCode::BAD_REQUEST, message));
+    if !is_safe_segment(&req.session_id) || req.student_id.is_empty() {
+        bad("invalid session or student id".to_string())
+    } else if !EVIDENCE_KINDS.contains(&req.kind.as_str()) {
+        bad(format!("unknown evidence kind '{}'", req.kind))
+    } else if req.sha256.len() != 64 || !req.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        bad("sha256 must be 64 hex characters".to_string())
+    } else if req.size <= 0 {
+        bad("empty upload".to_string())
+    } else if req.size > limits.max_blob_bytes {
+        let message = format!("{} bytes is over the {} byte limit", req.size, limits.max_blob_bytes);
+        Some((StatusCode::PAYLOAD_TOO_LARGE, message))
+    } else {
+        None
+    }
+}
+
+fn fits_quota(used: i64, size: i64, limits: EvidenceLimits) -> bool {
+    used + size <= limits.session_quota_bytes
+}
+
+fn chunk_count(size: i64) -> i64 {
+    (size + CHUNK_SIZE - 1) / CHUNK_SIZE
+}
+
+// bytes chunk `index` of a blob of `size` has, none past the last one
+fn chunk_len(size: i64, index: i64) -> Option<i64> {
+    let chunks = chunk_count(size);
+    if index < 0 || index >= chunks {
+        None
+    } else if index == chunks - 1 {
+        Some(size - index * CHUNK_SIZE)
+    } else {
+        Some(CHUNK_SIZE)
+    }
+}
+
+// assembled from the chunks in order, stored content addressed: the same file twice is one blob.
+// a blob that doesn't match the announced hash isn't stored, the chunks go either way
+fn assemble_blob(
+    blobs: &dyn BlobStore,
+    upload_id: &str,
+    session_id: &str,
+    chunks: i64,
+    size: i64,
+    expected_hash: &str,
+) -> std::io::Result<(String, String)> {
+    let mut data = Vec::with_capacity(size as usize);
+    for index in 0..chunks {
+        data.extend(blobs.get(&format!("uploads/{}/{}", upload_id, index))?);
+    }
+    let hash = hex::encode(Sha256::digest(&data));
+    let key = format!("{}/{}", session_id, hash);
+    if hash == expected_hash {
+        blobs.put(&key, &data)?;
+    }
+    blobs.delete_prefix(&format!("uploads/{}", upload_id))?;
+    Ok((hash, key))
+}
+
+// upload ids are only ObjectIds, the caller has to be able to report for the upload's student
+fn owns_upload(user: &AuthUser, upload: &mongodb::bson::Document) -> bool {
+    user.can_report(upload.get_str("session_id").unwrap_or_default(), upload.get_str("student_id").unwrap_or_default())
+}
+
+// bytes of a session's stored blobs and of its unfinished uploads
+async fn session_usage(state: &AppState, session_id: &str) -> mongodb::error::Result<i64> {
+    let pipeline = vec![
+        doc! { "$match": { "session_id": session_id } },
+        doc! { "$group": { "_id": null, "total": { "$sum": "$size" } } },
+    ];
+
+    let mut used = 0;
+    for name in ["evidence", "evidence_uploads"] {
+        let collection = state.db.collection::<mongodb::bson::Document>(name);
+        let mut cursor = collection.aggregate(pipeline.clone(), None).await?;
+        if cursor.advance().await? {
+            used += cursor.current().get_i64("total").unwrap_or(0);
+        }
+    }
+    Ok(used)
+}
+
+// blob store calls are blocking file IO
+async fn blocking<T, F>(f: F) -> std::io::Result<T>
+where
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(std::io::Error::other)?
+}
+
+fn storage_error(e: std::io::Error) -> (StatusCode, Json<GenericResponse>) {
+    tracing::error!("[server] [blob] {:?}", e);
+    response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "error",
+        format!("[server] [blob] storage operation failed: {}", e),
+    )
+}
+
+pub(crate) fn response(code: StatusCode, status: &str, message: String) -> (StatusCode, Json<GenericResponse>) {
+    (code, Json(GenericResponse { status: status.to_string(), message }))
+}
+
+pub(crate) async fn collect(
+    cursor: mongodb::error::Result<mongodb::Cursor<mongodb::bson::Document>>,
+) -> mongodb::error::Result<Vec<mongodb::bson::Document>> {
+    let mut cursor = cursor?;
+    let mut docs = Vec::new();
+    while cursor.advance().await? {
+        docs.push(cursor.deserialize_current()?);
+    }
+    Ok(docs)
+}
+
+pub(crate) fn db_error(e: mongodb::error::Error) -> (StatusCode, Json<GenericResponse>) {
+    tracing::error!("[server] [db] {:?}", e);
+    response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "error",
+        format!("[server] [db] database operation failed: {}", e),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob_store::LocalBlobStore;
+
+    const LIMITS: EvidenceLimits =
+        EvidenceLimits { max_blob_bytes: 3 * CHUNK_SIZE, session_quota_bytes: 10 * CHUNK_SIZE };
+
+    fn upload(size: i64) -> StartUpload {
+        StartUpload {
+            session_id: "s1".to_string(),
+            student_id: "st-1".to_string(),
+            violation_id: None,
+            kind: "screenshot".to_string(),
+            content_type: "image/png".to_string(),
+            size,
+            sha256: "ab".repeat(32),
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn chunk_sizes() {
+        assert_eq!(chunk_count(1), 1);
+        assert_eq!(chunk_count(CHUNK_SIZE), 1);
+        assert_eq!(chunk_count(CHUNK_SIZE + 1), 2);
+
+        let size = 2 * CHUNK_SIZE + 10;
+        assert_eq!(chunk_len(size, 0), Some(CHUNK_SIZE));
+        assert_eq!(chunk_len(size, 1), Some(CHUNK_SIZE));
+        assert_eq!(chunk_len(size, 2), Some(10));
+        assert_eq!(chunk_len(size, 3), None);
+        assert_eq!(chunk_len(size, -1), None);
+        // an exact multiple has a full last chunk
+        assert_eq!(chunk_len(2 * CHUNK_SIZE, 1), Some(CHUNK_SIZE));
+        assert_eq!(chunk_len(7, 0), Some(7));
+    }
+
+    #[test]
+    fn upload_checks() {
+        assert_eq!(invalid_upload(&upload(LIMITS.max_blob_bytes), LIMITS), None);
+
+        let code = |req: StartUpload| invalid_upload(&req, LIMITS).map(|(code, _)| code);
+        assert_eq!(code(upload(LIMITS.max_blob_bytes + 1)), Some(StatusCode::PAYLOAD_TOO_LARGE));
+        assert_eq!(code(upload(0)), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(code(StartUpload { session_id: "../s1".to_string(), ..upload(1) }), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(code(StartUpload { student_id: String::new(), ..upload(1) }), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(code(StartUpload { kind: "audio".to_string(), ..upload(1) }), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(code(StartUpload { sha256: "ab".repeat(31), ..upload(1) }), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(code(StartUpload { sha256: "zz".repeat(32), ..upload(1) }), Some(StatusCode::BAD_REQUEST));
+
+        assert!(fits_quota(9 * CHUNK_SIZE, CHUNK_SIZE, LIMITS));
+        assert!(!fits_quota(9 * CHUNK_SIZE, CHUNK_SIZE + 1, LIMITS));
+    }
+
+    #[test]
+    fn assembled_blob_is_checked_against_its_hash() {
+        let root = std::env::temp_dir().join(format!("blackice-upload-{}", ObjectId::new().to_hex()));
+        let store = LocalBlobStore::new(&root).unwrap();
+        let data: Vec<u8> = (0..CHUNK_SIZE + 5).map(|i| i as u8).collect();
+        let put_chunks = |upload_id: &str| {
+            for (index, chunk) in data.chunks(CHUNK_SIZE as usize).enumerate() {
+                store.put(&format!("uploads/{}/{}", upload_id, index), chunk).unwrap();
+            }
+        };
+        let size = data.len() as i64;
+        let hash = hex::encode(Sha256::digest(&data));
+
+        put_chunks("u1");
+        let (got, key) = assemble_blob(&store, "u1", "s1", chunk_count(size), size, &hash).unwrap();
+        assert_eq!((got.as_str(), key.as_str()), (hash.as_str(), format!("s1/{}", hash).as_str()));
+        assert_eq!(store.get(&key).unwrap(), data);
+        assert!(store.get("uploads/u1/0").is_err());
+
+        // announced with another hash: nothing is stored, the chunks are dropped
+        put_chunks("u2");
+        let other = "00".repeat(32);
+        store.delete_prefix("s1").unwrap();
+        let (got, key) = assemble_blob(&store, "u2", "s1", chunk_count(size), size, &other).unwrap();
+        assert_eq!(got, hash);
+        assert!(store.get(&key).is_err());
+        assert!(store.get("uploads/u2/1").is_err());
+
+        // a missing chunk is an error, not a short blob
+        store.put("uploads/u3/0", &data[..CHUNK_SIZE as usize]).unwrap();
+        assert!(assemble_blob(&store, "u3", "s1", 2, size, &hash).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
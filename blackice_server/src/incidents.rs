@@ -0,0 +1,493 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument, UpdateOptions};
+use mongodb::IndexModel;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use crate::{
+    auth::{self, AuthUser, STAFF},
+    dashboard::{risk_levels, signature},
+    db::{AppState, DbResult},
+    handlers::{collect, db_error, response},
+    models::{
+        GenericResponse, Incident, IncidentAudit, IncidentAuditEntry, IncidentDetail, IncidentNoteRequest, IncidentQuery, IncidentStatus,
+        IncidentStatusRequest, LogEntry,
+    },
+};
+
+// incident review. the risk entries of a student are grouped like the risk score counts them, by
+// level and message up to numbers, and each group is one incident that proctors review: notes and
+// a decision (false positive, confirmed, escalated). only pending incidents take new entries, once
+// reviewed the next matching entry opens a new one. every change is written to incident_audit
+// first, which is only ever inserted into, and the incident lists the entries that were applied
+
+const MAX_NOTE_LEN: usize = 2000;
+
+// one ingested batch folded into an incident
+struct Group {
+    session_id: String,
+    student_id: String,
+    level: String,
+    signature: String,
+    entries: i64,
+    first_at: i64,
+    last_at: i64,
+    violation_ids: BTreeSet<String>,
+}
+
+pub async fn init(db: &mongodb::Database) -> DbResult<()> {
+    let incidents = db.collection::<Document>("incidents");
+    let unique = || IndexOptions::builder().unique(true).build();
+    // only pending incidents have an open_key, so there is one per student and violation
+    let open = IndexOptions::builder().unique(true).sparse(true).build();
+    incidents
+        .create_index(IndexModel::builder().keys(doc! { "open_key": 1 }).options(open).build(), None)
+        .await?;
+    incidents
+        .create_index(IndexModel::builder().keys(doc! { "incident_id": 1 }).options(unique()).build(), None)
+        .await?;
+    incidents
+        .create_index(IndexModel::builder().keys(doc! { "session_id": 1, "student_id": 1 }).build(), None)
+        .await?;
+    db.collection::<Document>("incident_audit")
+        .create_index(IndexModel::builder().keys(doc! { "incident_id": 1, "at": 1 }).build(), None)
+        .await?;
+    Ok(())
+}
+
+// staff: incidents of a session, latest first
+pub async fn list_incidents(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<IncidentQuery>,
+) -> axum::response::Response {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e.into_response();
+    }
+    let mut filter = doc! { "session_id": &session_id };
+    if let Some(student_id) = &query.student_id {
+        filter.insert("student_id", student_id);
+    }
+    if let Some(status) = query.status {
+        filter.insert("status", status_str(status));
+    }
+    let incidents = state.db.collection::<Document>("incidents");
+    let options = FindOptions::builder().sort(doc! { "last_at": -1 }).build();
+    match collect(incidents.find(filter, options).await).await {
+        Ok(docs) => Json(
+            docs.into_iter()
+                .filter_map(|d| mongodb::bson::from_document::<Incident>(d).ok())
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// staff: one incident with its audit trail
+pub async fn get_incident(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(incident_id): Path<String>,
+) -> axum::response::Response {
+    let incident = match load_incident(&state, &user, &incident_id).await {
+        Ok(i) => i,
+        Err(e) => return e.into_response(),
+    };
+    let audit = state.db.collection::<Document>("incident_audit");
+    let options = FindOptions::builder().sort(doc! { "at": 1 }).build();
+    match collect(audit.find(doc! { "incident_id": &incident_id }, options).await).await {
+        Ok(docs) => {
+            let audit = docs
+                .into_iter()
+                .filter_map(|d| mongodb::bson::from_document::<IncidentAudit>(d).ok())
+                .map(|audit| IncidentAuditEntry { applied: incident.audit_ids.contains(&audit.audit_id), audit })
+                .collect();
+            Json(IncidentDetail { incident, audit }).into_response()
+        }
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// staff: adds a note
+pub async fn add_note(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(incident_id): Path<String>,
+    Json(req): Json<IncidentNoteRequest>,
+) -> axum::response::Response {
+    let text = match note_text(Some(req.text)) {
+        Ok(Some(text)) => text,
+        Ok(None) => {
+            return response(StatusCode::BAD_REQUEST, "error", "[server] [incidents] empty note".to_string())
+                .into_response();
+        }
+        Err(e) => return e.into_response(),
+    };
+    let incident = match load_incident(&state, &user, &incident_id).await {
+        Ok(i) => i,
+        Err(e) => return e.into_response(),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let update = doc! { "$push": { "notes": { "author": &user.username, "at": now, "text": &text } } };
+    let entry = IncidentAudit {
+        audit_id: ObjectId::new().to_hex(),
+        incident_id: incident_id.clone(),
+        session_id: incident.session_id,
+        actor: user.username.clone(),
+        at: now,
+        action: "note".to_string(),
+        from: None,
+        to: None,
+        note: Some(text),
+    };
+    apply(&state, doc! { "incident_id": &incident_id }, update, entry).await
+}
+
+// staff: records the review decision, or puts the incident back to pending
+pub async fn set_status(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(incident_id): Path<String>,
+    Json(req): Json<IncidentStatusRequest>,
+) -> axum::response::Response {
+    let note = match note_text(req.note) {
+        Ok(note) => note,
+        Err(e) => return e.into_response(),
+    };
+    let incident = match load_incident(&state, &user, &incident_id).await {
+        Ok(i) => i,
+        Err(e) => return e.into_response(),
+    };
+    if incident.status == req.status {
+        return response(
+            StatusCode::CONFLICT,
+            "error",
+            format!("[server] [incidents] incident {} is already {}", incident_id, status_str(req.status)),
+        )
+        .into_response();
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let update = status_update(&incident, req.status, &user.username, now, note.as_deref());
+    let entry = IncidentAudit {
+        audit_id: ObjectId::new().to_hex(),
+        incident_id: incident_id.clone(),
+        session_id: incident.session_id,
+        actor: user.username.clone(),
+        at: now,
+        action: "status".to_string(),
+        from: Some(incident.status),
+        to: Some(req.status),
+        note,
+    };
+    // a decision made by someone else in between wins, the caller sees the conflict
+    let filter = doc! { "incident_id": &incident_id, "status": status_str(incident.status) };
+    apply(&state, filter, update, entry).await
+}
+
+// ingestion: folds the risk entries of a stored batch into their incidents
+pub async fn record_logs(state: Arc<AppState>, logs: Vec<LogEntry>) {
+    let groups = group_logs(&logs);
+    let incidents = state.db.collection::<Document>("incidents");
+    let options = UpdateOptions::builder().upsert(true).build();
+    for (key, group) in groups {
+        let violation_ids: Vec<&String> = group.violation_ids.iter().collect();
+        let update = doc! {
+            "$inc": { "entries": group.entries },
+            "$min": { "first_at": group.first_at },
+            "$max": { "last_at": group.last_at },
+            "$addToSet": { "violation_ids": { "$each": violation_ids } },
+            "$setOnInsert": {
+                "incident_id": ObjectId::new().to_hex(),
+                "session_id": &group.session_id,
+                "student_id": &group.student_id,
+                "level": &group.level,
+                "signature": &group.signature,
+                "status": status_str(IncidentStatus::Pending),
+            },
+        };
+        // two batches of one student racing both try to insert, the loser updates
+        let filter = doc! { "open_key": &key };
+        let mut result = incidents.update_one(filter.clone(), update.clone(), options.clone()).await;
+        if matches!(&result, Err(e) if auth::is_duplicate(e)) {
+            result = incidents.update_one(filter, update, options.clone()).await;
+        }
+        if let Err(e) = result {
+            tracing::error!("[server] [incidents] failed to record incident of {}: {:?}", group.student_id, e);
+        }
+    }
+}
+
+// reports: every incident of a session
+pub(crate) async fn session_incidents(db: &mongodb::Database, session_id: &str) -> mongodb::error::Result<Vec<Incident>> {
+    let incidents = db.collection::<Document>("incidents");
+    let options = FindOptions::builder().sort(doc! { "first_at": 1 }).build();
+    let docs = collect(incidents.find(doc! { "session_id": session_id }, options).await).await?;
+    Ok(docs.into_iter().filter_map(|d| mongodb::bson::from_document::<Incident>(d).ok()).collect())
+}
+
+// Helpers functions
+
+async fn load_incident(state: &AppState, user: &AuthUser, incident_id: &str) -> Result<Incident, (StatusCode, Json<GenericResponse>)> {
+    let incidents = state.db.collection::<Document>("incidents");
+    let incident = match incidents.find_one(doc! { "incident_id": incident_id }, None).await {
+        Ok(Some(d)) => mongodb::bson::from_document::<Incident>(d).map_err(|e| {
+            tracing::error!("[server] [incidents] corrupt incident {}: {}", incident_id, e);
+            response(StatusCode::INTERNAL_SERVER_ERROR, "error", "[server] [incidents] corrupt incident".to_string())
+        })?,
+        Ok(None) => {
+            return Err(response(
+                StatusCode::NOT_FOUND,
+                "error",
+                format!("[server] [incidents] no incident {}", incident_id),
+            ));
+        }
+        Err(e) => return Err(db_error(e)),
+    };
+    user.require_session(STAFF, &incident.session_id)?;
+    Ok(incident)
+}
+
+// writes the audit entry, then the change with the entry's id. no change without its entry, and an
+// entry whose change didn't go through isn't listed on the incident
+async fn apply(state: &AppState, filter: Document, mut update: Document, entry: IncidentAudit) -> axum::response::Response {
+    let audit = state.db.collection::<IncidentAudit>("incident_audit");
+    if let Err(e) = audit.insert_one(&entry, None).await {
+        tracing::error!("[server] [incidents] failed to audit {} of {}: {:?}", entry.action, entry.incident_id, e);
+        return db_error(e).into_response();
+    }
+
+    match update.get_document_mut("$push") {
+        Ok(push) => {
+            push.insert("audit_ids", &entry.audit_id);
+        }
+        Err(_) => {
+            update.insert("$push", doc! { "audit_ids": &entry.audit_id });
+        }
+    }
+    let incidents = state.db.collection::<Document>("incidents");
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let incident = match incidents.find_one_and_update(filter, update, options).await {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            return response(
+                StatusCode::CONFLICT,
+                "error",
+                format!("[server] [incidents] incident {} changed in the meantime", entry.incident_id),
+            )
+            .into_response();
+        }
+        // reopening while newer entries already opened another incident
+        Err(e) if auth::is_duplicate(&e) => {
+            return response(
+                StatusCode::CONFLICT,
+                "error",
+                format!("[server] [incidents] a newer incident like {} is pending", entry.incident_id),
+            )
+            .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+    tracing::info!("[server] [incidents] {} added a {} to {}", entry.actor, entry.action, entry.incident_id);
+
+    match mongodb::bson::from_document::<Incident>(incident) {
+        Ok(incident) => Json(incident).into_response(),
+        Err(e) => {
+            tracing::error!("[server] [incidents] corrupt incident {}: {}", entry.incident_id, e);
+            response(StatusCode::INTERNAL_SERVER_ERROR, "error", "[server] [incidents] corrupt incident".to_string())
+                .into_response()
+        }
+    }
+}
+
+// the risk entries of a batch by open_key, the incident each of them goes to
+fn group_logs(logs: &[LogEntry]) -> HashMap<String, Group> {
+    let levels = risk_levels();
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    for log in logs.iter().filter(|l| levels.contains(&l.level.as_str())) {
+        let signature = signature(&log.message);
+        let key = open_key(&log.session_id, &log.student_id, &log.level, &signature);
+        let group = groups.entry(key).or_insert_with(|| Group {
+            session_id: log.session_id.clone(),
+            student_id: log.student_id.clone(),
+            level: log.level.clone(),
+            signature,
+            entries: 0,
+            first_at: log.timestamp,
+            last_at: log.timestamp,
+            violation_ids: BTreeSet::new(),
+        });
+        group.entries += 1;
+        group.first_at = group.first_at.min(log.timestamp);
+        group.last_at = group.last_at.max(log.timestamp);
+        group.violation_ids.extend(log.violation_id.clone());
+    }
+    groups
+}
+
+fn status_update(
+    incident: &Incident,
+    status: IncidentStatus,
+    reviewer: &str,
+    now: i64,
+    note: Option<&str>,
+) -> Document {
+    let mut update = doc! { "$set": {
+        "status": status_str(status),
+        "reviewed_by": reviewer,
+        "reviewed_at": now,
+    } };
+    if let Some(text) = note {
+        update.insert("$push", doc! { "notes": { "author": reviewer, "at": now, "text": text } });
+    }
+    // a reviewed incident takes no more entries, a reopened one does again
+    if status == IncidentStatus::Pending {
+        let key = open_key(&incident.session_id, &incident.student_id, &incident.level, &incident.signature);
+        update.get_document_mut("$set").expect("set above").insert("open_key", key);
+    } else {
+        update.insert("$unset", doc! { "open_key": "" });
+    }
+    update
+}
+
+fn open_key(session_id: &str, student_id: &str, level: &str, signature: &str) -> String {
+    format!("{}|{}|{}|{}", session_id, student_id, level, signature)
+}
+
+// trimmed, none when empty
+fn note_text(text: Option<String>) -> Result<Option<String>, (StatusCode, Json<GenericResponse>)> {
+    let Some(text) = text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()) else { return Ok(None) };
+    if text.chars().count() > MAX_NOTE_LEN {
+        return Err(response(
+            StatusCode::BAD_REQUEST,
+            "error",
+            format!("[server] [incidents] notes are at most {} characters", MAX_NOTE_LEN),
+        ));
+    }
+    Ok(Some(text))
+}
+
+pub(crate) fn status_str(status: IncidentStatus) -> &'static str {
+    match status {
+        IncidentStatus::Pending => "pending",
+        IncidentStatus::FalsePositive => "false_positive",
+        IncidentStatus::Confirmed => "confirmed",
+        IncidentStatus::Escalated => "escalated",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(student_id: &str, level: &str, message: &str, timestamp: i64, violation_id: Option<&str>) -> LogEntry {
+        LogEntry {
+            student_id: student_id.to_string(),
+            session_id: "s-1".to_string(),
+            level: level.to_string(),
+            message: message.to_string(),
+            timestamp,
+            evidence_seq: None,
+            evidence_hash: None,
+            violation_id: violation_id.map(str::to_string),
+        }
+    }
+
+    fn incident(status: IncidentStatus) -> Incident {
+        Incident {
+            incident_id: "i-1".to_string(),
+            session_id: "s-1".to_string(),
+            student_id: "st-1".to_string(),
+            level: "VIOLATION_APP".to_string(),
+            signature: "BANNED WINDOW: 'discord' (PID: #)".to_string(),
+            violation_ids: Vec::new(),
+            entries: 1,
+            first_at: 0,
+            last_at: 0,
+            status,
+            notes: Vec::new(),
+            reviewed_by: None,
+            reviewed_at: None,
+            audit_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn batch_is_grouped_by_signature() {
+        let logs = vec![
+            log("st-1", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 10)", 300, Some("v-2")),
+            log("st-1", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 20)", 100, Some("v-1")),
+            log("st-1", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 30)", 200, None),
+            log("st-1", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 40)", 250, Some("v-1")),
+            log("st-2", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: 10)", 50, Some("v-3")),
+            log("st-1", "VIOLATION_NET", "BANNED WINDOW: 'discord' (PID: 10)", 60, None),
+            log("st-1", "INFO", "heartbeat 12", 70, None),
+            log("st-1", "EXAM_TRANSITION", "{\"to\":\"Locked\"}", 80, None),
+        ];
+        let groups = group_logs(&logs);
+        assert_eq!(groups.len(), 3);
+
+        let key = open_key("s-1", "st-1", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: #)");
+        let group = &groups[&key];
+        assert_eq!((group.entries, group.first_at, group.last_at), (4, 100, 300));
+        assert_eq!(group.violation_ids.iter().collect::<Vec<_>>(), ["v-1", "v-2"]);
+        assert_eq!(group.signature, "BANNED WINDOW: 'discord' (PID: #)");
+
+        let other = &groups[&open_key("s-1", "st-2", "VIOLATION_APP", "BANNED WINDOW: 'discord' (PID: #)")];
+        assert_eq!((other.student_id.as_str(), other.entries), ("st-2", 1));
+        assert!(groups.contains_key(&open_key("s-1", "st-1", "VIOLATION_NET", "BANNED WINDOW: 'discord' (PID: #)")));
+    }
+
+    #[test]
+    fn review_unsets_the_open_key_and_reopening_sets_it() {
+        let reviewed =
+            status_update(&incident(IncidentStatus::Pending), IncidentStatus::Confirmed, "proctor", 5, None);
+        assert_eq!(reviewed.get_document("$set").unwrap().get_str("status"), Ok("confirmed"));
+        assert_eq!(reviewed.get_document("$set").unwrap().get_str("reviewed_by"), Ok("proctor"));
+        assert!(reviewed.get_document("$set").unwrap().get("open_key").is_none());
+        assert!(reviewed.get_document("$unset").unwrap().contains_key("open_key"));
+        assert!(!reviewed.contains_key("$push"));
+
+        let reopened =
+            status_update(&incident(IncidentStatus::Confirmed), IncidentStatus::Pending, "proctor", 9, Some("again"));
+        let set = reopened.get_document("$set").unwrap();
+        assert_eq!(set.get_str("status"), Ok("pending"));
+        assert_eq!(set.get_str("open_key"), Ok("s-1|st-1|VIOLATION_APP|BANNED WINDOW: 'discord' (PID: #)"));
+        assert!(!reopened.contains_key("$unset"));
+        let note = reopened.get_document("$push").unwrap().get_document("notes").unwrap();
+        assert_eq!(note.get_str("author"), Ok("proctor"));
+        assert_eq!(note.get_i64("at"), Ok(9));
+        assert_eq!(note.get_str("text"), Ok("again"));
+    }
+
+    #[test]
+    fn notes_are_trimmed_and_bounded() {
+        assert_eq!(note_text(None).ok(), Some(None));
+        assert_eq!(note_text(Some("   \n".to_string())).ok(), Some(None));
+        assert_eq!(note_text(Some("  seen it \n".to_string())).ok(), Some(Some("seen it".to_string())));
+        // characters, not bytes
+        assert!(note_text(Some("é".repeat(MAX_NOTE_LEN))).is_ok_and(|n| n.is_some()));
+        let code = note_text(Some("x".repeat(MAX_NOTE_LEN + 1))).err().map(|(code, _)| code);
+        assert_eq!(code, Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn status_names_match_the_stored_ones() {
+        for status in [
+            IncidentStatus::Pending,
+            IncidentStatus::FalsePositive,
+            IncidentStatus::Confirmed,
+            IncidentStatus::Escalated,
+        ] {
+            assert_eq!(mongodb::bson::to_bson(&status).unwrap().as_str(), Some(status_str(status)));
+        }
+    }
+}
@@ -1,8 +1,16 @@
+mod alerts;
+mod auth;
+mod blob_store;
+mod dashboard;
 mod db;
 mod handlers;
+mod incidents;
 mod models;
+mod report;
+mod roster;
+mod webhooks;
 
-use axum::{routing::{get, post}, Router};
+use axum::{routing::{delete, get, post, put}, Router};
 use std::sync::Arc;
 use dotenv::dotenv;
 use std::net::SocketAddr;
@@ -26,10 +34,79 @@ async fn main() {
         }
     };
 
+    // unfinished evidence uploads are dropped after a while
+    let cleanup_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(10 * 60));
+        loop {
+            interval.tick().await;
+            handlers::expire_uploads(cleanup_state.clone()).await;
+        }
+    });
+
+    // offline clients only show up by not polling
+    let heartbeat_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            alerts::check_heartbeats(heartbeat_state.clone()).await;
+        }
+    });
+
+    // queued webhook deliveries, retried with backoff
+    let webhook_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            webhooks::deliver_due(webhook_state.clone()).await;
+        }
+    });
+
     // routes
     let app = Router::new()
         .route("/health", get(handlers::health_checker))
+        .route("/api/auth/login", post(auth::login))
+        .route("/api/auth/logout", post(auth::logout))
+        .route("/api/auth/me", get(auth::me))
+        .route("/api/users", get(auth::list_users).post(auth::create_user))
+        .route("/api/users/:username", delete(auth::disable_user))
+        .route("/api/users/:username/sessions", put(auth::assign_sessions))
+        .route("/api/users/:username/password", put(auth::change_password))
+        .route("/api/join", post(roster::join))
+        .route("/api/exams/:exam_id/roster", get(roster::list_roster).post(roster::import_roster))
+        .route("/api/exams/:exam_id/report.csv", get(report::exam_report_csv))
+        .route("/api/sessions/:session_id/report.json", get(report::session_report_json))
         .route("/api/logs", post(handlers::ingest_logs))
+        .route("/api/sessions/:session_id/schedule", get(handlers::get_schedule).put(handlers::put_schedule))
+        .route("/api/sessions/:session_id/extend", post(handlers::extend_time))
+        .route("/api/sessions/:session_id/verify_log", post(handlers::verify_log))
+        .route("/api/evidence", post(handlers::start_upload))
+        .route("/api/evidence/uploads/:upload_id/:index", put(handlers::put_chunk))
+        .route("/api/evidence/uploads/:upload_id/complete", post(handlers::complete_upload))
+        .route("/api/evidence/:evidence_id", get(handlers::get_evidence))
+        .route("/api/violations/:violation_id/evidence", get(handlers::violation_evidence))
+        .route("/api/sessions/:session_id/incidents", get(incidents::list_incidents))
+        .route("/api/incidents/:incident_id", get(incidents::get_incident))
+        .route("/api/incidents/:incident_id/notes", post(incidents::add_note))
+        .route("/api/incidents/:incident_id/status", put(incidents::set_status))
+        .route("/api/alert_rules", get(alerts::list_rules).post(alerts::create_rule))
+        .route("/api/alert_rules/:rule_id", delete(alerts::delete_rule))
+        .route("/api/alerts", get(alerts::list_alerts))
+        .route("/api/alerts/:alert_id/acknowledge", post(alerts::acknowledge_alert))
+        .route("/api/alerts/:alert_id/resolve", post(alerts::resolve_alert))
+        .route("/api/webhooks", get(webhooks::list_webhooks).post(webhooks::create_webhook))
+        .route("/api/webhooks/:webhook_id", delete(webhooks::delete_webhook))
+        .route("/api/webhooks/:webhook_id/test", post(webhooks::test_webhook))
+        .route("/api/webhook_deliveries", get(webhooks::list_deliveries))
+        .route("/api/webhook_deliveries/:delivery_id/retry", post(webhooks::retry_delivery))
+        .route("/dashboard", get(dashboard::index))
+        .route("/dashboard/app.js", get(dashboard::app_js))
+        .route("/dashboard/style.css", get(dashboard::style_css))
+        .route("/api/dashboard/sessions", get(dashboard::list_sessions))
+        .route("/api/dashboard/sessions/:session_id/students", get(dashboard::list_students))
+        .route("/api/dashboard/sessions/:session_id/students/:student_id/timeline", get(dashboard::student_timeline))
         // allow client to talk to server (CORS)
         .layer(CorsLayer::permissive()) 
         .with_state(state);
@@ -1,16 +1,718 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub student_id: String,
     pub session_id: String,
     pub level: String,
     pub message: String,
     pub timestamp: i64,
+    // position in the client's local evidence log (older clients don't send it)
+    #[serde(default)]
+    pub evidence_seq: Option<i64>,
+    #[serde(default)]
+    pub evidence_hash: Option<String>,
+    // set on violations, uploaded evidence refers to it
+    #[serde(default)]
+    pub violation_id: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct GenericResponse {
     pub status: String,
     pub message: String,
-}
\ No newline at end of file
+}
+
+// exam window of a session, set by the proctor. unix seconds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionSchedule {
+    pub starts_at: i64,
+    pub ends_at: i64,
+    // how long after the start a student can still enter the exam
+    #[serde(default = "default_join_grace")]
+    pub join_grace_secs: i64,
+    // time to finish the submission after the end, the network is released after it either way
+    #[serde(default = "default_submit_grace")]
+    pub submit_grace_secs: i64,
+    #[serde(default = "default_warn_before_end")]
+    pub warn_before_end_secs: i64,
+    // periodic webcam snapshots on the clients, none = camera not used
+    #[serde(default)]
+    pub webcam: Option<WebcamPolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebcamPolicy {
+    pub interval_secs: i64,
+    // every n-th snapshot is uploaded, the ones failing the presence checks always are
+    #[serde(default = "default_upload_every")]
+    pub upload_every: i64,
+}
+
+fn default_upload_every() -> i64 { 10 }
+
+fn default_join_grace() -> i64 { 15 * 60 }
+fn default_submit_grace() -> i64 { 2 * 60 }
+fn default_warn_before_end() -> i64 { 5 * 60 }
+
+// what a client gets, the session window plus this student's extension
+#[derive(Serialize)]
+pub struct StudentSchedule {
+    pub session_id: String,
+    pub student_id: String,
+    #[serde(flatten)]
+    pub schedule: SessionSchedule,
+    pub extra_secs: i64,
+    // lets the client correct for its own clock
+    pub server_time: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleQuery {
+    pub student_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ExtendRequest {
+    pub student_id: String,
+    pub extra_secs: i64,
+}
+
+#[derive(Deserialize)]
+pub struct EvidenceRef {
+    pub seq: i64,
+    pub hash: String,
+}
+
+// the reported entries of an exported client evidence log
+#[derive(Deserialize)]
+pub struct VerifyLogRequest {
+    pub student_id: String,
+    pub entries: Vec<EvidenceRef>,
+}
+
+#[derive(Serialize)]
+pub struct VerifyLogResponse {
+    // every entry matches and nothing is missing on either side
+    pub consistent: bool,
+    pub matched: usize,
+    // in the client log, never received (network down, server rejected the batch)
+    pub missing_on_server: Vec<i64>,
+    // received with a different hash, one side was altered
+    pub mismatched: Vec<i64>,
+    // received, but not in the client log (log truncated or from another run)
+    pub unknown_to_client: Vec<i64>,
+}
+
+// announced before the bytes of a blob are sent
+#[derive(Deserialize)]
+pub struct StartUpload {
+    pub session_id: String,
+    pub student_id: String,
+    // the violation this is evidence for, none for pre-check reports and log exports
+    #[serde(default)]
+    pub violation_id: Option<String>,
+    pub kind: String,
+    pub content_type: String,
+    pub size: i64,
+    // hex SHA-256 of the whole blob, checked when the upload completes
+    pub sha256: String,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct UploadStarted {
+    pub upload_id: String,
+    pub chunk_size: i64,
+    pub chunks: i64,
+}
+
+#[derive(Serialize)]
+pub struct UploadCompleted {
+    pub evidence_id: String,
+    pub sha256: String,
+    pub size: i64,
+}
+
+// a stored blob, the bytes are in the blob store under blob_key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvidenceRecord {
+    pub evidence_id: String,
+    pub session_id: String,
+    pub student_id: String,
+    pub violation_id: Option<String>,
+    pub kind: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub blob_key: String,
+    pub metadata: serde_json::Value,
+    pub uploaded_at: i64,
+}
+
+// dashboard: one exam session
+#[derive(Serialize)]
+pub struct SessionOverview {
+    pub session_id: String,
+    // none for sessions without a schedule
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub students: usize,
+    // heartbeat within the online window
+    pub online: usize,
+    // students with a risk score above zero
+    pub flagged: usize,
+    pub last_activity: Option<i64>,
+}
+
+// dashboard: one student of a session
+#[derive(Serialize)]
+pub struct StudentOverview {
+    pub student_id: String,
+    // from the roster, none for students that signed in by id
+    pub name: Option<String>,
+    // latest exam state reported by the client ("Locked", "Released" ...)
+    pub state: Option<String>,
+    pub last_heartbeat: Option<i64>,
+    pub online: bool,
+    // 0-100, distinct violations weighted by kind
+    pub risk_score: i64,
+    pub violations: i64,
+    pub latest_violations: Vec<TimelineEntry>,
+    pub extra_secs: i64,
+}
+
+#[derive(Serialize)]
+pub struct TimelineEntry {
+    pub at: i64,
+    pub level: String,
+    pub message: String,
+    pub violation_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StudentTimeline {
+    pub session_id: String,
+    pub student_id: String,
+    // oldest first
+    pub entries: Vec<TimelineEntry>,
+    // everything uploaded for the student, linked to entries by violation_id
+    pub evidence: Vec<EvidenceRecord>,
+    // more entries exist than were returned
+    pub truncated: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TimelineQuery {
+    #[serde(default = "default_timeline_limit")]
+    pub limit: i64,
+}
+
+fn default_timeline_limit() -> i64 { 1000 }
+
+// who an account is. admins can do everything, instructors and proctors only see the sessions
+// assigned to them, student clients only talk to the client endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Instructor,
+    Proctor,
+    StudentClient,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserRecord {
+    pub username: String,
+    // argon2 PHC string
+    pub password_hash: String,
+    pub role: Role,
+    // session ids an instructor / proctor is assigned to
+    #[serde(default)]
+    pub sessions: Vec<String>,
+    #[serde(default)]
+    pub disabled: bool,
+    pub created_at: i64,
+}
+
+// a user without the password hash
+#[derive(Serialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: Role,
+    pub sessions: Vec<String>,
+    pub disabled: bool,
+}
+
+impl From<UserRecord> for UserInfo {
+    fn from(user: UserRecord) -> Self {
+        Self { username: user.username, role: user.role, sessions: user.sessions, disabled: user.disabled }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    // sent back as `Authorization: Bearer <token>`
+    pub token: String,
+    pub role: Role,
+    pub expires_at: i64,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUser {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    #[serde(default)]
+    pub sessions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AssignSessions {
+    pub sessions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePassword {
+    pub password: String,
+}
+
+// one student of an exam roster, join_code is what the student types into the client
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RosterStudent {
+    pub exam_id: String,
+    pub student_id: String,
+    pub name: String,
+    pub email: String,
+    #[serde(default)]
+    pub seat: Option<String>,
+    #[serde(default)]
+    pub lab: Option<String>,
+    pub session_id: String,
+    pub join_code: String,
+    // last time the code was redeemed
+    #[serde(default)]
+    pub joined_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct RosterQuery {
+    // only validate, nothing is written
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct RowError {
+    // line in the CSV file, the header is line 1
+    pub line: u64,
+    pub student_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct RosterReport {
+    pub exam_id: String,
+    pub dry_run: bool,
+    pub rows: usize,
+    // nothing is imported while there are errors
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+    pub sessions: Vec<String>,
+    pub students: Vec<RosterStudent>,
+}
+
+#[derive(Deserialize)]
+pub struct JoinRequest {
+    pub join_code: String,
+}
+
+#[derive(Serialize)]
+pub struct JoinResponse {
+    pub token: String,
+    pub student_id: String,
+    pub session_id: String,
+    pub name: String,
+    pub expires_at: i64,
+}
+
+// report: one student of a session, also one row of the exam CSV
+#[derive(Serialize)]
+pub struct StudentSummary {
+    pub session_id: String,
+    pub student_id: String,
+    // roster details, none for students that signed in by id
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub seat: Option<String>,
+    pub lab: Option<String>,
+    pub risk_score: i64,
+    // violations that only differ in numbers counted once, as in the risk score
+    pub distinct_violations: i64,
+    // every reported violation event, repeats included, and the same per kind
+    pub violations: i64,
+    pub application: i64,
+    pub network: i64,
+    pub environment: i64,
+    pub tamper: i64,
+    pub other: i64,
+    // gaps between schedule polls, plus the time since the last one if the exam wasn't finished
+    pub offline_secs: i64,
+    pub first_seen: Option<i64>,
+    pub last_seen: Option<i64>,
+    pub final_state: Option<String>,
+    // incidents per review decision
+    pub incidents: i64,
+    pub pending_review: i64,
+    pub confirmed: i64,
+    pub false_positive: i64,
+    pub escalated: i64,
+    // the strongest decision: escalated, confirmed, pending_review, cleared (all false positives) or none
+    pub outcome: String,
+}
+
+#[derive(Serialize)]
+pub struct StudentReport {
+    #[serde(flatten)]
+    pub summary: StudentSummary,
+    // every reported entry, oldest first
+    pub entries: Vec<TimelineEntry>,
+    pub evidence: Vec<EvidenceRecord>,
+    // with their notes, the audit trail is on the incident itself
+    pub incidents: Vec<Incident>,
+}
+
+#[derive(Serialize)]
+pub struct SessionReport {
+    pub session_id: String,
+    pub exam_id: Option<String>,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub generated_at: i64,
+    pub students: Vec<StudentReport>,
+}
+
+// ordered, webhooks send alerts from a minimum severity up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+// what makes a rule fire. levels are exam_logs levels, `contains` narrows to messages with
+// that text (e.g. "BANNED WINDOW")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCondition {
+    // any one matching entry
+    Level {
+        levels: Vec<String>,
+        #[serde(default)]
+        contains: Option<String>,
+    },
+    // more than `count` matching entries of one student within `window_secs`
+    Rate {
+        levels: Vec<String>,
+        #[serde(default)]
+        contains: Option<String>,
+        count: i64,
+        window_secs: i64,
+    },
+    // no schedule poll for longer than `after_secs` while the exam window is open
+    Offline { after_secs: i64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub rule_id: String,
+    pub name: String,
+    pub severity: Severity,
+    pub condition: RuleCondition,
+    // empty for every session
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: i64,
+}
+
+#[derive(Deserialize)]
+pub struct NewAlertRule {
+    pub name: String,
+    pub severity: Severity,
+    pub condition: RuleCondition,
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+// a rule that fired for one student. while it isn't resolved, the rule firing again only
+// updates it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub alert_id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub severity: Severity,
+    pub session_id: String,
+    pub student_id: String,
+    pub status: AlertStatus,
+    // from the latest time the rule fired
+    pub message: String,
+    pub first_at: i64,
+    pub last_at: i64,
+    // times the rule fired while the alert was open
+    pub count: i64,
+    #[serde(default)]
+    pub acknowledged_by: Option<String>,
+    #[serde(default)]
+    pub acknowledged_at: Option<i64>,
+    #[serde(default)]
+    pub resolved_by: Option<String>,
+    #[serde(default)]
+    pub resolved_at: Option<i64>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AlertQuery {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub status: Option<AlertStatus>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AlertAction {
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    // {"event": ..., "alert": {...}}
+    Generic,
+    // {"text": ...}, for Slack and chat tools with compatible incoming webhooks
+    Slack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub webhook_id: String,
+    pub name: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    // HMAC-SHA256 key of the signature header
+    pub secret: String,
+    pub min_severity: Severity,
+    // empty for every session
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: i64,
+}
+
+#[derive(Deserialize)]
+pub struct NewWebhook {
+    pub name: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    // generated when missing
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Warning
+}
+
+// the webhook without its secret
+#[derive(Serialize)]
+pub struct WebhookInfo {
+    pub webhook_id: String,
+    pub name: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    pub min_severity: Severity,
+    pub session_ids: Vec<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: i64,
+}
+
+impl From<Webhook> for WebhookInfo {
+    fn from(w: Webhook) -> Self {
+        WebhookInfo {
+            webhook_id: w.webhook_id,
+            name: w.name,
+            url: w.url,
+            format: w.format,
+            min_severity: w.min_severity,
+            session_ids: w.session_ids,
+            enabled: w.enabled,
+            created_by: w.created_by,
+            created_at: w.created_at,
+        }
+    }
+}
+
+// the only time the secret is returned
+#[derive(Serialize)]
+pub struct WebhookCreated {
+    #[serde(flatten)]
+    pub webhook: WebhookInfo,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    // out of attempts, kept until someone retries it
+    Dead,
+}
+
+// one alert for one webhook. the body is fixed when queued so every attempt sends the same bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub delivery_id: String,
+    pub webhook_id: String,
+    pub alert_id: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    pub next_attempt_at: i64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    #[serde(default)]
+    pub delivered_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct DeliveryQuery {
+    #[serde(default)]
+    pub status: Option<DeliveryStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentStatus {
+    // not reviewed yet
+    Pending,
+    FalsePositive,
+    Confirmed,
+    Escalated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentNote {
+    pub author: String,
+    pub at: i64,
+    pub text: String,
+}
+
+// the entries of one student with the same level and message up to numbers, reviewed together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub incident_id: String,
+    pub session_id: String,
+    pub student_id: String,
+    pub level: String,
+    // the message with numbers masked
+    pub signature: String,
+    #[serde(default)]
+    pub violation_ids: Vec<String>,
+    // reported entries, a violation is reported again on every scan
+    pub entries: i64,
+    pub first_at: i64,
+    pub last_at: i64,
+    pub status: IncidentStatus,
+    #[serde(default)]
+    pub notes: Vec<IncidentNote>,
+    #[serde(default)]
+    pub reviewed_by: Option<String>,
+    #[serde(default)]
+    pub reviewed_at: Option<i64>,
+    // the audit entries that were applied, in order
+    #[serde(default)]
+    pub audit_ids: Vec<String>,
+}
+
+// one change of an incident, written before the change and never updated or removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentAudit {
+    pub audit_id: String,
+    pub incident_id: String,
+    pub session_id: String,
+    pub actor: String,
+    pub at: i64,
+    // "note" or "status"
+    pub action: String,
+    #[serde(default)]
+    pub from: Option<IncidentStatus>,
+    #[serde(default)]
+    pub to: Option<IncidentStatus>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct IncidentDetail {
+    #[serde(flatten)]
+    pub incident: Incident,
+    // oldest first
+    pub audit: Vec<IncidentAuditEntry>,
+}
+
+#[derive(Serialize)]
+pub struct IncidentAuditEntry {
+    #[serde(flatten)]
+    pub audit: IncidentAudit,
+    // false when the change didn't go through after it was recorded
+    pub applied: bool,
+}
+
+#[derive(Deserialize)]
+pub struct IncidentQuery {
+    #[serde(default)]
+    pub student_id: Option<String>,
+    #[serde(default)]
+    pub status: Option<IncidentStatus>,
+}
+
+#[derive(Deserialize)]
+pub struct IncidentNoteRequest {
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+pub struct IncidentStatusRequest {
+    pub status: IncidentStatus,
+    #[serde(default)]
+    pub note: Option<String>,
+}
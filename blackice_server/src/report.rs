@@ -0,0 +1,462 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, Document};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use crate::{
+    auth::{AuthUser, STAFF},
+    dashboard::session_activity,
+    db::AppState,
+    handlers::{collect, db_error, response, OFFLINE_GAP_SECS},
+    incidents::session_incidents,
+    models::{
+        EvidenceRecord, GenericResponse, Incident, IncidentStatus, SessionReport, StudentReport, StudentSummary,
+        TimelineEntry,
+    },
+};
+
+// exports for after the exam: a CSV with one row per student of an exam and a JSON report with
+// the full timeline of a session. both are built from exam_logs, session_students, the roster and
+// the reviewed incidents
+
+// distinct_violations counts repeats that only differ in numbers once (like the risk score),
+// violations and the per kind columns count every reported event
+const CSV_HEADER: &[&str] = &[
+    "session_id", "student_id", "name", "email", "seat", "lab", "risk_score", "distinct_violations", "violations",
+    "application", "network", "environment", "tamper", "other", "offline_secs", "first_seen", "last_seen", "final_state",
+    "incidents", "pending_review", "confirmed", "false_positive", "escalated", "outcome",
+];
+// states in which the client is done, no offline time is counted after them
+const FINISHED_STATES: &[&str] = &["Released", "SignedOut"];
+
+// staff: one CSV row per student over every session of the exam the account can see
+pub async fn exam_report_csv(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(exam_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+
+    let sessions = state.db.collection::<Document>("sessions");
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "session_id": 1 }).build();
+    let docs = match collect(sessions.find(doc! { "exam_id": &exam_id }, options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let docs: Vec<Document> = docs
+        .into_iter()
+        .filter(|d| d.get_str("session_id").is_ok_and(|s| user.can_access(s)))
+        .collect();
+    if docs.is_empty() {
+        return response(StatusCode::NOT_FOUND, "error", format!("[server] [report] no sessions for exam {}", exam_id))
+            .into_response();
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if let Err(e) = writer.write_record(CSV_HEADER) {
+        return csv_error(e).into_response();
+    }
+    for session in &docs {
+        let summaries = match session_summaries(&state, session).await {
+            Ok(s) => s,
+            Err(e) => return db_error(e).into_response(),
+        };
+        for s in summaries {
+            if let Err(e) = writer.write_record(csv_row(&s)) {
+                return csv_error(e).into_response();
+            }
+        }
+    }
+    let body = match writer.into_inner() {
+        Ok(body) => body,
+        Err(e) => return csv_error(e.into_error().into()).into_response(),
+    };
+
+    tracing::info!("[server] [report] {} exported {}", user.username, exam_id);
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}-report.csv\"", file_name(&exam_id))),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// staff: every student of a session with summary, full timeline and evidence
+pub async fn session_report_json(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(session_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require_session(STAFF, &session_id) {
+        return e.into_response();
+    }
+
+    let sessions = state.db.collection::<Document>("sessions");
+    let session = match sessions.find_one(doc! { "session_id": &session_id }, None).await {
+        Ok(s) => s.unwrap_or_else(|| doc! { "session_id": &session_id }),
+        Err(e) => return db_error(e).into_response(),
+    };
+    let summaries = match session_summaries(&state, &session).await {
+        Ok(s) => s,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let mut incidents: HashMap<String, Vec<Incident>> = HashMap::new();
+    match session_incidents(&state.db, &session_id).await {
+        Ok(list) => {
+            for incident in list {
+                incidents.entry(incident.student_id.clone()).or_default().push(incident);
+            }
+        }
+        Err(e) => return db_error(e).into_response(),
+    }
+
+    let logs = state.db.collection::<Document>("exam_logs");
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "original_ts": 1 }).build();
+    let docs = match collect(logs.find(doc! { "session_id": &session_id }, options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let mut entries: HashMap<String, Vec<TimelineEntry>> = HashMap::new();
+    for d in docs {
+        let Ok(student_id) = d.get_str("student_id") else { continue };
+        entries.entry(student_id.to_string()).or_default().push(TimelineEntry {
+            at: d.get_i64("original_ts").unwrap_or(0),
+            level: d.get_str("level").unwrap_or_default().to_string(),
+            message: d.get_str("message").unwrap_or_default().to_string(),
+            violation_id: d.get_str("violation_id").ok().map(str::to_string),
+        });
+    }
+
+    let evidence = state.db.collection::<Document>("evidence");
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "uploaded_at": 1 }).build();
+    let docs = match collect(evidence.find(doc! { "session_id": &session_id }, options).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let mut evidence: HashMap<String, Vec<EvidenceRecord>> = HashMap::new();
+    for record in docs.into_iter().filter_map(|d| mongodb::bson::from_document::<EvidenceRecord>(d).ok()) {
+        evidence.entry(record.student_id.clone()).or_default().push(record);
+    }
+
+    let students = summaries
+        .into_iter()
+        .map(|summary| StudentReport {
+            entries: entries.remove(&summary.student_id).unwrap_or_default(),
+            evidence: evidence.remove(&summary.student_id).unwrap_or_default(),
+            incidents: incidents.remove(&summary.student_id).unwrap_or_default(),
+            summary,
+        })
+        .collect();
+
+    tracing::info!("[server] [report] {} exported session {}", user.username, session_id);
+    Json(SessionReport {
+        session_id,
+        exam_id: session.get_str("exam_id").ok().map(str::to_string),
+        starts_at: session.get_i64("starts_at").ok(),
+        ends_at: session.get_i64("ends_at").ok(),
+        generated_at: chrono::Utc::now().timestamp(),
+        students,
+    })
+    .into_response()
+}
+
+// Helpers functions
+
+// every student that is on the roster, polled the schedule or logged anything, by student id
+async fn session_summaries(state: &AppState, session: &Document) -> mongodb::error::Result<Vec<StudentSummary>> {
+    let session_id = session.get_str("session_id").unwrap_or_default();
+    let mut students: BTreeMap<String, StudentSummary> = BTreeMap::new();
+    let mut extra: HashMap<String, i64> = HashMap::new();
+
+    for (student_id, a) in session_activity(&state.db, session_id).await? {
+        let s = summary_entry(&mut students, session_id, &student_id);
+        s.risk_score = a.risk_score();
+        s.distinct_violations = a.violations();
+        s.first_seen = a.first_log;
+        s.last_seen = a.last_log;
+        s.final_state = a.state;
+    }
+
+    // every violation event per kind, the activity above folds repeats
+    let logs = state.db.collection::<Document>("exam_logs");
+    let pipeline = vec![
+        doc! { "$match": { "session_id": session_id, "level": { "$regex": "^VIOLATION_" } } },
+        doc! { "$group": { "_id": { "student_id": "$student_id", "level": "$level" }, "events": { "$sum": 1 } } },
+    ];
+    for d in collect(logs.aggregate(pipeline, None).await).await? {
+        let Ok(id) = d.get_document("_id") else { continue };
+        let (Ok(student_id), Ok(level)) = (id.get_str("student_id"), id.get_str("level")) else { continue };
+        let events = d.get_i32("events").map(i64::from).or_else(|_| d.get_i64("events")).unwrap_or(0);
+        add_events(summary_entry(&mut students, session_id, student_id), level, events);
+    }
+
+    let session_students = state.db.collection::<Document>("session_students");
+    for d in collect(session_students.find(doc! { "session_id": session_id }, None).await).await? {
+        let Ok(student_id) = d.get_str("student_id") else { continue };
+        let s = summary_entry(&mut students, session_id, student_id);
+        let secs = |field: &str| d.get_datetime(field).ok().map(|t| t.timestamp_millis() / 1000);
+        s.first_seen = earliest(s.first_seen, secs("first_seen"));
+        s.last_seen = s.last_seen.max(secs("last_seen"));
+        s.offline_secs = d.get_i64("offline_secs").unwrap_or(0);
+        extra.insert(student_id.to_string(), d.get_i64("extra_secs").unwrap_or(0));
+    }
+
+    let roster = state.db.collection::<Document>("roster");
+    for d in collect(roster.find(doc! { "session_id": session_id }, None).await).await? {
+        let Ok(student_id) = d.get_str("student_id") else { continue };
+        let s = summary_entry(&mut students, session_id, student_id);
+        let field = |name: &str| d.get_str(name).ok().map(str::to_string);
+        s.name = field("name");
+        s.email = field("email");
+        s.seat = field("seat");
+        s.lab = field("lab");
+    }
+
+    for incident in session_incidents(&state.db, session_id).await? {
+        let s = summary_entry(&mut students, session_id, &incident.student_id);
+        s.incidents += 1;
+        match incident.status {
+            IncidentStatus::Pending => s.pending_review += 1,
+            IncidentStatus::Confirmed => s.confirmed += 1,
+            IncidentStatus::FalsePositive => s.false_positive += 1,
+            IncidentStatus::Escalated => s.escalated += 1,
+        }
+    }
+
+    // a client that stopped polling before it was done was offline from then on
+    let now = chrono::Utc::now().timestamp();
+    let submit_grace = session.get_i64("submit_grace_secs").unwrap_or(0);
+    for s in students.values_mut() {
+        let finished = s.final_state.as_deref().is_some_and(|state| FINISHED_STATES.contains(&state));
+        let Some(last_seen) = s.last_seen else { continue };
+        if finished {
+            continue;
+        }
+        let closes_at = match session.get_i64("ends_at") {
+            Ok(ends_at) => now.min(ends_at + submit_grace + extra.get(&s.student_id).copied().unwrap_or(0)),
+            Err(_) => now,
+        };
+        if closes_at - last_seen > OFFLINE_GAP_SECS {
+            s.offline_secs += closes_at - last_seen;
+        }
+    }
+    for s in students.values_mut() {
+        s.outcome = outcome(s).to_string();
+    }
+
+    Ok(students.into_values().collect())
+}
+
+fn summary_entry<'a>(
+    students: &'a mut BTreeMap<String, StudentSummary>,
+    session_id: &str,
+    student_id: &str,
+) -> &'a mut StudentSummary {
+    students.entry(student_id.to_string()).or_insert_with(|| StudentSummary {
+        session_id: session_id.to_string(),
+        student_id: student_id.to_string(),
+        name: None,
+        email: None,
+        seat: None,
+        lab: None,
+        risk_score: 0,
+        distinct_violations: 0,
+        violations: 0,
+        application: 0,
+        network: 0,
+        environment: 0,
+        tamper: 0,
+        other: 0,
+        offline_secs: 0,
+        first_seen: None,
+        last_seen: None,
+        final_state: None,
+        incidents: 0,
+        pending_review: 0,
+        confirmed: 0,
+        false_positive: 0,
+        escalated: 0,
+        outcome: String::new(),
+    })
+}
+
+fn add_events(s: &mut StudentSummary, level: &str, events: i64) {
+    let kind = match level {
+        "VIOLATION_APP" => &mut s.application,
+        "VIOLATION_NET" => &mut s.network,
+        "VIOLATION_ENV" => &mut s.environment,
+        "VIOLATION_TAMPER" => &mut s.tamper,
+        "VIOLATION_OTH" => &mut s.other,
+        _ => return,
+    };
+    *kind += events;
+    s.violations += events;
+}
+
+// the strongest review decision over the student's incidents
+fn outcome(s: &StudentSummary) -> &'static str {
+    if s.escalated > 0 {
+        "escalated"
+    } else if s.confirmed > 0 {
+        "confirmed"
+    } else if s.pending_review > 0 {
+        "pending_review"
+    } else if s.false_positive > 0 {
+        "cleared"
+    } else {
+        "none"
+    }
+}
+
+// the numbers are ours, only the text columns can carry a formula
+fn csv_row(s: &StudentSummary) -> Vec<String> {
+    let text = |v: &Option<String>| csv_text(v.as_deref().unwrap_or_default());
+    let time = |v: Option<i64>| {
+        v.and_then(|t| chrono::DateTime::from_timestamp(t, 0)).map(|t| t.to_rfc3339()).unwrap_or_default()
+    };
+    vec![
+        csv_text(&s.session_id),
+        csv_text(&s.student_id),
+        text(&s.name),
+        text(&s.email),
+        text(&s.seat),
+        text(&s.lab),
+        s.risk_score.to_string(),
+        s.distinct_violations.to_string(),
+        s.violations.to_string(),
+        s.application.to_string(),
+        s.network.to_string(),
+        s.environment.to_string(),
+        s.tamper.to_string(),
+        s.other.to_string(),
+        s.offline_secs.to_string(),
+        time(s.first_seen),
+        time(s.last_seen),
+        text(&s.final_state),
+        s.incidents.to_string(),
+        s.pending_review.to_string(),
+        s.confirmed.to_string(),
+        s.false_positive.to_string(),
+        s.escalated.to_string(),
+        csv_text(&s.outcome),
+    ]
+}
+
+// roster fields come from imported files, a spreadsheet would run "=HYPERLINK(...)" as a formula
+fn csv_text(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+// the exam id is free text, only [A-Za-z0-9_-] goes into the header
+fn file_name(exam_id: &str) -> String {
+    exam_id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+fn earliest(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn csv_error(e: csv::Error) -> (StatusCode, Json<GenericResponse>) {
+    tracing::error!("[server] [report] {:?}", e);
+    response(StatusCode::INTERNAL_SERVER_ERROR, "error", format!("[server] [report] csv export failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(name: &str) -> StudentSummary {
+        StudentSummary {
+            session_id: "s-1".to_string(),
+            student_id: "st-1".to_string(),
+            name: Some(name.to_string()),
+            email: Some("@evil".to_string()),
+            seat: Some("+A12".to_string()),
+            lab: None,
+            risk_score: -3,
+            distinct_violations: 2,
+            violations: 2,
+            application: 1,
+            network: 1,
+            environment: 0,
+            tamper: 0,
+            other: 0,
+            offline_secs: 30,
+            first_seen: Some(0),
+            last_seen: None,
+            final_state: Some("Released".to_string()),
+            incidents: 1,
+            pending_review: 1,
+            confirmed: 0,
+            false_positive: 0,
+            escalated: 0,
+            outcome: "pending_review".to_string(),
+        }
+    }
+
+    #[test]
+    fn formula_text_is_escaped() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(csv_text(value), format!("'{}", value));
+        }
+        for value in ["", "Ana", "a=1", "1-2", "'quoted"] {
+            assert_eq!(csv_text(value), value);
+        }
+    }
+
+    #[test]
+    fn csv_row_escapes_text_columns_only() {
+        let row = csv_row(&summary("=HYPERLINK(\"http://x\")"));
+        assert_eq!(row.len(), CSV_HEADER.len());
+        assert_eq!(row[2], "'=HYPERLINK(\"http://x\")");
+        assert_eq!(row[3], "'@evil");
+        assert_eq!(row[4], "'+A12");
+        assert_eq!(row[5], "");
+        // a negative score is a number, not a formula
+        assert_eq!(row[6], "-3");
+        assert_eq!(row[15], "1970-01-01T00:00:00+00:00");
+        assert_eq!(row[17], "Released");
+    }
+
+    #[test]
+    fn events_are_counted_per_kind() {
+        let mut s = summary("Ana");
+        s.violations = 0;
+        s.application = 0;
+        s.network = 0;
+        // forty banned window hits with different pids are one distinct violation, but forty events
+        add_events(&mut s, "VIOLATION_APP", 40);
+        add_events(&mut s, "VIOLATION_TAMPER", 2);
+        add_events(&mut s, "VIOLATION_OTH", 1);
+        add_events(&mut s, "VIOLATION_UNKNOWN", 5);
+
+        assert_eq!((s.application, s.network, s.tamper, s.other), (40, 0, 2, 1));
+        assert_eq!(s.violations, 43);
+        assert_eq!(s.distinct_violations, 2);
+        let row = csv_row(&s);
+        assert_eq!((row[7].as_str(), row[8].as_str(), row[9].as_str()), ("2", "43", "40"));
+    }
+
+    #[test]
+    fn file_name_keeps_safe_characters_only() {
+        assert_eq!(file_name("CS101_final-2026"), "CS101_final-2026");
+        assert_eq!(file_name("a\"; filename=x.exe"), "a___filename_x_exe");
+        assert_eq!(file_name("exam\r\nSet-Cookie: x"), "exam__Set-Cookie__x");
+        assert_eq!(file_name("prüfung"), "pr_fung");
+    }
+}
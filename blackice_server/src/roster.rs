@@ -0,0 +1,487 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, Document};
+use mongodb::options::{IndexOptions, UpdateOptions};
+use mongodb::IndexModel;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{
+    auth::{self, AuthUser, INSTRUCTORS, STAFF},
+    blob_store::is_safe_segment,
+    db::{AppState, DbResult},
+    handlers::{collect, db_error, response},
+    models::{JoinRequest, JoinResponse, Role, RosterQuery, RosterReport, RosterStudent, RowError},
+};
+
+// exam rosters. an import creates one session per lab ("{exam_id}-{lab}", or just the exam id
+// without labs) and gives every student a join code. the client trades the code for a token
+// that only works for that student in that session
+
+const REQUIRED_COLUMNS: &[&str] = &["student_id", "name", "email"];
+const OPTIONAL_COLUMNS: &[&str] = &["seat", "lab"];
+const MAX_ROWS: usize = 5000;
+// join tokens outlive the exam window by this much, for the last uploads
+const JOIN_TOKEN_GRACE: Duration = Duration::from_secs(60 * 60);
+// for sessions without a schedule
+const UNSCHEDULED_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// a valid row, ready to be written
+struct RosterRow {
+    student_id: String,
+    name: String,
+    email: String,
+    seat: Option<String>,
+    lab: Option<String>,
+}
+
+pub async fn init(db: &mongodb::Database) -> DbResult<()> {
+    let roster = db.collection::<Document>("roster");
+    let unique = || IndexOptions::builder().unique(true).build();
+    roster
+        .create_index(
+            IndexModel::builder().keys(doc! { "exam_id": 1, "student_id": 1 }).options(unique()).build(),
+            None,
+        )
+        .await?;
+    roster
+        .create_index(IndexModel::builder().keys(doc! { "join_code": 1 }).options(unique()).build(), None)
+        .await?;
+    Ok(())
+}
+
+// instructor: imports a roster CSV (student_id, name, email and optionally seat, lab).
+// all or nothing, any row error and the report is returned without writing anything
+pub async fn import_roster(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(exam_id): Path<String>,
+    Query(query): Query<RosterQuery>,
+    body: String,
+) -> axum::response::Response {
+    if let Err(e) = user.require(INSTRUCTORS) {
+        return e.into_response();
+    }
+    if !is_safe_segment(&exam_id) {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] [roster] invalid exam id".to_string()).into_response();
+    }
+
+    let (rows, errors) = match parse_roster(&body) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return response(StatusCode::BAD_REQUEST, "error", format!("[server] [roster] {}", message)).into_response();
+        }
+    };
+    let session_ids: BTreeSet<String> = rows.iter().map(|r| session_for(&exam_id, r.lab.as_deref())).collect();
+
+    // sessions that already exist have to be this exam's and the instructor's, and so do the other
+    // sessions of the exam, a re-import can move students between them
+    let sessions = state.db.collection::<Document>("sessions");
+    let ids: Vec<String> = session_ids.iter().cloned().collect();
+    let filter = doc! { "$or": [{ "session_id": { "$in": ids } }, { "exam_id": &exam_id }] };
+    let existing = match collect(sessions.find(filter, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    for d in existing {
+        let session_id = d.get_str("session_id").unwrap_or_default();
+        if d.get_str("exam_id").is_ok_and(|e| e != exam_id) {
+            return response(
+                StatusCode::CONFLICT,
+                "error",
+                format!("[server] [roster] session {} belongs to another exam", session_id),
+            )
+            .into_response();
+        }
+        if !user.can_access(session_id) {
+            return auth::forbidden(format!("not assigned to session {}", session_id)).into_response();
+        }
+    }
+
+    // students already on the roster stay where they are unless the instructor has their session
+    let roster = state.db.collection::<Document>("roster");
+    let student_ids: Vec<&String> = rows.iter().map(|r| &r.student_id).collect();
+    let filter = doc! { "exam_id": &exam_id, "student_id": { "$in": student_ids } };
+    let listed = match collect(roster.find(filter, None).await).await {
+        Ok(docs) => docs,
+        Err(e) => return db_error(e).into_response(),
+    };
+    if let Some(d) = listed.iter().find(|d| !user.can_access(d.get_str("session_id").unwrap_or_default())) {
+        return auth::forbidden(format!(
+            "student {} is in session {}, which isn't assigned to you",
+            d.get_str("student_id").unwrap_or_default(),
+            d.get_str("session_id").unwrap_or_default(),
+        ))
+        .into_response();
+    }
+
+    let mut report = RosterReport {
+        exam_id: exam_id.clone(),
+        dry_run: query.dry_run,
+        rows: rows.len() + errors.len(),
+        imported: 0,
+        errors,
+        sessions: session_ids.iter().cloned().collect(),
+        students: Vec::new(),
+    };
+    if !report.errors.is_empty() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response();
+    }
+    if query.dry_run {
+        return Json(report).into_response();
+    }
+
+    // new sessions start without a window, the schedule is set per session afterwards
+    let upsert = UpdateOptions::builder().upsert(true).build();
+    for session_id in &session_ids {
+        let update = doc! { "$set": { "exam_id": &exam_id } };
+        if let Err(e) = sessions.update_one(doc! { "session_id": session_id }, update, upsert.clone()).await {
+            return db_error(e).into_response();
+        }
+    }
+
+    // students already on the roster keep their join code
+    for row in &rows {
+        let filter = doc! { "exam_id": &exam_id, "student_id": &row.student_id };
+        let mut attempt = 0;
+        loop {
+            let update = doc! {
+                "$set": {
+                    "name": &row.name,
+                    "email": &row.email,
+                    "seat": row.seat.clone(),
+                    "lab": row.lab.clone(),
+                    "session_id": session_for(&exam_id, row.lab.as_deref()),
+                },
+                "$setOnInsert": { "join_code": auth::new_join_code() },
+            };
+            match roster.update_one(filter.clone(), update, upsert.clone()).await {
+                Ok(_) => break,
+                // another student already has the generated code
+                Err(e) if auth::is_duplicate(&e) && attempt < 3 => attempt += 1,
+                Err(e) => return db_error(e).into_response(),
+            }
+        }
+        report.imported += 1;
+    }
+
+    // the importing instructor gets the sessions
+    if user.role == Role::Instructor {
+        let users = state.db.collection::<Document>("users");
+        let update = doc! { "$addToSet": { "sessions": { "$each": report.sessions.clone() } } };
+        if let Err(e) = users.update_one(doc! { "username": &user.username }, update, None).await {
+            return db_error(e).into_response();
+        }
+    }
+
+    let imported: Vec<String> = rows.iter().map(|r| r.student_id.clone()).collect();
+    let filter = doc! { "exam_id": &exam_id, "student_id": { "$in": imported } };
+    report.students = match load_students(&state, filter).await {
+        Ok(students) => students,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    tracing::info!(
+        "[server] [roster] {} imported {} students into {} sessions of {}",
+        user.username, report.imported, report.sessions.len(), exam_id
+    );
+    Json(report).into_response()
+}
+
+// staff: the roster with join codes, only the sessions the account is assigned to
+pub async fn list_roster(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(exam_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(STAFF) {
+        return e.into_response();
+    }
+
+    match load_students(&state, doc! { "exam_id": &exam_id }).await {
+        Ok(students) => {
+            let visible: Vec<RosterStudent> = students.into_iter().filter(|s| user.can_access(&s.session_id)).collect();
+            Json(visible).into_response()
+        }
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// client: trades a join code for a token of that student, until the exam window is over
+pub async fn join(State(state): State<Arc<AppState>>, Json(req): Json<JoinRequest>) -> axum::response::Response {
+    let Some(code) = normalize_join_code(&req.join_code) else {
+        return auth::unauthorized("unknown join code").into_response();
+    };
+
+    let roster = state.db.collection::<RosterStudent>("roster");
+    let student = match roster.find_one(doc! { "join_code": &code }, None).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return auth::unauthorized("unknown join code").into_response(),
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let sessions = state.db.collection::<Document>("sessions");
+    let session = match sessions.find_one(doc! { "session_id": &student.session_id }, None).await {
+        Ok(s) => s,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let expires_at = match session.as_ref().and_then(|s| s.get_i64("ends_at").ok()) {
+        Some(ends_at) => {
+            let students = state.db.collection::<Document>("session_students");
+            let filter = doc! { "session_id": &student.session_id, "student_id": &student.student_id };
+            let extra_secs = match students.find_one(filter, None).await {
+                Ok(d) => d.and_then(|d| d.get_i64("extra_secs").ok()).unwrap_or(0),
+                Err(e) => return db_error(e).into_response(),
+            };
+            let submit_grace = session.as_ref().and_then(|s| s.get_i64("submit_grace_secs").ok()).unwrap_or(0);
+            let closes_at = ends_at + extra_secs + submit_grace;
+            if now > closes_at {
+                return auth::forbidden(format!("session {} is over", student.session_id)).into_response();
+            }
+            closes_at + JOIN_TOKEN_GRACE.as_secs() as i64
+        }
+        None => now + UNSCHEDULED_TOKEN_TTL.as_secs() as i64,
+    };
+
+    let token = match auth::issue_student_token(&state.db, &student.student_id, &student.session_id, expires_at).await {
+        Ok(t) => t,
+        Err(e) => return db_error(e).into_response(),
+    };
+    let update = doc! { "$set": { "joined_at": now } };
+    let filter = doc! { "exam_id": &student.exam_id, "student_id": &student.student_id };
+    if let Err(e) = roster.update_one(filter, update, None).await {
+        tracing::error!("[server] [roster] failed to record join: {:?}", e);
+    }
+
+    tracing::info!("[server] [roster] {} joined {}", student.student_id, student.session_id);
+    Json(JoinResponse {
+        token,
+        student_id: student.student_id,
+        session_id: student.session_id,
+        name: student.name,
+        expires_at,
+    })
+    .into_response()
+}
+
+// Helpers functions
+
+// the header names the columns, in any order. Err only for a file that can't be read at all
+fn parse_roster(body: &str) -> Result<(Vec<RosterRow>, Vec<RowError>), String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(body.as_bytes());
+
+    let headers = reader.headers().map_err(|e| format!("unreadable header: {}", e))?.clone();
+    let columns: Vec<String> = headers.iter().map(|h| h.to_ascii_lowercase().replace([' ', '-'], "_")).collect();
+    if let Some(unknown) = columns.iter().find(|c| !REQUIRED_COLUMNS.contains(&c.as_str()) && !OPTIONAL_COLUMNS.contains(&c.as_str())) {
+        return Err(format!("unknown column '{}'", unknown));
+    }
+    for required in REQUIRED_COLUMNS {
+        if !columns.iter().any(|c| c == required) {
+            return Err(format!("missing column '{}'", required));
+        }
+    }
+    let index = |name: &str| columns.iter().position(|c| c == name);
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    for record in reader.records() {
+        if rows.len() + errors.len() >= MAX_ROWS {
+            return Err(format!("more than {} rows", MAX_ROWS));
+        }
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(0);
+                errors.push(RowError { line, student_id: None, message: e.to_string() });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        if record.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        if record.len() != columns.len() {
+            errors.push(RowError {
+                line,
+                student_id: None,
+                message: format!("{} fields, the header has {}", record.len(), columns.len()),
+            });
+            continue;
+        }
+
+        let field = |name: &str| index(name).and_then(|i| record.get(i)).unwrap_or_default().to_string();
+        let optional = |name: &str| Some(field(name)).filter(|v| !v.is_empty());
+        let row = RosterRow {
+            student_id: field("student_id"),
+            name: field("name"),
+            email: field("email"),
+            seat: optional("seat"),
+            lab: optional("lab"),
+        };
+
+        let problem = if !is_safe_segment(&row.student_id) {
+            Some("student_id must be letters, digits, '-', '_' or '.'".to_string())
+        } else if row.name.is_empty() {
+            Some("name is empty".to_string())
+        } else if !is_email(&row.email) {
+            Some(format!("invalid email '{}'", row.email))
+        } else if row.lab.as_deref().is_some_and(|l| !is_safe_segment(l)) {
+            Some("lab must be letters, digits, '-', '_' or '.'".to_string())
+        } else if !seen.insert(row.student_id.clone()) {
+            Some("student_id appears more than once".to_string())
+        } else {
+            None
+        };
+
+        match problem {
+            Some(message) => errors.push(RowError { line, student_id: Some(row.student_id), message }),
+            None => rows.push(row),
+        }
+    }
+
+    if rows.is_empty() && errors.is_empty() {
+        return Err("no students".to_string());
+    }
+    Ok((rows, errors))
+}
+
+fn session_for(exam_id: &str, lab: Option<&str>) -> String {
+    match lab {
+        Some(lab) => format!("{}-{}", exam_id, lab),
+        None => exam_id.to_string(),
+    }
+}
+
+// one '@', something before it and a dot in the domain
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else { return false };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.contains(char::is_whitespace)
+}
+
+// "abcd efgh" / "ABCD-EFGH" -> "ABCD-EFGH"
+fn normalize_join_code(code: &str) -> Option<String> {
+    let chars: String = code.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect();
+    if chars.len() != auth::JOIN_CODE_LEN {
+        return None;
+    }
+    let (first, second) = chars.split_at(auth::JOIN_CODE_LEN / 2);
+    Some(format!("{}-{}", first, second))
+}
+
+async fn load_students(state: &AppState, filter: Document) -> mongodb::error::Result<Vec<RosterStudent>> {
+    let roster = state.db.collection::<RosterStudent>("roster");
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "session_id": 1, "student_id": 1 }).build();
+    let mut cursor = roster.find(filter, options).await?;
+    let mut students = Vec::new();
+    while cursor.advance().await? {
+        students.push(cursor.deserialize_current()?);
+    }
+    Ok(students)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(body: &str) -> Vec<(u64, Option<String>, String)> {
+        let (_, errors) = parse_roster(body).unwrap();
+        errors.into_iter().map(|e| (e.line, e.student_id, e.message)).collect()
+    }
+
+    #[test]
+    fn header_in_any_order_and_spelling() {
+        let (rows, errors) =
+            parse_roster("Email, Student-ID ,name,LAB,seat\nana@uni.edu,s1,Ana,L1,A3\nbo@uni.edu,s2,Bo,,\n").unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].student_id.as_str(), rows[0].email.as_str()), ("s1", "ana@uni.edu"));
+        assert_eq!((rows[0].lab.as_deref(), rows[0].seat.as_deref()), (Some("L1"), Some("A3")));
+        assert_eq!((rows[1].lab.as_deref(), rows[1].seat.as_deref()), (None, None));
+    }
+
+    #[test]
+    fn missing_and_unknown_columns_reject_the_file() {
+        assert_eq!(parse_roster("student_id,name\ns1,Ana\n").err(), Some("missing column 'email'".to_string()));
+        assert_eq!(
+            parse_roster("student_id,name,email,phone\ns1,Ana,a@b.c,1\n").err(),
+            Some("unknown column 'phone'".to_string())
+        );
+        assert_eq!(parse_roster("student_id,name,email\n").err(), Some("no students".to_string()));
+    }
+
+    #[test]
+    fn row_errors_carry_their_line() {
+        let body = "student_id,name,email,lab\n\
+                    s1,Ana,ana@uni.edu,L1\n\
+                    s2,Bo,bo@uni.edu\n\
+                    \n\
+                    ,,,\n\
+                    s3,Cy,not-an-email,L1\n\
+                    s4,Di,di@uni.edu,../L1\n\
+                    s1,Ana Again,ana2@uni.edu,L1\n\
+                    s/5,Ed,ed@uni.edu,L1\n\
+                    s6,,fi@uni.edu,L1\n\
+                    s7,Gu,gu@uni.edu,L2,extra\n";
+        let (rows, _) = parse_roster(body).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            errors(body),
+            vec![
+                (3, None, "3 fields, the header has 4".to_string()),
+                (6, Some("s3".to_string()), "invalid email 'not-an-email'".to_string()),
+                (7, Some("s4".to_string()), "lab must be letters, digits, '-', '_' or '.'".to_string()),
+                (8, Some("s1".to_string()), "student_id appears more than once".to_string()),
+                (9, Some("s/5".to_string()), "student_id must be letters, digits, '-', '_' or '.'".to_string()),
+                (10, Some("s6".to_string()), "name is empty".to_string()),
+                (11, None, "5 fields, the header has 4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn row_limit() {
+        let body = |n: usize| {
+            let rows: String = (0..n).map(|i| format!("s{},N{},s{}@uni.edu\n", i, i, i)).collect();
+            format!("student_id,name,email\n{}", rows)
+        };
+        assert_eq!(parse_roster(&body(MAX_ROWS)).unwrap().0.len(), MAX_ROWS);
+        assert_eq!(parse_roster(&body(MAX_ROWS + 1)).err(), Some(format!("more than {} rows", MAX_ROWS)));
+    }
+
+    #[test]
+    fn email_checks() {
+        for ok in ["ana@uni.edu", "a.b+c@mail.uni.edu"] {
+            assert!(is_email(ok), "{}", ok);
+        }
+        for bad in ["", "ana", "@uni.edu", "ana@uni", "ana@.uni.edu", "ana@uni.edu.", "a@b@uni.edu", "an a@uni.edu"] {
+            assert!(!is_email(bad), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn join_codes_are_normalized() {
+        assert_eq!(normalize_join_code("abcd efgh").as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(normalize_join_code(" ABCD-EFGH ").as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(normalize_join_code("abcdefg"), None);
+        assert_eq!(normalize_join_code("abcd-efgh-i"), None);
+    }
+
+    #[test]
+    fn sessions_per_lab() {
+        assert_eq!(session_for("cs101", Some("L1")), "cs101-L1");
+        assert_eq!(session_for("cs101", None), "cs101");
+    }
+}
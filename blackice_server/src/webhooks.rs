@@ -0,0 +1,622 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use hmac::{Hmac, Mac};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{FindOptions, IndexOptions};
+use mongodb::IndexModel;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use crate::{
+    auth::{self, AuthUser, ADMINS},
+    db::{AppState, DbResult},
+    handlers::{collect, db_error, response},
+    models::{
+        Alert, AlertStatus, DeliveryQuery, DeliveryStatus, NewWebhook, Severity, Webhook, WebhookCreated,
+        WebhookDelivery, WebhookFormat, WebhookInfo,
+    },
+};
+
+// outbound webhooks for alerts. a new alert is queued once per matching webhook in
+// webhook_deliveries, and a background task sends the queue with retries and backoff. a delivery
+// that runs out of attempts stays there as dead until an admin retries it.
+//
+// every request is signed: X-BlackIce-Signature is "sha256=" + hex HMAC-SHA256 of
+// "{X-BlackIce-Timestamp}.{body}" with the webhook's secret
+
+const MAX_ATTEMPTS: i64 = 6;
+// 10s, 20s, 40s, ... between attempts
+const FIRST_BACKOFF_SECS: i64 = 10;
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+// deliveries sent per run of the background task
+const DELIVERY_BATCH: i64 = 50;
+const MAX_DELIVERIES: i64 = 200;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub async fn init(db: &mongodb::Database) -> DbResult<()> {
+    let unique = || IndexOptions::builder().unique(true).build();
+    db.collection::<Document>("webhooks")
+        .create_index(IndexModel::builder().keys(doc! { "webhook_id": 1 }).options(unique()).build(), None)
+        .await?;
+    let deliveries = db.collection::<Document>("webhook_deliveries");
+    deliveries
+        .create_index(IndexModel::builder().keys(doc! { "delivery_id": 1 }).options(unique()).build(), None)
+        .await?;
+    deliveries
+        .create_index(IndexModel::builder().keys(doc! { "status": 1, "next_attempt_at": 1 }).build(), None)
+        .await?;
+    Ok(())
+}
+
+// admin: configured webhooks, without their secrets
+pub async fn list_webhooks(State(state): State<Arc<AppState>>, user: AuthUser) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    match load_webhooks(&state, doc! {}).await {
+        Ok(hooks) => Json(hooks.into_iter().map(WebhookInfo::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// admin: adds a webhook, the response has the signing secret
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<NewWebhook>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    if req.name.trim().is_empty() {
+        return response(StatusCode::BAD_REQUEST, "error", "[server] [webhooks] webhook needs a name".to_string())
+            .into_response();
+    }
+    match reqwest::Url::parse(&req.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        _ => {
+            return response(StatusCode::BAD_REQUEST, "error", format!("[server] [webhooks] invalid url {}", req.url))
+                .into_response();
+        }
+    }
+
+    let webhook = Webhook {
+        webhook_id: ObjectId::new().to_hex(),
+        name: req.name.trim().to_string(),
+        url: req.url,
+        format: req.format,
+        secret: req.secret.filter(|s| !s.is_empty()).unwrap_or_else(auth::new_token),
+        min_severity: req.min_severity,
+        session_ids: req.session_ids,
+        enabled: true,
+        created_by: user.username.clone(),
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    let hooks = state.db.collection::<Webhook>("webhooks");
+    if let Err(e) = hooks.insert_one(&webhook, None).await {
+        return db_error(e).into_response();
+    }
+    tracing::info!("[server] [webhooks] {} added webhook '{}' ({})", user.username, webhook.name, webhook.webhook_id);
+    let secret = webhook.secret.clone();
+    (StatusCode::CREATED, Json(WebhookCreated { webhook: webhook.into(), secret })).into_response()
+}
+
+// admin: removes a webhook, its queued deliveries are dropped when due
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(webhook_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    let hooks = state.db.collection::<Document>("webhooks");
+    match hooks.delete_one(doc! { "webhook_id": &webhook_id }, None).await {
+        Ok(r) if r.deleted_count == 0 => {
+            response(StatusCode::NOT_FOUND, "error", format!("[server] [webhooks] no webhook {}", webhook_id))
+                .into_response()
+        }
+        Ok(_) => {
+            tracing::info!("[server] [webhooks] {} removed webhook {}", user.username, webhook_id);
+            response(StatusCode::OK, "success", format!("[server] [webhooks] removed webhook {}", webhook_id))
+                .into_response()
+        }
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// admin: sends a sample alert right away, without the queue, to check the receiver
+pub async fn test_webhook(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(webhook_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    let webhook = match load_webhooks(&state, doc! { "webhook_id": &webhook_id }).await {
+        Ok(mut hooks) if !hooks.is_empty() => hooks.remove(0),
+        Ok(_) => {
+            return response(StatusCode::NOT_FOUND, "error", format!("[server] [webhooks] no webhook {}", webhook_id))
+                .into_response();
+        }
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let alert = Alert {
+        alert_id: "test".to_string(),
+        rule_id: "test".to_string(),
+        rule_name: "Test alert".to_string(),
+        severity: webhook.min_severity,
+        session_id: "test-session".to_string(),
+        student_id: "test-student".to_string(),
+        status: AlertStatus::Open,
+        message: format!("test from {}", user.username),
+        first_at: now,
+        last_at: now,
+        count: 1,
+        acknowledged_by: None,
+        acknowledged_at: None,
+        resolved_by: None,
+        resolved_at: None,
+        note: None,
+    };
+    match send(&webhook, &ObjectId::new().to_hex(), &payload(&webhook, "alert.test", &alert)).await {
+        Ok(()) => response(StatusCode::OK, "success", format!("[server] [webhooks] delivered to {}", webhook.url))
+            .into_response(),
+        Err(e) => response(StatusCode::BAD_GATEWAY, "error", format!("[server] [webhooks] {}", e)).into_response(),
+    }
+}
+
+// admin: the latest deliveries, `?status=dead` for the dead letters
+pub async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<DeliveryQuery>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    let filter = match query.status {
+        Some(status) => doc! { "status": delivery_status_str(status) },
+        None => doc! {},
+    };
+    let deliveries = state.db.collection::<Document>("webhook_deliveries");
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).limit(MAX_DELIVERIES).build();
+    match collect(deliveries.find(filter, options).await).await {
+        Ok(docs) => Json(
+            docs.into_iter()
+                .filter_map(|d| mongodb::bson::from_document::<WebhookDelivery>(d).ok())
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// admin: puts a dead delivery back in the queue with fresh attempts
+pub async fn retry_delivery(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(delivery_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(e) = user.require(ADMINS) {
+        return e.into_response();
+    }
+    let deliveries = state.db.collection::<Document>("webhook_deliveries");
+    let filter = doc! { "delivery_id": &delivery_id, "status": delivery_status_str(DeliveryStatus::Dead) };
+    let update = doc! { "$set": {
+        "status": delivery_status_str(DeliveryStatus::Pending),
+        "attempts": 0_i64,
+        "next_attempt_at": chrono::Utc::now().timestamp(),
+    } };
+    match deliveries.update_one(filter, update, None).await {
+        Ok(r) if r.matched_count == 0 => response(
+            StatusCode::NOT_FOUND,
+            "error",
+            format!("[server] [webhooks] no dead delivery {}", delivery_id),
+        )
+        .into_response(),
+        Ok(_) => {
+            tracing::info!("[server] [webhooks] {} requeued delivery {}", user.username, delivery_id);
+            response(StatusCode::OK, "success", format!("[server] [webhooks] requeued delivery {}", delivery_id))
+                .into_response()
+        }
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+// alerts: queues a new alert for every webhook it matches
+pub async fn enqueue(state: &AppState, alert: &Alert) {
+    let hooks = match load_webhooks(state, doc! { "enabled": true }).await {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            tracing::error!("[server] [webhooks] failed to load webhooks: {:?}", e);
+            return;
+        }
+    };
+    let now = chrono::Utc::now().timestamp();
+    let queued: Vec<WebhookDelivery> = hooks
+        .iter()
+        .filter(|w| alert.severity >= w.min_severity)
+        .filter(|w| w.session_ids.is_empty() || w.session_ids.contains(&alert.session_id))
+        .map(|w| WebhookDelivery {
+            delivery_id: ObjectId::new().to_hex(),
+            webhook_id: w.webhook_id.clone(),
+            alert_id: alert.alert_id.clone(),
+            body: payload(w, "alert.opened", alert),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+        })
+        .collect();
+    if queued.is_empty() {
+        return;
+    }
+    let deliveries = state.db.collection::<WebhookDelivery>("webhook_deliveries");
+    if let Err(e) = deliveries.insert_many(queued, None).await {
+        tracing::error!("[server] [webhooks] failed to queue alert {}: {:?}", alert.alert_id, e);
+    }
+}
+
+// background: sends the deliveries that are due
+pub async fn deliver_due(state: Arc<AppState>) {
+    let now = chrono::Utc::now().timestamp();
+    let deliveries = state.db.collection::<Document>("webhook_deliveries");
+    let filter = doc! {
+        "status": delivery_status_str(DeliveryStatus::Pending),
+        "next_attempt_at": { "$lte": now },
+    };
+    let options = FindOptions::builder().sort(doc! { "next_attempt_at": 1 }).limit(DELIVERY_BATCH).build();
+    let due: Vec<WebhookDelivery> = match collect(deliveries.find(filter, options).await).await {
+        Ok(docs) => docs.into_iter().filter_map(|d| mongodb::bson::from_document(d).ok()).collect(),
+        Err(e) => {
+            tracing::error!("[server] [webhooks] failed to list due deliveries: {:?}", e);
+            return;
+        }
+    };
+    if due.is_empty() {
+        return;
+    }
+    let hooks: HashMap<String, Webhook> = match load_webhooks(&state, doc! { "enabled": true }).await {
+        Ok(hooks) => hooks.into_iter().map(|w| (w.webhook_id.clone(), w)).collect(),
+        Err(e) => {
+            tracing::error!("[server] [webhooks] failed to load webhooks: {:?}", e);
+            return;
+        }
+    };
+
+    // one task per webhook, so a receiver that is down only holds up its own deliveries
+    let mut by_webhook: HashMap<String, Vec<WebhookDelivery>> = HashMap::new();
+    for delivery in due {
+        by_webhook.entry(delivery.webhook_id.clone()).or_default().push(delivery);
+    }
+    let mut tasks = tokio::task::JoinSet::new();
+    for (webhook_id, queue) in by_webhook {
+        let state = state.clone();
+        let webhook = hooks.get(&webhook_id).cloned();
+        tasks.spawn(async move { deliver_queue(&state, webhook.as_ref(), queue).await });
+    }
+    while tasks.join_next().await.is_some() {}
+}
+
+// Helpers functions
+
+// sends the due deliveries of one webhook in order. after a failure the rest wait for the next run
+async fn deliver_queue(state: &AppState, webhook: Option<&Webhook>, queue: Vec<WebhookDelivery>) {
+    let deliveries = state.db.collection::<Document>("webhook_deliveries");
+    for delivery in queue {
+        let (update, delivered) = attempt(webhook, &delivery).await;
+        if let Err(e) = deliveries.update_one(doc! { "delivery_id": &delivery.delivery_id }, update, None).await {
+            tracing::error!("[server] [webhooks] failed to update delivery {}: {:?}", delivery.delivery_id, e);
+        }
+        if !delivered && webhook.is_some() {
+            break;
+        }
+    }
+}
+
+// sends one delivery, returns the update of its record and whether it went through
+async fn attempt(webhook: Option<&Webhook>, delivery: &WebhookDelivery) -> (Document, bool) {
+    let attempts = delivery.attempts + 1;
+    let result = match webhook {
+        Some(webhook) => send(webhook, &delivery.delivery_id, &delivery.body).await,
+        None => Err("webhook was removed or disabled".to_string()),
+    };
+    let now = chrono::Utc::now().timestamp();
+    match result {
+        Ok(()) => {
+            let update = doc! { "$set": {
+                "status": delivery_status_str(DeliveryStatus::Delivered),
+                "attempts": attempts,
+                "delivered_at": now,
+                "last_error": null,
+            } };
+            (update, true)
+        }
+        Err(e) if attempts >= MAX_ATTEMPTS || webhook.is_none() => {
+            tracing::error!("[server] [webhooks] giving up on delivery {}: {}", delivery.delivery_id, e);
+            let update = doc! { "$set": {
+                "status": delivery_status_str(DeliveryStatus::Dead),
+                "attempts": attempts,
+                "last_error": e,
+            } };
+            (update, false)
+        }
+        Err(e) => {
+            tracing::warn!(
+                "[server] [webhooks] delivery {} failed (attempt {}): {}",
+                delivery.delivery_id, attempts, e
+            );
+            let update = doc! { "$set": {
+                "attempts": attempts,
+                "next_attempt_at": now + backoff_secs(attempts),
+                "last_error": e,
+            } };
+            (update, false)
+        }
+    }
+}
+
+async fn load_webhooks(state: &AppState, filter: Document) -> mongodb::error::Result<Vec<Webhook>> {
+    let hooks = state.db.collection::<Document>("webhooks");
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let docs = collect(hooks.find(filter, options).await).await?;
+    Ok(docs.into_iter().filter_map(|d| mongodb::bson::from_document::<Webhook>(d).ok()).collect())
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            // a redirect would re-send the signature headers to wherever it points
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+async fn send(webhook: &Webhook, delivery_id: &str, body: &str) -> Result<(), String> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let res = client()
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-BlackIce-Delivery", delivery_id)
+        .header("X-BlackIce-Timestamp", timestamp.to_string())
+        .header("X-BlackIce-Signature", sign(&webhook.secret, timestamp, body))
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| format!("request to {} failed: {}", webhook.url, e))?;
+    if res.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("{} answered {}", webhook.url, res.status()))
+    }
+}
+
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn payload(webhook: &Webhook, event: &str, alert: &Alert) -> String {
+    match webhook.format {
+        WebhookFormat::Generic => serde_json::json!({ "event": event, "alert": alert }).to_string(),
+        WebhookFormat::Slack => {
+            let (icon, label) = match alert.severity {
+                Severity::Info => (":information_source:", "INFO"),
+                Severity::Warning => (":warning:", "WARNING"),
+                Severity::Critical => (":rotating_light:", "CRITICAL"),
+            };
+            let text = format!(
+                "{} *{}* {}: student `{}` in session `{}`\n{}",
+                icon, label, alert.rule_name, alert.student_id, alert.session_id, alert.message
+            );
+            serde_json::json!({ "text": text }).to_string()
+        }
+    }
+}
+
+fn backoff_secs(attempts: i64) -> i64 {
+    let exp = (attempts - 1).clamp(0, 20) as u32;
+    (FIRST_BACKOFF_SECS * 2_i64.pow(exp)).min(MAX_BACKOFF_SECS)
+}
+
+fn delivery_status_str(status: DeliveryStatus) -> &'static str {
+    match status {
+        DeliveryStatus::Pending => "pending",
+        DeliveryStatus::Delivered => "delivered",
+        DeliveryStatus::Dead => "dead",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::HeaderMap, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    fn webhook(url: String, format: WebhookFormat) -> Webhook {
+        Webhook {
+            webhook_id: "wh-1".to_string(),
+            name: "test".to_string(),
+            url,
+            format,
+            secret: "secret".to_string(),
+            min_severity: Severity::Info,
+            session_ids: Vec::new(),
+            enabled: true,
+            created_by: "admin".to_string(),
+            created_at: 0,
+        }
+    }
+
+    fn alert(severity: Severity) -> Alert {
+        Alert {
+            alert_id: "al-1".to_string(),
+            rule_id: "rule-1".to_string(),
+            rule_name: "Blocked sites".to_string(),
+            severity,
+            session_id: "s-1".to_string(),
+            student_id: "st-1".to_string(),
+            status: AlertStatus::Open,
+            message: "5 blocked requests".to_string(),
+            first_at: 0,
+            last_at: 0,
+            count: 1,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            resolved_by: None,
+            resolved_at: None,
+            note: None,
+        }
+    }
+
+    fn delivery(attempts: i64) -> WebhookDelivery {
+        WebhookDelivery {
+            delivery_id: "dl-1".to_string(),
+            webhook_id: "wh-1".to_string(),
+            alert_id: "al-1".to_string(),
+            body: r#"{"hello":"world"}"#.to_string(),
+            status: DeliveryStatus::Pending,
+            attempts,
+            next_attempt_at: 0,
+            last_error: None,
+            created_at: 0,
+            delivered_at: None,
+        }
+    }
+
+    // serves the router on a free local port, returns its base url
+    async fn receiver(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn sign_matches_known_vector() {
+        assert_eq!(
+            sign("secret", 1_700_000_000, r#"{"hello":"world"}"#),
+            "sha256=654f06c856baf080af3fa272934823257a542d35cf1f88099338f850a60601a4"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_secs(0), 10);
+        assert_eq!(backoff_secs(1), 10);
+        assert_eq!(backoff_secs(2), 20);
+        assert_eq!(backoff_secs(3), 40);
+        assert_eq!(backoff_secs(9), 2560);
+        assert_eq!(backoff_secs(10), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(1000), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn generic_payload_carries_event_and_alert() {
+        let hook = webhook("http://localhost".to_string(), WebhookFormat::Generic);
+        let body = payload(&hook, "alert.opened", &alert(Severity::Warning));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["event"], "alert.opened");
+        assert_eq!(body["alert"]["alert_id"], "al-1");
+        assert_eq!(body["alert"]["severity"], "warning");
+    }
+
+    #[test]
+    fn slack_payload_is_one_text_line_per_alert() {
+        let hook = webhook("http://localhost".to_string(), WebhookFormat::Slack);
+        let body = payload(&hook, "alert.opened", &alert(Severity::Critical));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body["text"],
+            ":rotating_light: *CRITICAL* Blocked sites: student `st-1` in session `s-1`\n5 blocked requests"
+        );
+        assert_eq!(body.as_object().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn delivered_request_is_signed() {
+        let seen: Arc<Mutex<Option<(HeaderMap, String)>>> = Arc::default();
+        let store = seen.clone();
+        let app = Router::new().route(
+            "/",
+            post(move |headers: HeaderMap, body: String| async move {
+                *store.lock().unwrap() = Some((headers, body));
+                StatusCode::OK
+            }),
+        );
+        let hook = webhook(receiver(app).await, WebhookFormat::Generic);
+        let (update, delivered) = attempt(Some(&hook), &delivery(0)).await;
+        assert!(delivered);
+        assert_eq!(update.get_document("$set").unwrap().get_str("status").unwrap(), "delivered");
+
+        let (headers, body) = seen.lock().unwrap().take().expect("receiver got the delivery");
+        assert_eq!(body, r#"{"hello":"world"}"#);
+        assert_eq!(headers["X-BlackIce-Delivery"], "dl-1");
+        let timestamp: i64 = headers["X-BlackIce-Timestamp"].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers["X-BlackIce-Signature"], sign("secret", timestamp, &body).as_str());
+    }
+
+    #[tokio::test]
+    async fn failing_receiver_is_retried_then_dead() {
+        let app = Router::new().route("/", post(|| async { StatusCode::INTERNAL_SERVER_ERROR }));
+        let hook = webhook(receiver(app).await, WebhookFormat::Generic);
+
+        let (update, delivered) = attempt(Some(&hook), &delivery(0)).await;
+        assert!(!delivered);
+        let set = update.get_document("$set").unwrap();
+        assert!(set.get("status").is_none());
+        assert_eq!(set.get_i64("attempts").unwrap(), 1);
+        assert!(set.get_str("last_error").unwrap().contains("500"));
+
+        let (update, delivered) = attempt(Some(&hook), &delivery(MAX_ATTEMPTS - 1)).await;
+        assert!(!delivered);
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_str("status").unwrap(), "dead");
+        assert_eq!(set.get_i64("attempts").unwrap(), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn removed_webhook_is_dead_at_once() {
+        let (update, delivered) = attempt(None, &delivery(0)).await;
+        assert!(!delivered);
+        assert_eq!(update.get_document("$set").unwrap().get_str("status").unwrap(), "dead");
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new()
+            .route("/", post(|| async { (StatusCode::TEMPORARY_REDIRECT, [("location", "/elsewhere")]) }))
+            .route(
+                "/elsewhere",
+                post(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    StatusCode::OK
+                }),
+            );
+        let hook = webhook(receiver(app).await, WebhookFormat::Generic);
+        let (_, delivered) = attempt(Some(&hook), &delivery(0)).await;
+        assert!(!delivered);
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+}